@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use dmds::IoHandle;
+use tracing::{error, info, warn};
+
+use crate::{mail, paper, Global};
+
+/// State of alerting admins about the pending queue.
+///
+/// An alert is sent once the queue crosses the threshold, and is
+/// sent again only after the queue drops below and re-crosses it,
+/// or after the cooldown if the queue stays above.
+#[derive(Debug, Default)]
+pub struct PendingAlert {
+    /// Time of the last alert while the queue stays above the threshold.
+    last: Option<Instant>,
+}
+
+impl PendingAlert {
+    /// Records the given pending count, returning whether to alert.
+    pub fn hit(&mut self, pending: usize, threshold: usize, cooldown: Duration) -> bool {
+        let now = Instant::now();
+        if pending <= threshold {
+            self.last = None;
+            return false;
+        }
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < cooldown)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Counts pending papers, and alerts the admin if the queue
+/// is over the threshold, returning whether an alert was sent.
+pub async fn check<Io: IoHandle>(state: &Global<Io>, alert: &mut PendingAlert) -> bool {
+    let config = &state.config;
+    let (Some(to), Some(mailer), Some(smtp)) =
+        (&config.admin_alert_email, &state.mailer, &config.smtp)
+    else {
+        return false;
+    };
+
+    let pending = paper::count(&state.papers, &state.metrics, paper::Status::Pending).await;
+    if !alert.hit(
+        pending,
+        config.alert_pending_threshold,
+        Duration::from_secs(config.alert_cooldown_secs),
+    ) {
+        return false;
+    }
+
+    warn!("{pending} papers are pending, alerting {to}");
+    let msg = match mail::pending_alert(
+        smtp.from.clone(),
+        to.clone(),
+        pending,
+        config.board_title.as_deref(),
+    ) {
+        Ok(msg) => msg,
+        Err(err) => {
+            error!("failed to build alert email: {err}");
+            return false;
+        }
+    };
+    if let Err(err) = mailer.send(msg).await {
+        error!("failed to send alert email: {err}");
+        return false;
+    }
+    true
+}
+
+/// Checks the pending queue periodically.
+pub async fn daemon<Io: IoHandle>(state: Global<Io>, interval: Duration) {
+    let mut alert = PendingAlert::default();
+    let mut interval = tokio::time::interval(interval);
+    info!(
+        "alerting admins of over {} pending papers",
+        state.config.alert_pending_threshold
+    );
+    loop {
+        interval.tick().await;
+        check(&state, &mut alert).await;
+    }
+}
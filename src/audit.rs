@@ -0,0 +1,249 @@
+use axum::extract::{Query, State};
+use bincode::Options as _;
+use chrono::{DateTime, Utc};
+use dmds::IoHandle;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    bincode_options,
+    json::Json,
+    pid::Pid,
+    scan::{self, Scan},
+    secret::Actor,
+    Global, Metrics,
+};
+
+/// Count of ids per chunk of the audit log, which is about 51 days
+/// of entries, so there are 1024 chunks in total.
+pub const ITEMS_PER_CHUNK: u64 = 1 << 54;
+
+/// Bits of randomness in the lower end of ids.
+const RANDOM_BITS: u32 = 12;
+
+/// Moderation actions recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Approve,
+    Reject,
+    Restore,
+    Prioritize,
+    Deprioritize,
+    ApproveAll,
+    RejectRange,
+    ApproveByContent,
+    RejectByContent,
+    PurgeAll,
+}
+
+impl Action {
+    /// Whether this action approves papers.
+    #[inline]
+    pub fn is_approval(self) -> bool {
+        matches!(
+            self,
+            Action::Approve | Action::ApproveAll | Action::ApproveByContent
+        )
+    }
+}
+
+/// Entry of the audit log, which is never mutated or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Only identifier of this entry, ordered by time.
+    pub id: u64,
+    pub action: Action,
+    /// Pid of the affected paper, or `0` for actions of all papers.
+    pub pid: Pid,
+    /// Label of the management secret authorizing the action,
+    /// as of [`Actor::as_str`].
+    pub actor: String,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV1 {
+    action: Action,
+    pid: Pid,
+    actor: String,
+    time: DateTime<Utc>,
+}
+
+/// Id of entries at the given time, with random lower bits, so ids
+/// are ordered by time and time ranges are selected by ids.
+fn id_of(time: DateTime<Utc>, random: u64) -> u64 {
+    let micros = time.timestamp_micros().max(0) as u64;
+    (micros << RANDOM_BITS) | (random & ((1 << RANDOM_BITS) - 1))
+}
+
+impl dmds::Data for AuditEntry {
+    const DIMS: usize = 1;
+    const VERSION: u32 = 1;
+
+    fn dim(&self, dim: usize) -> u64 {
+        match dim {
+            0 => self.id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        match version {
+            1 => {
+                let inner: StoreV1 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    id: dims[0],
+                    action: inner.action,
+                    pid: inner.pid,
+                    actor: inner.actor,
+                    time: inner.time,
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        bincode_options()
+            .serialize_into(
+                buf.writer(),
+                &StoreV1 {
+                    action: self.action,
+                    pid: self.pid,
+                    actor: self.actor.clone(),
+                    time: self.time,
+                },
+            )
+            .map_err(std::io::Error::other)
+    }
+}
+
+/// Appends an entry of the given action to the audit log.
+///
+/// Failures are logged rather than failing the action,
+/// which is done already.
+pub async fn record<Io: IoHandle>(
+    audit: &dmds::World<AuditEntry, 1, Io>,
+    action: Action,
+    pid: Pid,
+    actor: Actor,
+) {
+    let time = Utc::now();
+    let mut entry = AuditEntry {
+        id: id_of(time, fastrand::u64(..)),
+        action,
+        pid,
+        actor: actor.as_str().to_owned(),
+        time,
+    };
+    // ids of the same microsecond conflict only by their random bits
+    for _ in 0..4 {
+        match audit.try_insert(entry).await {
+            Ok(()) => return,
+            Err(e) => {
+                entry = e;
+                entry.id = id_of(time, fastrand::u64(..));
+            }
+        }
+    }
+    error!("failed to record {action:?} of paper {pid} in the audit log");
+}
+
+/// Approvals recorded at or after the given time, as pids and times
+/// of approved papers, sorted by time.
+///
+/// This is bounded by [`crate::Config::max_scan`] the same as [`query`].
+pub async fn approvals_since<Io: IoHandle>(
+    audit: &dmds::World<AuditEntry, 1, Io>,
+    metrics: &Metrics,
+    since: DateTime<Utc>,
+    max_scan: Option<usize>,
+) -> (Vec<(Pid, DateTime<Utc>)>, Scan) {
+    let from = id_of(since, 0);
+    let mut ret = Vec::new();
+    let scan = scan::scan_while(
+        audit,
+        ITEMS_PER_CHUNK,
+        from..=u64::MAX,
+        max_scan,
+        |id, entry| {
+            if let Some(entry) = metrics.read(id, entry) {
+                if entry.id >= from && entry.action.is_approval() {
+                    ret.push((entry.id, entry.pid, entry.time));
+                }
+            }
+            true
+        },
+    )
+    .await;
+    ret.sort_unstable();
+    (
+        ret.into_iter().map(|(_, pid, time)| (pid, time)).collect(),
+        scan,
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Pid of the paper to query entries of, or all entries if absent.
+    #[serde(default)]
+    pub pid: Option<Pid>,
+    /// Queries entries at or after this time.
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Queries entries at or before this time.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Id to continue a truncated scan from.
+    #[serde(default)]
+    pub from: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditRes {
+    pub entries: Vec<AuditEntry>,
+    #[serde(flatten)]
+    pub scan: Scan,
+}
+
+/// Queries the audit log by pid and time, sorted by time.
+///
+/// Only chunks in the time range are scanned, bounded by
+/// [`crate::Config::max_scan`].
+pub async fn query<Io: IoHandle>(
+    State(Global {
+        audit,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(query): Query<AuditQuery>,
+) -> Json<AuditRes> {
+    let from = query
+        .since
+        .map_or(query.from, |since| id_of(since, 0).max(query.from));
+    let until = query.until.map_or(u64::MAX, |until| id_of(until, u64::MAX));
+    let mut entries = Vec::new();
+    let scan = scan::scan_while(
+        &audit,
+        ITEMS_PER_CHUNK,
+        from..=until,
+        config.max_scan,
+        |id, entry| {
+            if let Some(entry) = metrics.read(id, entry) {
+                if (from..=until).contains(&entry.id)
+                    && query.pid.is_none_or(|pid| entry.pid == pid)
+                {
+                    entries.push(entry.clone());
+                }
+            }
+            true
+        },
+    )
+    .await;
+    entries.sort_by_key(|entry| entry.id);
+    Json(AuditRes { entries, scan })
+}
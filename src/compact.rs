@@ -0,0 +1,206 @@
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use dmds::{IoHandle, StreamExt};
+use dmds_tokio_fs::FsHandle;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{cooldown, json::Json, locale::Localize, request::ErrContext, Global};
+
+/// Minimum interval between two compactions.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// IO handles able to write chunk buffers back to their storage.
+pub trait FlushChunk: IoHandle {
+    /// Writes the given chunk buffer to the storage, replacing
+    /// the stored chunk.
+    fn flush_chunk<T: dmds::Data, const DIMS: usize>(
+        &self,
+        chunk: &dmds::Chunk<T, DIMS>,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+impl FlushChunk for FsHandle {
+    #[inline]
+    fn flush_chunk<T: dmds::Data, const DIMS: usize>(
+        &self,
+        chunk: &dmds::Chunk<T, DIMS>,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        self.write_chunk(chunk)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("internal database error")]
+    Db,
+    #[error("compacted too frequently, retry after {0} seconds")]
+    Cooldown(u64),
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Db => "数据库内部错误".to_owned(),
+            Error::Cooldown(secs) => format!("压缩过于频繁，请在 {secs} 秒后重试"),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        match self {
+            Error::Db => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(JErr {
+                    error: self.localized(),
+                    context: ErrContext::current(),
+                }),
+            )
+                .into_response(),
+            Error::Cooldown(secs) => cooldown::rate_limited(secs, self.localized()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompactRes {
+    /// Size of the database directory before compaction, in bytes.
+    pub before: u64,
+    /// Size of the database directory after compaction, in bytes.
+    pub after: u64,
+    /// Count of chunks written.
+    pub chunks: usize,
+}
+
+/// Loads and rewrites every chunk of both worlds, dropping moved
+/// and removed records from the storage.
+///
+/// Compactions are allowed at most once per [`MIN_INTERVAL`].
+pub async fn compact<Io: FlushChunk>(
+    State(Global {
+        config,
+        papers,
+        questions,
+        compact_cooldown,
+        ..
+    }): State<Global<Io>>,
+) -> Result<Json<CompactRes>, Error> {
+    compact_cooldown
+        .hit(&(), MIN_INTERVAL)
+        .map_err(Error::Cooldown)?;
+
+    let before = dir_size(config.db_path.clone()).await;
+    let chunks = flush_world(&papers).await? + flush_world(&questions).await?;
+    let after = dir_size(config.db_path.clone()).await;
+    info!("compacted {chunks} chunks, from {before} to {after} bytes");
+
+    Ok(Json(CompactRes {
+        before,
+        after,
+        chunks,
+    }))
+}
+
+/// Loads all chunks of the given world and writes them back,
+/// returning the count of chunks written.
+///
+/// Iterating a world reads unbuffered chunks without buffering them,
+/// so their positions are collected from their records and they are
+/// loaded before writing. dmds drops records failed to decode while
+/// loading, and loads chunks failed to read as empty ones, so either
+/// fails the compaction instead of losing records.
+async fn flush_world<T: dmds::Data, const DIMS: usize, Io: FlushChunk>(
+    world: &dmds::World<T, DIMS, Io>,
+) -> Result<usize, Error> {
+    let mut stored = BTreeSet::new();
+    {
+        let select = world.select_all();
+        let mut iter = select.iter();
+        while let Some(lazy) = iter.next().await {
+            let lazy = lazy.map_err(|err| {
+                error!("failed to read chunk for compaction: {err}");
+                Error::Db
+            })?;
+            match lazy.get().await {
+                Ok(value) => {
+                    if let Ok(pos) = world.chunk_pos_of_data(value) {
+                        stored.insert(pos);
+                    }
+                }
+                Err(dmds::Error::Io(err)) => {
+                    error!(
+                        "failed to decode record {} for compaction: {err}",
+                        lazy.id()
+                    );
+                    return Err(Error::Db);
+                }
+                // moved records are in buffered chunks
+                Err(_) => {}
+            }
+        }
+    }
+
+    for pos in stored {
+        if world.chunk_buf_of_pos(pos).is_some() {
+            continue;
+        }
+        match world.io_handle().read_chunk(pos).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                error!("failed to read chunk {pos:?} for compaction: {err}");
+                return Err(Error::Db);
+            }
+        }
+        world.chunk_buf_of_pos_or_load(pos).await.map_err(|err| {
+            error!("failed to load chunk {pos:?} for compaction: {err}");
+            Error::Db
+        })?;
+    }
+
+    let chunks: Vec<_> = world.chunks().map(|chunk| chunk.value().clone()).collect();
+    for chunk in &chunks {
+        world.io_handle().flush_chunk(chunk).await.map_err(|err| {
+            error!("failed to write chunk {:?}: {err}", chunk.pos());
+            Error::Db
+        })?;
+    }
+    Ok(chunks.len())
+}
+
+/// Sums sizes of all files under the given directory,
+/// treating unreadable entries as empty.
+pub async fn dir_size(path: PathBuf) -> u64 {
+    fn walk(path: &Path) -> u64 {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .map(|entry| match entry.metadata() {
+                Ok(meta) if meta.is_dir() => walk(&entry.path()),
+                Ok(meta) => meta.len(),
+                Err(_) => 0,
+            })
+            .sum()
+    }
+
+    tokio::task::spawn_blocking(move || walk(&path))
+        .await
+        .unwrap_or_default()
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{json::Json, request::ErrContext};
+
+/// Machine-readable code of rate-limited responses.
+pub const RATE_LIMITED: &str = "rate_limited";
+
+/// Response of a rate-limited client, shared by all limiters so
+/// clients handle them the same.
+///
+/// The body carries the seconds to wait and a localized message
+/// to display directly, also sent as the `Retry-After` header.
+pub fn rate_limited(retry_after_secs: u64, message: String) -> Response {
+    #[derive(Serialize)]
+    struct JErr {
+        code: &'static str,
+        retry_after_secs: u64,
+        message: String,
+        #[serde(flatten)]
+        context: ErrContext,
+    }
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs)],
+        Json(JErr {
+            code: RATE_LIMITED,
+            retry_after_secs,
+            message,
+            context: ErrContext::current(),
+        }),
+    )
+        .into_response()
+}
+
+/// Cooldowns per key, such as the email address of submissions.
+///
+/// Expired entries are pruned on every hit, so the map only
+/// holds keys hit within the cooldown.
+#[derive(Debug)]
+pub struct Cooldowns<K> {
+    last: Mutex<HashMap<K, Instant>>,
+}
+
+/// Submission cooldowns per email address.
+pub type EmailCooldown = Cooldowns<lettre::Address>;
+
+impl<K> Default for Cooldowns<K> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            last: Mutex::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> Cooldowns<K> {
+    /// Records a hit of the given key, or returns the remaining
+    /// cooldown in seconds, rounded up, if the key is still
+    /// cooling down.
+    ///
+    /// A zero cooldown is always passed.
+    pub fn hit(&self, key: &K, cooldown: Duration) -> Result<(), u64> {
+        if cooldown.is_zero() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut last = self.last.lock().unwrap();
+        last.retain(|_, time| now.duration_since(*time) < cooldown);
+        if let Some(time) = last.get(key) {
+            let remaining = cooldown - now.duration_since(*time);
+            return Err(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0));
+        }
+        last.insert(key.clone(), now);
+        Ok(())
+    }
+
+    /// Clears the cooldown of the given key, for hits of
+    /// requests failed afterwards, which should not count.
+    pub fn clear(&self, key: &K) {
+        self.last.lock().unwrap().remove(key);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fmt, io, sync::OnceLock};
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::pid::Pid;
+
+/// Length of encryption keys in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Length of nonces of sealed fields in bytes.
+const NONCE_LEN: usize = 24;
+
+/// Cipher of records at rest, set once at startup.
+static CIPHER: OnceLock<Cipher> = OnceLock::new();
+
+/// Cipher sealing personal fields of records at rest.
+#[derive(Clone)]
+pub struct Cipher(XChaCha20Poly1305);
+
+impl Cipher {
+    /// Parses a cipher from a key of [`KEY_LEN`] bytes in hex.
+    pub fn from_hex(key: &str) -> Result<Self, String> {
+        if key.len() != KEY_LEN * 2 || !key.is_ascii() {
+            return Err(format!("key should be {} hex digits", KEY_LEN * 2));
+        }
+        let key = (0..key.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&key[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("key should be hex digits: {err}"))?;
+        XChaCha20Poly1305::new_from_slice(&key)
+            .map(Self)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl fmt::Debug for Cipher {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cipher(..)")
+    }
+}
+
+/// Sets the cipher of records encoded from now on, which is
+/// ignored if set already.
+pub fn init(cipher: Cipher) {
+    let _ = CIPHER.set(cipher);
+}
+
+/// Cipher of records, if configured.
+#[inline]
+pub fn cipher() -> Option<&'static Cipher> {
+    CIPHER.get()
+}
+
+/// Email address stored at rest, sealed if a cipher is configured.
+///
+/// Plain addresses are kept readable after a key is configured,
+/// and are sealed once their records are encoded again.
+///
+/// Addresses are stored as strings, since [`lettre::Address`]
+/// requires self-describing formats to deserialize.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum StoredEmail<S = String> {
+    Plain(S),
+    Sealed {
+        nonce: [u8; NONCE_LEN],
+        ciphertext: Vec<u8>,
+    },
+}
+
+/// Seals the given email of the record of the given pid, or keeps it
+/// plain without a cipher.
+///
+/// The pid is authenticated along, so sealed emails can't be moved
+/// between records.
+pub fn seal<'a>(
+    cipher: Option<&Cipher>,
+    pid: Pid,
+    email: &'a lettre::Address,
+) -> io::Result<StoredEmail<&'a str>> {
+    let Some(Cipher(cipher)) = cipher else {
+        return Ok(StoredEmail::Plain(email.as_ref()));
+    };
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: AsRef::<str>::as_ref(email).as_bytes(),
+                aad: &pid.0.to_le_bytes(),
+            },
+        )
+        .map_err(|_| io::Error::other("failed to seal email"))?;
+    Ok(StoredEmail::Sealed {
+        nonce: nonce.into(),
+        ciphertext,
+    })
+}
+
+/// Opens the given stored email of the record of the given pid.
+///
+/// Sealed emails fail to open without the cipher sealing them.
+pub fn open(cipher: Option<&Cipher>, pid: Pid, email: StoredEmail) -> io::Result<lettre::Address> {
+    let (nonce, ciphertext) = match email {
+        StoredEmail::Plain(email) => return parse(email.into_bytes()),
+        StoredEmail::Sealed { nonce, ciphertext } => (nonce, ciphertext),
+    };
+    let Some(Cipher(cipher)) = cipher else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "sealed email requires an encryption key",
+        ));
+    };
+    let plain = cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: &pid.0.to_le_bytes(),
+            },
+        )
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "failed to open sealed email, is the encryption key changed?",
+            )
+        })?;
+    parse(plain)
+}
+
+fn parse(email: Vec<u8>) -> io::Result<lettre::Address> {
+    String::from_utf8(email)
+        .ok()
+        .and_then(|email| email.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid stored email"))
+}
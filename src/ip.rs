@@ -0,0 +1,85 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use dmds::IoHandle;
+use ipnetwork::IpNetwork;
+
+use crate::Global;
+
+/// Header set by reverse proxies containing the original client address.
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Header set by Cloudflare containing the original client address.
+const CF_CONNECTING_IP: &str = "cf-connecting-ip";
+
+/// IP address of the client who sent the request.
+///
+/// The address is `None` if it's not available,
+/// for example the server is not serving with connect info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<Io: IoHandle> FromRequestParts<Global<Io>> for ClientIp {
+    type Rejection = Infallible;
+
+    #[inline]
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Global<Io>,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(client_ip(parts, &state.config.trusted_proxies)))
+    }
+}
+
+/// Resolves the client address of a request.
+///
+/// Forwarding headers are only respected if the direct peer is a
+/// trusted proxy, so they can't be spoofed by clients. In that case,
+/// the rightmost untrusted hop of `X-Forwarded-For` is taken, or
+/// `CF-Connecting-IP` if there is no `X-Forwarded-For`.
+pub fn client_ip(parts: &Parts, trusted_proxies: &[IpNetwork]) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+    if !is_trusted(peer) {
+        return Some(peer);
+    }
+
+    let forwarded = parts
+        .headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .map(|val| val.to_str().ok())
+        .collect::<Option<Vec<_>>>()
+        .and_then(|vals| {
+            vals.iter()
+                .flat_map(|val| val.split(','))
+                .map(|hop| hop.trim().parse::<IpAddr>().ok())
+                .collect::<Option<Vec<_>>>()
+        })
+        .unwrap_or_default();
+    if let Some(&leftmost) = forwarded.first() {
+        return Some(
+            forwarded
+                .into_iter()
+                .rev()
+                .find(|&hop| !is_trusted(hop))
+                .unwrap_or(leftmost),
+        );
+    }
+
+    parts
+        .headers
+        .get(CF_CONNECTING_IP)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.trim().parse().ok())
+        .or(Some(peer))
+}
@@ -0,0 +1,226 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{
+        rejection::{BytesRejection, JsonRejection},
+        FromRequest, Request,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::request::ErrContext;
+
+/// Maximum nesting depth of JSON bodies.
+///
+/// Bodies are flat objects, so this only guards against
+/// pathological inputs burning CPU in deserialization.
+pub const MAX_DEPTH: usize = 32;
+
+/// JSON extractor and response.
+///
+/// This works the same as [`axum::Json`], except that rejections
+/// are responded with JSON error bodies, and bodies nested deeper
+/// than [`MAX_DEPTH`] are rejected before deserialization.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(Rejection::Bytes)?;
+        if exceeds_depth(&bytes, MAX_DEPTH) {
+            return Err(Rejection::TooDeep);
+        }
+
+        axum::Json::from_request(Request::from_parts(parts, Body::from(bytes)), state)
+            .await
+            .map(|axum::Json(value)| Self(value))
+            .map_err(Rejection::Json)
+    }
+}
+
+/// Whether nesting of the given JSON exceeds the given depth,
+/// skipping brackets in strings.
+///
+/// Malformed JSON is left for the deserializer to reject.
+fn exceeds_depth(json: &[u8], max: usize) -> bool {
+    let mut depth = 0_usize;
+    let mut in_str = false;
+    let mut escaped = false;
+    for &byte in json {
+        if in_str {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_str = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_str = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    #[inline]
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Rejection of the [`Json`] extractor.
+#[derive(Debug)]
+pub enum Rejection {
+    Json(JsonRejection),
+    Bytes(BytesRejection),
+    TooDeep,
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        let (status, error) = match self {
+            Rejection::Json(JsonRejection::MissingJsonContentType(rejection)) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, rejection.body_text())
+            }
+            Rejection::Json(
+                rejection @ (JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_)),
+            ) => (StatusCode::BAD_REQUEST, rejection.body_text()),
+            Rejection::Json(rejection) => (rejection.status(), rejection.body_text()),
+            Rejection::Bytes(rejection) => (rejection.status(), rejection.body_text()),
+            Rejection::TooDeep => (
+                StatusCode::BAD_REQUEST,
+                format!("JSON is nested deeper than {MAX_DEPTH} levels"),
+            ),
+        };
+        (
+            status,
+            axum::Json(JErr {
+                error,
+                context: ErrContext::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Incremental splitter of a JSON array into the raw bytes of its
+/// elements, so that large arrays are parsed one element at a time.
+///
+/// Elements are not validated here, and are left for the deserializer.
+#[derive(Debug, Default)]
+pub struct Elements {
+    pos: ArrayPos,
+    depth: usize,
+    in_str: bool,
+    escaped: bool,
+    buf: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ArrayPos {
+    #[default]
+    Before,
+    Inside,
+    After,
+}
+
+/// Error of splitting a JSON array.
+#[derive(Debug, thiserror::Error)]
+pub enum SplitError {
+    #[error("expected a JSON array")]
+    NotArray,
+    #[error("JSON is nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+    #[error("unexpected data after the JSON array")]
+    TrailingData,
+    #[error("JSON array is truncated")]
+    Truncated,
+}
+
+impl Elements {
+    /// Feeds a chunk of the array, pushing completed elements to `out`.
+    pub fn feed(&mut self, chunk: &[u8], out: &mut Vec<Vec<u8>>) -> Result<(), SplitError> {
+        for &byte in chunk {
+            match self.pos {
+                ArrayPos::Before => match byte {
+                    b'[' => self.pos = ArrayPos::Inside,
+                    _ if byte.is_ascii_whitespace() => {}
+                    _ => return Err(SplitError::NotArray),
+                },
+                ArrayPos::After if byte.is_ascii_whitespace() => {}
+                ArrayPos::After => return Err(SplitError::TrailingData),
+                ArrayPos::Inside if self.in_str => {
+                    match byte {
+                        _ if self.escaped => self.escaped = false,
+                        b'\\' => self.escaped = true,
+                        b'"' => self.in_str = false,
+                        _ => {}
+                    }
+                    self.buf.push(byte);
+                }
+                ArrayPos::Inside => match byte {
+                    b',' if self.depth == 0 => out.push(std::mem::take(&mut self.buf)),
+                    b']' if self.depth == 0 => {
+                        if !self.buf.iter().all(u8::is_ascii_whitespace) {
+                            out.push(std::mem::take(&mut self.buf));
+                        }
+                        self.pos = ArrayPos::After;
+                    }
+                    _ if self.depth == 0 && self.buf.is_empty() && byte.is_ascii_whitespace() => {}
+                    _ => {
+                        match byte {
+                            b'"' => self.in_str = true,
+                            b'{' | b'[' => {
+                                self.depth += 1;
+                                // the array itself takes a level
+                                if self.depth >= MAX_DEPTH {
+                                    return Err(SplitError::TooDeep);
+                                }
+                            }
+                            b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                            _ => {}
+                        }
+                        self.buf.push(byte);
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes splitting, failing if the array was not closed.
+    pub fn finish(self) -> Result<(), SplitError> {
+        match self.pos {
+            ArrayPos::Before => Err(SplitError::NotArray),
+            ArrayPos::Inside => Err(SplitError::Truncated),
+            ArrayPos::After => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Chunk layout of worlds, which controls how data is sharded on disk.
+///
+/// The layout is persisted to a marker file along with the data,
+/// as opening data with a different layout corrupts it silently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    /// Binary logarithm of the count of chunks in pid dimension of papers.
+    pub papers_chunk_bits: u32,
+    /// Binary logarithm of the count of chunks in pid dimension of questions.
+    pub questions_chunk_bits: u32,
+}
+
+impl Default for Layout {
+    #[inline]
+    fn default() -> Self {
+        Self::LEGACY
+    }
+}
+
+impl Layout {
+    /// Layout used before it was configurable.
+    pub const LEGACY: Self = Self {
+        papers_chunk_bits: 5,
+        questions_chunk_bits: 4,
+    };
+
+    /// Maximum count of chunk bits.
+    const MAX_CHUNK_BITS: u32 = 16;
+
+    /// Name of the marker file in the database directory.
+    const MARKER: &'static str = "layout.toml";
+
+    /// Count of items per chunk in pid dimension of papers.
+    #[inline]
+    pub fn papers_items_per_chunk(&self) -> u64 {
+        1 << (u64::BITS - self.papers_chunk_bits)
+    }
+
+    /// Count of items per chunk in pid dimension of questions.
+    #[inline]
+    pub fn questions_items_per_chunk(&self) -> u64 {
+        1 << (u64::BITS - self.questions_chunk_bits)
+    }
+
+    /// Checks that counts of chunk bits are in range.
+    pub fn check_bits(&self) -> Result<(), Error> {
+        for bits in [self.papers_chunk_bits, self.questions_chunk_bits] {
+            if !(1..=Self::MAX_CHUNK_BITS).contains(&bits) {
+                return Err(Error::InvalidBits(bits));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks this layout against the marker in the given database directory,
+    /// creating the marker if there is none.
+    ///
+    /// Existing data without a marker is treated as in [`Self::LEGACY`] layout.
+    pub fn check(&self, db_path: &Path) -> Result<(), Error> {
+        self.check_bits()?;
+
+        let marker = db_path.join(Self::MARKER);
+        let persisted = match std::fs::read_to_string(&marker) {
+            Ok(str) => Some(toml::from_str(&str)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let has_data = ["papers", "questions"].iter().any(|dir| {
+                    std::fs::read_dir(db_path.join(dir))
+                        .is_ok_and(|mut entries| entries.next().is_some())
+                });
+                has_data.then_some(Self::LEGACY)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        match persisted {
+            Some(persisted) if persisted != *self => Err(Error::Mismatch {
+                persisted,
+                configured: *self,
+            }),
+            Some(_) if marker.exists() => Ok(()),
+            _ => {
+                std::fs::create_dir_all(db_path)?;
+                std::fs::write(
+                    marker,
+                    toml::to_string(self).expect("layout should be serializable"),
+                )?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to access layout marker: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse layout marker: {0}")]
+    Marker(#[from] toml::de::Error),
+    #[error("chunk bits should be between 1 and {max}, found {0}", max = Layout::MAX_CHUNK_BITS)]
+    InvalidBits(u32),
+    #[error("configured layout {configured:?} mismatches persisted layout {persisted:?}")]
+    Mismatch {
+        persisted: Layout,
+        configured: Layout,
+    },
+}
@@ -0,0 +1,61 @@
+use std::fmt::Display;
+
+use crate::request::Context;
+
+/// Languages of messages shown to users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Zh,
+}
+
+impl Lang {
+    /// Picks the most preferred supported language from the
+    /// value of an `Accept-Language` header, defaulting to English.
+    pub fn from_accept_language(value: &str) -> Self {
+        value
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next()?.trim();
+                let q = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                let primary = tag.split('-').next()?;
+                let lang = if primary.eq_ignore_ascii_case("zh") {
+                    Self::Zh
+                } else if primary.eq_ignore_ascii_case("en") {
+                    Self::En
+                } else {
+                    return None;
+                };
+                (q > 0.0).then_some((lang, q))
+            })
+            // keeps the first of equally preferred languages
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(Self::En, |(lang, _)| lang)
+    }
+
+    /// Language of the current request, or English if not
+    /// handling any request.
+    #[inline]
+    pub fn current() -> Self {
+        Context::with(|cx| cx.lang).unwrap_or_default()
+    }
+}
+
+/// Errors with messages translated from their English [`Display`].
+pub trait Localize: Display {
+    /// Message of this error in Chinese.
+    fn zh(&self) -> String;
+
+    /// Message of this error in the language of the current request.
+    fn localized(&self) -> String {
+        match Lang::current() {
+            Lang::En => self.to_string(),
+            Lang::Zh => self.zh(),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{fmt::Debug, future::Future, path::Path, pin::Pin};
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Deserializer};
+
+use crate::{paper::Paper, Config};
+
+/// Error of sending emails.
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Transport of outgoing emails.
+pub trait Mailer: Debug + Send + Sync {
+    /// Sends the given message.
+    fn send(&self, msg: Message) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + '_>>;
+}
+
+impl Mailer for AsyncSmtpTransport<Tokio1Executor> {
+    fn send(&self, msg: Message) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + '_>> {
+        Box::pin(async move {
+            AsyncTransport::send(self, msg).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Deserializes an optional email address from user input, accepting
+/// both bare addresses and the `Name <address>` form, in which case
+/// only the address is kept.
+///
+/// Blank strings are taken as absent addresses.
+pub fn deserialize_address<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<lettre::Address>, D::Error> {
+    let Some(src) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let src = src.trim();
+    if src.is_empty() {
+        return Ok(None);
+    }
+    src.parse::<Mailbox>()
+        .map(|mailbox| Some(mailbox.email))
+        .map_err(|_| {
+            serde::de::Error::custom(format!(
+                "invalid email address `{src}`, expected `name@example.com` \
+                or `Name <name@example.com>`"
+            ))
+        })
+}
+
+/// SMTP server to send emails through.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Smtp {
+    /// Host of the server, connected with TLS.
+    pub host: String,
+    /// Port of the server, or the default SMTPS port if absent.
+    #[serde(default)]
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: String,
+    /// Sender of emails.
+    pub from: Mailbox,
+}
+
+impl Smtp {
+    /// Creates a transport of this server.
+    pub fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, Error> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?.credentials(
+            Credentials::new(self.username.clone(), self.password.clone()),
+        );
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Built-in template of approval emails.
+const DEFAULT_APPROVAL: &str = "Your paper was approved on {board}
+Hi {name},
+
+Your paper was approved and is now shown on {board}.
+";
+
+/// Field of papers substituted into templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Info,
+    Pid,
+    Board,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// Error of parsing templates.
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("unknown placeholder {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("unclosed placeholder")]
+    Unclosed,
+    #[error("unmatched '}}', which should be escaped as '}}}}'")]
+    Unmatched,
+    #[error("missing subject in the first line")]
+    MissingSubject,
+}
+
+/// Template of notification emails, with the subject in the first line
+/// and the body in the rest.
+///
+/// Placeholders `{name}`, `{info}`, `{pid}` and `{board}` are substituted
+/// by the paper and the board title, and `{{` and `}}` escape braces.
+#[derive(Debug, Clone)]
+pub struct Template {
+    subject: Vec<Segment>,
+    body: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses the given template, rejecting unknown placeholders.
+    pub fn parse(src: &str) -> Result<Self, TemplateError> {
+        let (subject, body) = src.split_once('\n').unwrap_or((src, ""));
+        let subject = subject.trim();
+        if subject.is_empty() {
+            return Err(TemplateError::MissingSubject);
+        }
+        Ok(Self {
+            subject: parse_segments(subject)?,
+            body: parse_segments(body)?,
+        })
+    }
+
+    /// Renders the subject and body of the given paper.
+    pub fn render(&self, paper: &Paper, config: &Config) -> (String, String) {
+        let render = |segments: &[Segment]| {
+            let mut ret = String::new();
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => ret.push_str(text),
+                    Segment::Field(Field::Name) => ret.push_str(&paper.name),
+                    Segment::Field(Field::Info) => ret.push_str(&paper.info),
+                    Segment::Field(Field::Pid) => ret.push_str(&paper.pid.to_string()),
+                    Segment::Field(Field::Board) => {
+                        ret.push_str(config.board_title.as_deref().unwrap_or("the board"))
+                    }
+                }
+            }
+            ret
+        };
+        (render(&self.subject), render(&self.body))
+    }
+}
+
+fn parse_segments(src: &str) -> Result<Vec<Segment>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err(TemplateError::Unmatched),
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(TemplateError::Unclosed),
+                    }
+                }
+                let field = match name.as_str() {
+                    "name" => Field::Name,
+                    "info" => Field::Info,
+                    "pid" => Field::Pid,
+                    "board" => Field::Board,
+                    _ => return Err(TemplateError::UnknownPlaceholder(name)),
+                };
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Field(field));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// Templates of notification emails.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    pub approval: Template,
+    /// Template of rejection emails, which are sent only if configured
+    /// as there is no built-in one.
+    pub rejection: Option<Template>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            approval: Template::parse(DEFAULT_APPROVAL).expect("built-in template should be valid"),
+            rejection: None,
+        }
+    }
+}
+
+impl Templates {
+    /// Loads templates from the configured paths, falling back to
+    /// built-in templates if unset.
+    pub fn load(config: &Config) -> Result<Self, String> {
+        fn load(field: &str, path: &Path) -> Result<Template, String> {
+            let src = std::fs::read_to_string(path)
+                .map_err(|err| format!("{field}: failed to read {}: {err}", path.display()))?;
+            Template::parse(&src).map_err(|err| format!("{field}: {}: {err}", path.display()))
+        }
+
+        let mut templates = Self::default();
+        if let Some(path) = &config.approval_template_path {
+            templates.approval = load("approval_template_path", path)?;
+        }
+        if let Some(path) = &config.rejection_template_path {
+            templates.rejection = Some(load("rejection_template_path", path)?);
+        }
+        Ok(templates)
+    }
+}
+
+/// Builds the email notifying an author of their paper by the given template.
+pub fn notification(
+    from: Mailbox,
+    to: lettre::Address,
+    template: &Template,
+    paper: &Paper,
+    config: &Config,
+) -> Result<Message, lettre::error::Error> {
+    let (subject, body) = template.render(paper, config);
+    Message::builder()
+        .from(from)
+        .to(Mailbox::new(None, to))
+        .subject(subject)
+        .body(body)
+}
+
+/// Builds the email alerting the admin that papers are piling up.
+pub fn pending_alert(
+    from: Mailbox,
+    to: lettre::Address,
+    pending: usize,
+    board_title: Option<&str>,
+) -> Result<Message, lettre::error::Error> {
+    let board = board_title.unwrap_or("the board");
+    Message::builder()
+        .from(from)
+        .to(Mailbox::new(None, to))
+        .subject(format!("{pending} papers are pending on {board}"))
+        .body(format!(
+            "Hi,\n\n{pending} papers are waiting for review on {board}.\n"
+        ))
+}
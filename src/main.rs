@@ -1,19 +1,61 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    http::{header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::{any, get, post, MethodRouter},
     Router,
 };
+use chrono::{DateTime, Utc};
 use dmds::IoHandle;
 use dmds_tokio_fs::FsHandle;
 use paper::Paper;
+use pid::Pid;
 use question::Question;
+use request::ErrContext;
 use serde::Deserialize;
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
-use tracing::info;
+use tokio::sync::broadcast;
+use tower_http::{
+    compression::CompressionLayer, cors::CorsLayer, services::ServeDir,
+    set_header::SetResponseHeaderLayer, timeout::TimeoutLayer, trace::TraceLayer,
+};
+use tracing::{error, info};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
+mod alert;
+mod audit;
+mod compact;
+mod cooldown;
+mod crypt;
+mod ip;
+mod json;
+mod layout;
+mod locale;
+mod mail;
+mod mng;
+mod nonce;
 mod paper;
+mod pid;
+mod public;
 mod question;
+mod request;
+mod retention;
+mod scan;
+mod secret;
+mod server;
+mod sign;
+mod ticker;
+mod version;
+mod views;
 
 #[cfg(test)]
 mod tests;
@@ -23,6 +65,76 @@ pub struct Global<Io: IoHandle> {
     config: Arc<Config>,
     papers: Arc<dmds::World<Paper, 2, Io>>,
     questions: Arc<dmds::World<Question, 1, Io>>,
+    /// Append-only log of moderation actions.
+    audit: Arc<dmds::World<audit::AuditEntry, 1, Io>>,
+    metrics: Arc<Metrics>,
+    paper_events: broadcast::Sender<paper::Event>,
+    /// Whether writes are currently blocked.
+    read_only: Arc<AtomicBool>,
+    email_cooldown: Arc<cooldown::EmailCooldown>,
+    /// Cooldowns of re-sending notification emails per paper.
+    resend_cooldown: Arc<cooldown::Cooldowns<Pid>>,
+    /// Nonces of management mutations seen recently.
+    nonces: Arc<nonce::Nonces>,
+    /// Cooldown of compactions, which are not told apart by any key.
+    compact_cooldown: Arc<cooldown::Cooldowns<()>>,
+    /// Transport of notification emails, or `None` to send no email.
+    mailer: Option<Arc<dyn mail::Mailer>>,
+    /// Templates of notification emails.
+    templates: Arc<mail::Templates>,
+    views: Arc<views::Views>,
+    /// Cache of pids of approved papers for random gets.
+    approved_pids: Arc<paper::ApprovedPids>,
+    /// Pids of recently received papers for duplicate checks.
+    recent_posts: Arc<paper::RecentPosts>,
+    /// Broadcaster of newly approved papers.
+    ticker: Arc<ticker::Ticker>,
+    live: Arc<secret::LiveApp>,
+    /// Time the backend started.
+    started: Instant,
+}
+
+impl<Io: IoHandle> Global<Io> {
+    fn new(
+        config: Arc<Config>,
+        papers: dmds::World<Paper, 2, Io>,
+        questions: dmds::World<Question, 1, Io>,
+        audit: dmds::World<audit::AuditEntry, 1, Io>,
+    ) -> Self {
+        Self {
+            read_only: Arc::new(AtomicBool::new(config.read_only)),
+            live: Arc::new(secret::LiveApp::new(config.clone())),
+            config,
+            papers: Arc::new(papers),
+            questions: Arc::new(questions),
+            audit: Arc::new(audit),
+            metrics: Arc::default(),
+            paper_events: broadcast::channel(PAPER_EVENTS_CAPACITY).0,
+            email_cooldown: Arc::default(),
+            resend_cooldown: Arc::default(),
+            nonces: Arc::default(),
+            compact_cooldown: Arc::default(),
+            mailer: None,
+            templates: Arc::default(),
+            views: Arc::default(),
+            approved_pids: Arc::default(),
+            recent_posts: Arc::default(),
+            ticker: Arc::default(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Sets the transport of notification emails.
+    fn with_mailer(mut self, mailer: Arc<dyn mail::Mailer>) -> Self {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    /// Sets the templates of notification emails.
+    fn with_templates(mut self, templates: mail::Templates) -> Self {
+        self.templates = Arc::new(templates);
+        self
+    }
 }
 
 impl<Io: IoHandle> Clone for Global<Io> {
@@ -32,19 +144,112 @@ impl<Io: IoHandle> Clone for Global<Io> {
             config: self.config.clone(),
             papers: self.papers.clone(),
             questions: self.questions.clone(),
+            audit: self.audit.clone(),
+            metrics: self.metrics.clone(),
+            paper_events: self.paper_events.clone(),
+            read_only: self.read_only.clone(),
+            email_cooldown: self.email_cooldown.clone(),
+            resend_cooldown: self.resend_cooldown.clone(),
+            nonces: self.nonces.clone(),
+            compact_cooldown: self.compact_cooldown.clone(),
+            mailer: self.mailer.clone(),
+            templates: self.templates.clone(),
+            views: self.views.clone(),
+            approved_pids: self.approved_pids.clone(),
+            recent_posts: self.recent_posts.clone(),
+            ticker: self.ticker.clone(),
+            live: self.live.clone(),
+            started: self.started,
+        }
+    }
+}
+
+/// Generates a new random pid.
+///
+/// Pid `0` is reserved and never generated.
+#[inline]
+fn new_pid() -> Pid {
+    Pid(fastrand::u64(1..))
+}
+
+/// Interval between flushes of papers to the storage.
+const PAPERS_FLUSH_INTERVAL: Duration = Duration::from_secs(45);
+/// Interval between flushes of questions to the storage.
+const QUESTIONS_FLUSH_INTERVAL: Duration = Duration::from_secs(120);
+/// Interval between flushes of the audit log to the storage.
+const AUDIT_FLUSH_INTERVAL: Duration = Duration::from_secs(45);
+
+/// Capacity of the new paper events channel.
+const PAPER_EVENTS_CAPACITY: usize = 64;
+
+/// Runtime counters of the backend.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Count of retries caused by pid conflicts.
+    pid_retries: AtomicU64,
+    /// Count of records failed to decode.
+    decode_errors: AtomicU64,
+}
+
+impl Metrics {
+    /// Converts the read result of the given record into an option,
+    /// logging and counting decode errors.
+    ///
+    /// Moved values are ignored, as they are left in their
+    /// previous chunks after changing dimensions.
+    fn read<T>(&self, pid: u64, result: Result<T, dmds::Error>) -> Option<T> {
+        match result {
+            Ok(val) => Some(val),
+            Err(dmds::Error::ValueMoved) => None,
+            Err(err) => {
+                tracing::error!("failed to read record {pid}: {err}");
+                self.decode_errors.fetch_add(1, Ordering::Relaxed);
+                None
+            }
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct Config {
     db_path: PathBuf,
     #[serde(default)]
     log_path: Option<PathBuf>,
     #[serde(default)]
     log_level: Option<String>,
+    #[serde(default)]
+    log_format: LogFormat,
     address: String,
-    static_path: PathBuf,
+    /// Whether to serve HTTP/2 besides HTTP/1.1, which is told apart
+    /// by the preface of connections.
+    #[serde(default = "Config::default_http2")]
+    http2: bool,
+    /// Maximum count of concurrent connections, beyond which new
+    /// connections wait in the backlog, or unlimited if absent.
+    #[serde(default)]
+    max_connections: Option<usize>,
+    /// Idle time of connections before TCP keep-alive probes,
+    /// in seconds, or `0` to disable.
+    #[serde(default = "Config::default_tcp_keepalive_secs")]
+    tcp_keepalive_secs: u64,
+    /// Maximum time to wait for open connections to close on shutdown,
+    /// in seconds, after which they are closed forcibly.
+    #[serde(default = "Config::default_shutdown_drain_secs")]
+    shutdown_drain_secs: u64,
+    /// Directory of static files served for unknown routes,
+    /// or responding JSON `404` if absent.
+    #[serde(default)]
+    static_path: Option<PathBuf>,
+    /// Seconds to cache static assets other than HTML pages,
+    /// or `0` to always revalidate them.
+    ///
+    /// Assets should have content hashes in their file names if set.
+    #[serde(default)]
+    static_cache_secs: u64,
+    /// Networks of trusted reverse proxies, whose forwarding headers
+    /// are respected for client addresses.
+    #[serde(default)]
+    trusted_proxies: Vec<ipnetwork::IpNetwork>,
 
     /// Root secret mapping.
     mng_secret: String,
@@ -55,104 +260,1234 @@ struct Config {
     mng_approve_papers_secret: String,
     /// Secret mapping for management clients to reject papers.
     mng_reject_papers_secret: String,
+    /// Secret mapping for management clients to get questions.
+    mng_get_questions_secret: String,
+    /// Secret mapping for management clients to resolve questions.
+    mng_resolve_questions_secret: String,
+    /// Confirmation for approving all pending papers,
+    /// which is disabled if absent.
+    #[serde(default)]
+    approve_all_confirm: Option<String>,
+    /// Confirmation for rejecting pending papers posted in a time range,
+    /// which is disabled if absent.
+    #[serde(default)]
+    reject_range_confirm: Option<String>,
+    /// Confirmation for removing all papers and questions, which is
+    /// disabled if absent, or unless [`mng::ALLOW_PURGE_ENV`] is set.
+    #[serde(default)]
+    purge_all_confirm: Option<String>,
+    /// Key signing permalinks of papers, which are disabled if absent.
+    ///
+    /// Papers are not served by unsigned pids if present.
+    #[serde(default)]
+    permalink_key: Option<String>,
+    /// Key sealing emails of papers and questions at rest, in hex,
+    /// or absent to store them in plaintext.
+    #[serde(default)]
+    encryption_key: Option<String>,
+    /// Key signing edit tokens returned on posting papers, which let
+    /// authors edit their pending papers, or absent to disable edits.
+    #[serde(default)]
+    edit_key: Option<String>,
+    /// Seconds edit tokens are valid after posting.
+    #[serde(default = "Config::default_edit_grace_secs")]
+    edit_grace_secs: u64,
+    /// Seconds to remember nonces of management mutations, which then
+    /// should carry a nonce unseen in this window, or absent to not
+    /// require nonces.
+    #[serde(default)]
+    nonce_ttl_secs: Option<u64>,
+    /// Maximum count of remembered nonces, beyond which management
+    /// mutations are rejected until older nonces expire.
+    #[serde(default = "Config::default_nonce_capacity")]
+    nonce_capacity: usize,
+
+    /// Maximum length of a paper's name, in characters.
+    #[serde(default = "Config::default_max_name_len")]
+    max_name_len: usize,
+    /// Maximum length of a paper's info, in characters.
+    #[serde(default = "Config::default_max_info_len")]
+    max_info_len: usize,
+    /// Length of previews of a paper's info in management lists,
+    /// in characters.
+    #[serde(default = "Config::default_list_preview_len")]
+    list_preview_len: usize,
+    /// Minimum length of a paper's info, in characters.
+    #[serde(default = "Config::default_min_info_len")]
+    min_info_len: usize,
+    /// Maximum count of papers posted in a batch.
+    #[serde(default = "Config::default_max_batch_len")]
+    max_batch_len: usize,
+    /// Maximum count of links in a paper, or `0` for no limit.
+    #[serde(default = "Config::default_max_links")]
+    max_links: usize,
+    /// Hosts allowed for image URLs of papers, including their subdomains,
+    /// or no image URL is allowed if empty.
+    #[serde(default)]
+    allowed_image_hosts: Vec<String>,
+
+    /// Maximum count of retries when a generated pid conflicts.
+    #[serde(default = "Config::default_pid_retries")]
+    pid_retries: u32,
+    /// Base backoff between pid conflict retries, in milliseconds.
+    ///
+    /// The backoff doubles every retry, with random jitter up to the base.
+    #[serde(default = "Config::default_pid_retry_backoff_ms")]
+    pid_retry_backoff_ms: u64,
+
+    /// Whether to start in read-only mode, blocking all writes.
+    ///
+    /// This can be toggled at runtime by management clients.
+    #[serde(default)]
+    read_only: bool,
+
+    /// Minimum length of management secrets.
+    #[serde(default = "Config::default_min_secret_len")]
+    min_secret_len: usize,
+
+    /// Title of the board shown by frontends.
+    #[serde(default)]
+    board_title: Option<String>,
+    /// Instructions of the board shown by frontends.
+    #[serde(default)]
+    instructions: Option<String>,
+
+    /// IANA timezone in which times are displayed to management clients,
+    /// or UTC if absent. Times are always stored in UTC.
+    #[serde(default)]
+    display_timezone: Option<chrono_tz::Tz>,
+    /// Format of pids in JSON responses. Pids are accepted in
+    /// both formats regardless.
+    #[serde(default)]
+    pid_format: pid::PidFormat,
+
+    /// Whether to count fetches of approved papers by pid as views.
+    #[serde(default)]
+    count_views: bool,
+    /// Interval between flushes of accumulated views, in seconds.
+    #[serde(default = "Config::default_views_flush_interval_secs")]
+    views_flush_interval_secs: u64,
+    /// Whether to respond no paper rather than the excluded one
+    /// if it is the only approved paper.
+    #[serde(default)]
+    strict_exclude: bool,
+    /// Half-life of weights of papers by their age in seconds,
+    /// when getting random papers weighted by recency.
+    #[serde(default = "Config::default_weighted_half_life_secs")]
+    weighted_half_life_secs: u64,
+    /// Seconds to cache pids of approved papers for `/paper/get`,
+    /// or `0` to scan them on every request.
+    #[serde(default = "Config::default_paper_get_cache_secs")]
+    paper_get_cache_secs: u64,
+    /// Maximum count of papers responded by `/paper/recent`.
+    #[serde(default = "Config::default_max_recent")]
+    max_recent: usize,
+    /// Start of the window accepting new papers and questions,
+    /// or open since ever if absent.
+    #[serde(default)]
+    submissions_open_from: Option<DateTime<Utc>>,
+    /// End of the window accepting new papers and questions,
+    /// or open forever if absent.
+    #[serde(default)]
+    submissions_open_until: Option<DateTime<Utc>>,
+    /// Maximum count of records read by a scanning request, such as searches,
+    /// before responding a truncated result. Unlimited if absent.
+    #[serde(default)]
+    max_scan: Option<usize>,
+    /// Paper shown when there is no approved paper.
+    #[serde(default)]
+    default_paper: Option<paper::DefaultPaper>,
+
+    /// Words that are not allowed in submissions.
+    ///
+    /// Matching is case-insensitive and on whole words only.
+    #[serde(default)]
+    banned_words: Vec<String>,
+    /// Whether submissions should carry an email, so authors can be notified.
+    ///
+    /// This takes precedence over anonymity, so anonymous
+    /// submissions should carry an email too.
+    #[serde(default)]
+    require_email: bool,
+    /// Minimum interval between submissions from the same email,
+    /// in seconds, or `0` to disable.
+    #[serde(default)]
+    email_cooldown_secs: u64,
+    /// Minimum interval between re-sends of notification emails of the
+    /// same paper, in seconds, or `0` to disable.
+    #[serde(default = "Config::default_resend_email_cooldown_secs")]
+    resend_email_cooldown_secs: u64,
+    /// Minimum interval between approvals of papers of the same name
+    /// or email, in seconds, or `0` to disable.
+    #[serde(default)]
+    author_approve_interval_secs: u64,
+    /// Window in seconds in which papers of the same content as an
+    /// unrejected paper are rejected as duplicates, or `0` to disable.
+    #[serde(default = "Config::default_dedup_window_secs")]
+    dedup_window_secs: u64,
+    /// Maximum age of times supplied by clients writing papers offline,
+    /// in seconds, or `0` to reject supplied times.
+    #[serde(default = "Config::default_max_submitted_age_secs")]
+    max_submitted_age_secs: u64,
+
+    /// Days to keep rejected papers and resolved questions,
+    /// or forever if absent.
+    #[serde(default)]
+    retention_days: Option<u64>,
+    /// Interval between retention purges, in seconds.
+    #[serde(default = "Config::default_retention_interval_secs")]
+    retention_interval_secs: u64,
+
+    /// Timeout of requests, in milliseconds.
+    #[serde(default = "Config::default_request_timeout_ms")]
+    request_timeout_ms: u64,
+    /// Timeouts of slow management routes, in milliseconds,
+    /// overriding `request_timeout_ms`.
+    ///
+    /// Tunable routes are `list`, `histogram`, `compact`, `import_legacy`,
+    /// `approve_all`, `reject_range` and `purge_all`.
+    #[serde(default)]
+    route_timeouts: HashMap<String, u64>,
+
+    /// SMTP server to send notification emails through,
+    /// or no email is sent if absent.
+    #[serde(default)]
+    smtp: Option<mail::Smtp>,
+    /// Path of the template of approval emails, or the built-in one
+    /// is used if absent. See [`mail::Template`] for the format.
+    #[serde(default)]
+    approval_template_path: Option<PathBuf>,
+    /// Path of the template of rejection emails, or no rejection email
+    /// is sent if absent.
+    #[serde(default)]
+    rejection_template_path: Option<PathBuf>,
+    /// Address alerting the admin of too many pending papers through
+    /// the SMTP server, or no alert is sent if absent.
+    #[serde(default)]
+    admin_alert_email: Option<lettre::Address>,
+    /// Count of pending papers over which the admin is alerted.
+    #[serde(default = "Config::default_alert_pending_threshold")]
+    alert_pending_threshold: usize,
+    /// Minimum interval between alerts while the queue stays over
+    /// the threshold, in seconds.
+    #[serde(default = "Config::default_alert_cooldown_secs")]
+    alert_cooldown_secs: u64,
+    /// Interval between checks of the pending queue, in seconds.
+    #[serde(default = "Config::default_alert_interval_secs")]
+    alert_interval_secs: u64,
+
+    /// Chunk layout of the database.
+    #[serde(flatten)]
+    layout: layout::Layout,
 }
 
-#[tokio::main]
-async fn main() {
-    const CONFIG_PATH: &str = "config.toml";
-    let config: Config = toml::from_str(&{
-        use std::io::Read;
-        let mut str = String::new();
-        std::fs::File::open(CONFIG_PATH)
-            .unwrap()
-            .read_to_string(&mut str)
-            .unwrap();
-        str
-    })
-    .unwrap();
-
-    tracing_subscriber::fmt::init();
-    if let Some(path) = &config.log_path {
-        tracing_subscriber::fmt()
-            .with_max_level(
-                config
-                    .log_level
-                    .as_ref()
-                    .and_then(|str| str.parse::<tracing::Level>().ok())
-                    .unwrap_or(tracing::Level::INFO),
-            )
-            .with_writer(
-                std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(path)
-                    .expect("failed to create log file"),
-            )
-            .init();
+/// Arguments of the command line.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Args {
+    /// Whether to validate the configuration and exit,
+    /// without opening the database or binding the address.
+    check_config: bool,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--check-config" => parsed.check_config = true,
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+        Ok(parsed)
     }
+}
 
-    let mut paper_path = config.db_path.clone();
-    paper_path.push("papers");
-    let mut questions_path = config.db_path.clone();
-    questions_path.push("questions");
-    let config = Arc::new(config);
+/// Problems of the configuration, reported all together.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid configuration:\n{}", .0.join("\n"))]
+struct ConfigError(Vec<String>);
 
-    let state = Global {
-        config: config.clone(),
-        papers: Arc::new(dmds::world! {
-            // 32 chunks, 1 chunk
-            dmds_tokio_fs::FsHandle::new(paper_path, false), 576460752303423488u64 | ..=u64::MAX, 1 | ..=1
-        }),
-        questions: Arc::new(dmds::world! {
-            // 32 chunks
-            dmds_tokio_fs::FsHandle::new(questions_path, true), 1152921504606846976u64 | ..=u64::MAX
-        }),
-    };
+/// Parses the configuration and validates it, collecting all problems.
+///
+/// Only the layout is not checked against the database,
+/// which requires accessing it.
+fn load_config(src: &str) -> Result<Config, ConfigError> {
+    let config: Config = toml::from_str(src).map_err(|err| ConfigError(vec![err.to_string()]))?;
 
-    let router: Router<()> = Router::new()
-        .layer(
-            TraceLayer::new_for_http()
-                .on_request(tower_http::trace::DefaultOnRequest::new().level(tracing::Level::INFO)),
+    let mut errors = Vec::new();
+    if let Err(secrets) = config.check_secrets() {
+        errors.extend(secrets);
+    }
+    if config
+        .permalink_key
+        .as_ref()
+        .is_some_and(|key| key.chars().count() < config.min_secret_len)
+    {
+        errors.push(format!(
+            "permalink_key should be at least {} characters",
+            config.min_secret_len
+        ));
+    }
+    if config
+        .edit_key
+        .as_ref()
+        .is_some_and(|key| key.chars().count() < config.min_secret_len)
+    {
+        errors.push(format!(
+            "edit_key should be at least {} characters",
+            config.min_secret_len
+        ));
+    }
+    if let Some(Err(err)) = config
+        .encryption_key
+        .as_deref()
+        .map(crypt::Cipher::from_hex)
+    {
+        errors.push(format!("encryption_key is invalid: {err}"));
+    }
+    if let Err(err) = config.check_static_path() {
+        errors.push(format!("static_path should be a readable directory: {err}"));
+    }
+    let unknown = config.unknown_route_timeouts();
+    if !unknown.is_empty() {
+        errors.push(format!(
+            "route_timeouts has unknown routes: {}",
+            unknown.join(", ")
+        ));
+    }
+    for (field, value) in [
+        ("request_timeout_ms", config.request_timeout_ms),
+        ("retention_interval_secs", config.retention_interval_secs),
+        ("alert_interval_secs", config.alert_interval_secs),
+        ("weighted_half_life_secs", config.weighted_half_life_secs),
+        (
+            "views_flush_interval_secs",
+            config.views_flush_interval_secs,
+        ),
+        ("edit_grace_secs", config.edit_grace_secs),
+    ] {
+        if value == 0 {
+            errors.push(format!("{field} should not be zero"));
+        }
+    }
+    if config.max_recent == 0 {
+        errors.push("max_recent should not be zero".to_owned());
+    }
+    if config.list_preview_len == 0 {
+        errors.push("list_preview_len should not be zero".to_owned());
+    }
+    if config.max_batch_len == 0 {
+        errors.push("max_batch_len should not be zero".to_owned());
+    }
+    if config.max_connections == Some(0) {
+        errors.push("max_connections should not be zero".to_owned());
+    }
+    if config.nonce_ttl_secs == Some(0) {
+        errors.push("nonce_ttl_secs should not be zero".to_owned());
+    }
+    if config.route_timeouts.values().any(|&timeout| timeout == 0) {
+        errors.push("route_timeouts should not be zero".to_owned());
+    }
+    if let Err(err) = config.layout.check_bits() {
+        errors.push(err.to_string());
+    }
+    if let (Some(from), Some(until)) = (config.submissions_open_from, config.submissions_open_until)
+    {
+        if from > until {
+            errors.push(
+                "submissions_open_from should not be after submissions_open_until".to_owned(),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(config)
+    } else {
+        Err(ConfigError(errors))
+    }
+}
+
+/// Error of submitting outside the configured window.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("submissions are closed{}", .reopen.map_or(String::new(), |time| format!(", reopening at {}", time.to_rfc3339())))]
+pub struct SubmissionsClosed {
+    /// Time when submissions reopen, or `None` if never.
+    pub reopen: Option<DateTime<Utc>>,
+}
+
+impl locale::Localize for SubmissionsClosed {
+    fn zh(&self) -> String {
+        match self.reopen {
+            Some(time) => format!("投稿已关闭，将于 {} 重新开放", time.to_rfc3339()),
+            None => "投稿已关闭".to_owned(),
+        }
+    }
+}
+
+/// Format of logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    /// Human-readable logs.
+    #[default]
+    Pretty,
+    /// Structured JSON logs, with span fields included.
+    Json,
+}
+
+impl Config {
+    #[inline]
+    fn default_views_flush_interval_secs() -> u64 {
+        60
+    }
+
+    #[inline]
+    fn default_request_timeout_ms() -> u64 {
+        30_000
+    }
+
+    #[inline]
+    fn default_weighted_half_life_secs() -> u64 {
+        7 * 86_400
+    }
+
+    #[inline]
+    fn default_paper_get_cache_secs() -> u64 {
+        5
+    }
+
+    #[inline]
+    fn default_max_recent() -> usize {
+        50
+    }
+
+    #[inline]
+    fn default_alert_pending_threshold() -> usize {
+        100
+    }
+
+    #[inline]
+    fn default_alert_cooldown_secs() -> u64 {
+        6 * 3600
+    }
+
+    #[inline]
+    fn default_alert_interval_secs() -> u64 {
+        300
+    }
+
+    #[inline]
+    fn default_dedup_window_secs() -> u64 {
+        86_400
+    }
+
+    #[inline]
+    fn default_max_name_len() -> usize {
+        64
+    }
+
+    #[inline]
+    fn default_max_info_len() -> usize {
+        1024
+    }
+
+    #[inline]
+    fn default_min_info_len() -> usize {
+        10
+    }
+
+    #[inline]
+    fn default_max_batch_len() -> usize {
+        10
+    }
+
+    #[inline]
+    fn default_list_preview_len() -> usize {
+        200
+    }
+
+    #[inline]
+    fn default_max_links() -> usize {
+        3
+    }
+
+    #[inline]
+    fn default_pid_retries() -> u32 {
+        5
+    }
+
+    #[inline]
+    fn default_pid_retry_backoff_ms() -> u64 {
+        10
+    }
+
+    #[inline]
+    fn default_http2() -> bool {
+        true
+    }
+
+    #[inline]
+    fn default_tcp_keepalive_secs() -> u64 {
+        60
+    }
+
+    #[inline]
+    fn default_shutdown_drain_secs() -> u64 {
+        30
+    }
+
+    #[inline]
+    fn default_min_secret_len() -> usize {
+        16
+    }
+
+    #[inline]
+    fn default_edit_grace_secs() -> u64 {
+        300
+    }
+
+    #[inline]
+    fn default_resend_email_cooldown_secs() -> u64 {
+        600
+    }
+
+    #[inline]
+    fn default_max_submitted_age_secs() -> u64 {
+        604_800
+    }
+
+    #[inline]
+    fn default_nonce_capacity() -> usize {
+        10_000
+    }
+
+    #[inline]
+    fn default_retention_interval_secs() -> u64 {
+        3600
+    }
+
+    /// Limit of bodies of batches of papers in bytes, enough for
+    /// [`Config::max_batch_len`] papers of the longest names and
+    /// infos, even if all characters are escaped.
+    fn batch_body_limit(&self) -> usize {
+        /// Bytes of a paper in JSON besides its name and info.
+        const OVERHEAD: usize = 2048;
+        /// Bytes of an escaped character in JSON, as `\uXXXX`.
+        const ESCAPED: usize = 6;
+        self.max_batch_len.saturating_mul(
+            (self.max_name_len + self.max_info_len)
+                .saturating_mul(ESCAPED)
+                .saturating_add(OVERHEAD),
+        )
+    }
+
+    /// Checks whether submissions are open at the given time.
+    fn check_submissions_open(&self, now: DateTime<Utc>) -> Result<(), SubmissionsClosed> {
+        match (self.submissions_open_from, self.submissions_open_until) {
+            (Some(from), _) if now < from => Err(SubmissionsClosed { reopen: Some(from) }),
+            (_, Some(until)) if now > until => Err(SubmissionsClosed { reopen: None }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Management secrets with their field names.
+    fn secrets(&self) -> [(&'static str, &str); 6] {
+        [
+            ("mng_secret", &self.mng_secret),
+            ("mng_get_papers_secret", &self.mng_get_papers_secret),
+            ("mng_approve_papers_secret", &self.mng_approve_papers_secret),
+            ("mng_reject_papers_secret", &self.mng_reject_papers_secret),
+            ("mng_get_questions_secret", &self.mng_get_questions_secret),
+            (
+                "mng_resolve_questions_secret",
+                &self.mng_resolve_questions_secret,
+            ),
+        ]
+    }
+
+    /// Gets the management secret of the given name mutably.
+    fn secret_mut(&mut self, name: &str) -> Option<&mut String> {
+        match name {
+            "mng_secret" => Some(&mut self.mng_secret),
+            "mng_get_papers_secret" => Some(&mut self.mng_get_papers_secret),
+            "mng_approve_papers_secret" => Some(&mut self.mng_approve_papers_secret),
+            "mng_reject_papers_secret" => Some(&mut self.mng_reject_papers_secret),
+            "mng_get_questions_secret" => Some(&mut self.mng_get_questions_secret),
+            "mng_resolve_questions_secret" => Some(&mut self.mng_resolve_questions_secret),
+            _ => None,
+        }
+    }
+
+    /// Checks that management secrets are non-empty, long enough
+    /// and mutually distinct, as duplicated secrets make routes collide.
+    fn check_secrets(&self) -> Result<(), Vec<String>> {
+        let secrets = self.secrets();
+        let mut errors = Vec::new();
+        for (i, (field, secret)) in secrets.iter().enumerate() {
+            if secret.is_empty() {
+                errors.push(format!("{field} should not be empty"));
+            } else if secret.chars().count() < self.min_secret_len {
+                errors.push(format!(
+                    "{field} should be at least {} characters",
+                    self.min_secret_len
+                ));
+            }
+            for (other, _) in secrets[..i].iter().filter(|(_, s)| s == secret) {
+                errors.push(format!("{field} should be different from {other}"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns routes in `route_timeouts` which are not tunable.
+    fn unknown_route_timeouts(&self) -> Vec<&str> {
+        self.route_timeouts
+            .keys()
+            .map(String::as_str)
+            .filter(|route| !TUNABLE_ROUTES.contains(route))
+            .collect()
+    }
+
+    /// Backoff before the given retry of a conflicted pid, doubling every
+    /// retry, with random jitter up to the base.
+    fn pid_retry_backoff(&self, attempt: u32) -> Duration {
+        let base = self.pid_retry_backoff_ms;
+        let backoff =
+            base.saturating_mul(1 << attempt.saturating_sub(1).min(16)) + fastrand::u64(0..=base);
+        Duration::from_millis(backoff)
+    }
+
+    /// Checks that the static files directory, if any, exists and is readable.
+    fn check_static_path(&self) -> std::io::Result<()> {
+        if let Some(path) = &self.static_path {
+            std::fs::read_dir(path)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the given text contains any banned word.
+    ///
+    /// A banned entry may contain multiple words, which matches
+    /// the same sequence of words in the text.
+    fn contains_banned_word(&self, text: &str) -> bool {
+        fn words(text: &str) -> Vec<String> {
+            text.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect()
+        }
+
+        if self.banned_words.is_empty() {
+            return false;
+        }
+
+        let text = words(text);
+        self.banned_words.iter().any(|banned| {
+            let banned = words(banned);
+            !banned.is_empty() && text.windows(banned.len()).any(|window| window == banned)
+        })
+    }
+}
+
+/// Options of bincode for encoding stored data.
+///
+/// Integers are encoded in fixed length little endian, and
+/// trailing bytes are allowed. This is the same as the legacy
+/// `bincode::serialize`, but pinned so the format stays stable.
+#[inline]
+fn bincode_options() -> impl bincode::Options {
+    use bincode::Options;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+/// Builds all routes of the backend with the given state.
+///
+/// Unknown paths under API prefixes are responded with a JSON
+/// `404` by [`route_not_found`], so they never fall through to
+/// the static files.
+///
+/// Every request is scoped within a [`request::Context`],
+/// so responses carry a request id.
+///
+/// Responses are compressed if the client accepts it, except
+/// for event streams, which are excluded by the default predicate
+/// of [`CompressionLayer`] so events are not buffered.
+fn routes<Io: compact::FlushChunk + 'static>(state: &Global<Io>) -> Router<Global<Io>> {
+    let config = &*state.config;
+    Router::new()
+        .route("/version", get(version::version))
+        .route("/config/public", get(public::config::<Io>))
+        .route("/questions/new", post(question::new::<Io>))
+        .route("/paper/post", post(paper::post::<Io>))
+        .route(
+            "/paper/post_batch",
+            post(paper::post_batch::<Io>).layer(DefaultBodyLimit::max(config.batch_body_limit())),
+        )
+        .route("/paper/validate", post(paper::validate::<Io>))
+        .route(
+            "/paper/get",
+            get(paper::get::<Io>).head(paper::exists::<Io>),
+        )
+        .route(
+            "/paper/get/{pid}",
+            // papers are reachable by signed permalinks only if signed
+            if config.permalink_key.is_some() {
+                any(route_not_found)
+            } else {
+                get(paper::get_one::<Io>)
+            },
+        )
+        .route(
+            "/paper/edit",
+            if config.edit_key.is_some() {
+                post(sign::edit::<Io>)
+            } else {
+                any(route_not_found)
+            },
+        )
+        .route("/paper/recent", get(paper::recent::<Io>))
+        .route("/ws/approved", get(ticker::approved::<Io>))
+        .route(
+            "/paper/signed/{pid}",
+            if config.permalink_key.is_some() {
+                get(sign::get_signed::<Io>)
+            } else {
+                any(route_not_found)
+            },
         )
-        .route("/questions/new", post(question::new::<FsHandle>))
-        .route("/paper/post", post(paper::post::<FsHandle>))
-        .route("/paper/get", get(paper::get::<FsHandle>))
         .route(
             &format!("/{}/{}", config.mng_secret, config.mng_get_papers_secret),
-            get(paper::unprocessed::<FsHandle>),
+            get(paper::unprocessed::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/sign",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            if config.permalink_key.is_some() {
+                post(sign::mint::<Io>)
+            } else {
+                any(route_not_found)
+            },
+        )
+        .route(
+            &format!(
+                "/{}/{}/search",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            get(paper::search_by_email::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/many",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            post(paper::get_many::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/stats",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            get(paper::stats::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/queue",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            get(paper::queue::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/stream",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            get(paper::stream::<Io>),
         )
         .route(
             &format!(
                 "/{}/{}",
                 config.mng_secret, config.mng_approve_papers_secret
             ),
-            post(paper::approve::<FsHandle>),
+            post(paper::approve::<Io>),
         )
         .route(
             &format!("/{}/{}", config.mng_secret, config.mng_reject_papers_secret),
-            post(paper::reject::<FsHandle>),
+            post(paper::reject::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/priority",
+                config.mng_secret, config.mng_approve_papers_secret
+            ),
+            post(paper::set_priority::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/resend_email",
+                config.mng_secret, config.mng_approve_papers_secret
+            ),
+            post(paper::resend_email::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/preview_email",
+                config.mng_secret, config.mng_approve_papers_secret
+            ),
+            get(paper::preview_email::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/by_content",
+                config.mng_secret, config.mng_approve_papers_secret
+            ),
+            post(paper::approve_by_content::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/by_content",
+                config.mng_secret, config.mng_reject_papers_secret
+            ),
+            post(paper::reject_by_content::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/restore",
+                config.mng_secret, config.mng_reject_papers_secret
+            ),
+            post(paper::restore_rejected::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/search",
+                config.mng_secret, config.mng_get_questions_secret
+            ),
+            get(question::search_by_email::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/{{pid}}",
+                config.mng_secret, config.mng_get_questions_secret
+            ),
+            get(question::get_one::<Io>),
+        )
+        .route(
+            &format!("/{}/{}", config.mng_secret, config.mng_get_questions_secret),
+            get(question::list::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}",
+                config.mng_secret, config.mng_resolve_questions_secret
+            ),
+            post(question::resolve::<Io>),
+        )
+        .route(
+            &format!(
+                "/{}/{}/update",
+                config.mng_secret, config.mng_resolve_questions_secret
+            ),
+            post(question::update::<Io>),
+        )
+        .route("/admin/whoami", get(secret::whoami::<Io>))
+        .route(
+            &format!("/{}/rotate_secret", config.mng_secret),
+            post(secret::rotate::<Io>),
+        )
+        .route(
+            &format!("/{}/read_only", config.mng_secret),
+            post(mng::set_read_only::<Io>),
+        )
+        .route(
+            &format!("/{}/audit", config.mng_secret),
+            get(audit::query::<Io>),
+        )
+        .route(
+            &format!("/{}/diag", config.mng_secret),
+            get(mng::diag::<Io>),
+        )
+        .layer(TimeoutLayer::new(Duration::from_millis(
+            config.request_timeout_ms,
+        )))
+        // routes below have their own timeouts
+        .route(
+            &format!(
+                "/{}/{}/list",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            with_timeout(config, "list", get(paper::list::<Io>)),
+        )
+        .route(
+            &format!(
+                "/{}/{}/histogram",
+                config.mng_secret, config.mng_get_papers_secret
+            ),
+            with_timeout(config, "histogram", get(paper::histogram::<Io>)),
+        )
+        .route(
+            &format!("/{}/compact", config.mng_secret),
+            with_timeout(config, "compact", post(compact::compact::<Io>)),
+        )
+        .route(
+            &format!("/{}/import_legacy", config.mng_secret),
+            with_timeout(config, "import_legacy", post(paper::import_legacy::<Io>)),
+        )
+        .route(
+            &format!("/{}/approve_all", config.mng_secret),
+            if config.approve_all_confirm.is_some() {
+                with_timeout(config, "approve_all", post(paper::approve_all::<Io>))
+            } else {
+                any(route_not_found)
+            },
+        )
+        .route(
+            &format!("/{}/purge_all", config.mng_secret),
+            if config.purge_all_confirm.is_some() && mng::purge_allowed() {
+                with_timeout(config, "purge_all", post(mng::purge_all::<Io>))
+            } else {
+                any(route_not_found)
+            },
+        )
+        .route(
+            &format!("/{}/reject_range", config.mng_secret),
+            if config.reject_range_confirm.is_some() {
+                with_timeout(config, "reject_range", post(paper::reject_range::<Io>))
+            } else {
+                any(route_not_found)
+            },
+        )
+        .route("/paper/{*rest}", any(route_not_found))
+        .route("/questions/{*rest}", any(route_not_found))
+        .route(
+            &format!("/{}/{{*rest}}", config.mng_secret),
+            any(route_not_found),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            nonce::middleware::<Io>,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            config.pid_format,
+            pid::middleware,
+        ))
+        .layer(axum::middleware::from_fn(request::middleware))
+        .layer(CompressionLayer::new())
+}
+
+/// Builds the application of the given state, serving routes
+/// of its configuration and static files.
+fn app<Io: compact::FlushChunk + 'static>(state: Global<Io>) -> Router {
+    let router = routes(&state).with_state(state.clone());
+    let router = if let Some(path) = &state.config.static_path {
+        router.fallback_service(static_files(path, state.config.static_cache_secs))
+    } else {
+        router.fallback(route_not_found)
+    };
+    // bearer tokens are resolved before routing, as paths are rewritten
+    Router::new()
+        .fallback_service(router)
+        .layer(axum::middleware::from_fn_with_state(
+            state,
+            secret::bearer::<Io>,
+        ))
+}
+
+/// Routes with timeouts tunable by [`Config::route_timeouts`].
+const TUNABLE_ROUTES: [&str; 7] = [
+    "list",
+    "histogram",
+    "compact",
+    "import_legacy",
+    "approve_all",
+    "reject_range",
+    "purge_all",
+];
+
+/// Wraps the given tunable route with its own timeout.
+fn with_timeout<S>(config: &Config, route: &str, method: MethodRouter<S>) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    debug_assert!(
+        TUNABLE_ROUTES.contains(&route),
+        "route {route} is not tunable"
+    );
+    method.layer(TimeoutLayer::new(Duration::from_millis(
+        config
+            .route_timeouts
+            .get(route)
+            .copied()
+            .unwrap_or(config.request_timeout_ms),
+    )))
+}
+
+/// Serves static files of the frontend, with cache policies.
+///
+/// HTML pages are always revalidated so deploys take effect immediately,
+/// while other assets are cached for the given seconds, so they are expected
+/// to have content hashes in their file names, like `index-3f2a1c.js`.
+fn static_files(path: &Path, cache_secs: u64) -> Router {
+    let cache_control = HeaderValue::from_str(&if cache_secs > 0 {
+        format!("public, max-age={cache_secs}")
+    } else {
+        "no-cache".to_owned()
+    })
+    .expect("cache control should be a valid header value");
+
+    Router::new()
+        .fallback_service(ServeDir::new(path))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CACHE_CONTROL,
+            move |res: &axum::response::Response| {
+                if !res.status().is_success() {
+                    return None;
+                }
+                let html = res
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("text/html"));
+                Some(if html {
+                    HeaderValue::from_static("no-cache")
+                } else {
+                    cache_control.clone()
+                })
+            },
+        ))
+}
+
+/// Responds unknown API routes with a JSON error.
+async fn route_not_found() -> impl IntoResponse {
+    #[derive(serde::Serialize)]
+    struct JErr {
+        error: &'static str,
+        code: &'static str,
+        #[serde(flatten)]
+        context: ErrContext,
+    }
+
+    (
+        StatusCode::NOT_FOUND,
+        json::Json(JErr {
+            error: match locale::Lang::current() {
+                locale::Lang::En => "not found",
+                locale::Lang::Zh => "未找到",
+            },
+            code: "route",
+            context: ErrContext::current(),
+        }),
+    )
+}
+
+/// Opens the log file for appending, creating it
+/// along with its parent directories if absent.
+fn open_log_file(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+#[tokio::main]
+async fn main() {
+    const CONFIG_PATH: &str = "config.toml";
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\nusage: subboard-next-backend [--check-config]");
+            std::process::exit(2);
+        }
+    };
+    let config = match std::fs::read_to_string(CONFIG_PATH) {
+        Ok(src) => load_config(&src),
+        Err(err) => Err(ConfigError(vec![format!(
+            "failed to read {CONFIG_PATH}: {err}"
+        )])),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    // templates are validated here so typos surface before any email is sent
+    let templates = match mail::Templates::load(&config) {
+        Ok(templates) => templates,
+        Err(err) => {
+            eprintln!("invalid email template: {err}");
+            std::process::exit(1);
+        }
+    };
+    if args.check_config {
+        // the layout is checked against the database, so it's left to startup
+        println!("{CONFIG_PATH} is valid");
+        return;
+    }
+    if let Err(err) = config.layout.check(&config.db_path) {
+        panic!("invalid database layout: {err}");
+    }
+    if let Some(key) = &config.encryption_key {
+        crypt::init(crypt::Cipher::from_hex(key).expect("encryption key should be validated"));
+    }
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(
+            config
+                .log_level
+                .as_ref()
+                .and_then(|str| str.parse::<tracing::Level>().ok())
+                .unwrap_or(tracing::Level::INFO),
+        )
+        .with_writer(match config.log_path.as_deref().map(open_log_file) {
+            Some(Ok(file)) => BoxMakeWriter::new(file),
+            Some(Err(err)) => {
+                // the subscriber is not initialized yet
+                eprintln!("failed to open log file, logging to stdout instead: {err}");
+                BoxMakeWriter::new(std::io::stdout)
+            }
+            None => BoxMakeWriter::new(std::io::stdout),
+        });
+    match config.log_format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+    }
+
+    let mut paper_path = config.db_path.clone();
+    paper_path.push("papers");
+    let mut questions_path = config.db_path.clone();
+    questions_path.push("questions");
+    let mut audit_path = config.db_path.clone();
+    audit_path.push("audit");
+    let config = Arc::new(config);
+
+    let mut state = Global::new(
+        config.clone(),
+        dmds::world! {
+            dmds_tokio_fs::FsHandle::new(paper_path, false),
+            config.layout.papers_items_per_chunk() => ..=u64::MAX,
+            // one status per chunk, so scans of the small pending set
+            // never read approved papers
+            1 => ..=paper::Status::max_dim(),
+        },
+        dmds::world! {
+            dmds_tokio_fs::FsHandle::new(questions_path, true),
+            config.layout.questions_items_per_chunk() => ..=u64::MAX,
+        },
+        dmds::world! {
+            dmds_tokio_fs::FsHandle::new(audit_path, true),
+            audit::ITEMS_PER_CHUNK => ..=u64::MAX,
+        },
+    )
+    .with_templates(templates);
+    if let Some(smtp) = &config.smtp {
+        let transport = smtp.transport().expect("invalid smtp server");
+        state = state.with_mailer(Arc::new(transport));
+    }
+
+    let router: Router<()> = Router::new()
+        .layer(
+            TraceLayer::new_for_http()
+                .on_request(tower_http::trace::DefaultOnRequest::new().level(tracing::Level::INFO)),
         )
-        .layer(CorsLayer::permissive())
-        .with_state(state.clone())
-        .fallback_service(ServeDir::new(config.static_path.clone()));
+        .merge(secret::serve::<FsHandle>(state.clone()))
+        .layer(CorsLayer::permissive());
 
     tokio::spawn(dmds_tokio_fs::daemon(
         state.papers.clone(),
-        Duration::from_secs(45),
+        PAPERS_FLUSH_INTERVAL,
     ));
     tokio::spawn(dmds_tokio_fs::daemon(
         state.questions.clone(),
-        Duration::from_secs(120),
+        QUESTIONS_FLUSH_INTERVAL,
+    ));
+    tokio::spawn(dmds_tokio_fs::daemon(
+        state.audit.clone(),
+        AUDIT_FLUSH_INTERVAL,
     ));
+    if config.count_views {
+        tokio::spawn(views::daemon(
+            state.clone(),
+            Duration::from_secs(config.views_flush_interval_secs),
+        ));
+    }
+    if config.admin_alert_email.is_some() && state.mailer.is_some() {
+        tokio::spawn(alert::daemon(
+            state.clone(),
+            Duration::from_secs(config.alert_interval_secs),
+        ));
+    }
+    if let Some(days) = config.retention_days {
+        tokio::spawn(retention::daemon(
+            state.clone(),
+            days,
+            Duration::from_secs(config.retention_interval_secs),
+        ));
+    }
 
     info!("backend initialized");
 
-    axum::serve(
+    server::serve(
         tokio::net::TcpListener::bind(&config.address)
             .await
             .unwrap(),
         router,
+        &config,
+        shutdown_signal(),
     )
-    .await
-    .unwrap();
+    .await;
+    if config.count_views {
+        // views accumulated since the last tick would be lost otherwise
+        let updated = views::flush(&state).await;
+        info!("flushed views of {updated} papers before stopping");
+    }
+    // flush daemons write dirty chunks back when dropped with the runtime
+    info!("backend stopped");
+}
+
+/// Completes on Ctrl-C, or `SIGTERM` on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for Ctrl-C: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
 }
@@ -0,0 +1,213 @@
+use std::sync::atomic::Ordering;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    audit, compact,
+    json::Json,
+    locale::Localize,
+    paper::{self, ConfirmQuery},
+    pid::Pid,
+    question,
+    request::ErrContext,
+    secret::Actor,
+    Global,
+};
+
+/// Environment variable which should be `1` for purging all data
+/// to be served at all.
+pub const ALLOW_PURGE_ENV: &str = "ALLOW_PURGE";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadOnlyReq {
+    pub read_only: bool,
+}
+
+/// Query of management searches by email.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailQuery {
+    pub email: lettre::Address,
+    /// Pid to continue a truncated search from.
+    #[serde(default)]
+    pub from: u64,
+}
+
+impl EmailQuery {
+    /// Whether the given email is the same mailbox as the queried one,
+    /// comparing local parts exactly and domains case-insensitively.
+    pub fn matches(&self, email: &lettre::Address) -> bool {
+        self.email.user() == email.user()
+            && self.email.domain().eq_ignore_ascii_case(email.domain())
+    }
+}
+
+/// Toggles read-only mode, in which all writes are blocked.
+pub async fn set_read_only<Io: IoHandle>(
+    State(Global { read_only, .. }): State<Global<Io>>,
+    Json(ReadOnlyReq { read_only: value }): Json<ReadOnlyReq>,
+) {
+    warn!("setting read-only mode to {value}");
+    read_only.store(value, Ordering::Release);
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("confirmation mismatched")]
+    Unconfirmed,
+    #[error(transparent)]
+    Paper(#[from] paper::Error),
+    #[error(transparent)]
+    Question(#[from] question::Error),
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Unconfirmed => "确认信息不匹配".to_owned(),
+            Error::Paper(err) => err.zh(),
+            Error::Question(err) => err.zh(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        match self {
+            Error::Paper(err) => err.into_response(),
+            Error::Question(err) => err.into_response(),
+            Error::Unconfirmed => (
+                StatusCode::BAD_REQUEST,
+                Json(JErr {
+                    error: self.localized(),
+                    context: ErrContext::current(),
+                }),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Whether purging all data is allowed by the environment,
+/// which is checked once the routes are built.
+pub fn purge_allowed() -> bool {
+    std::env::var(ALLOW_PURGE_ENV).is_ok_and(|value| value == "1")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurgeAllRes {
+    pub papers: usize,
+    pub questions: usize,
+}
+
+/// Removes all papers and questions, for resetting test environments.
+///
+/// This is served only if both [`crate::Config::purge_all_confirm`] is
+/// configured and [`ALLOW_PURGE_ENV`] is set to `1`.
+pub async fn purge_all<Io: IoHandle>(
+    State(Global {
+        papers,
+        questions,
+        audit,
+        config,
+        read_only,
+        metrics,
+        approved_pids,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Query(ConfirmQuery { confirm }): Query<ConfirmQuery>,
+) -> Result<Json<PurgeAllRes>, Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(paper::Error::ReadOnly.into());
+    }
+    if config.purge_all_confirm.as_ref() != Some(&confirm) {
+        return Err(Error::Unconfirmed);
+    }
+
+    warn!("purging all papers and questions as {actor}");
+    let res = PurgeAllRes {
+        papers: paper::purge_all(&papers, &metrics).await?,
+        questions: question::purge_all(&questions, &metrics).await?,
+    };
+    approved_pids.invalidate().await;
+    warn!(
+        "purged {} papers and {} questions",
+        res.papers, res.questions
+    );
+    audit::record(&audit, audit::Action::PurgeAll, Pid(0), actor).await;
+    Ok(Json(res))
+}
+
+/// Intervals between flushes to the storage, in seconds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlushIntervals {
+    pub papers: u64,
+    pub questions: u64,
+    pub audit: u64,
+    /// Interval of flushing views, or `None` if views are not counted.
+    pub views: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiagRes {
+    pub papers: Vec<paper::StatusCount>,
+    pub questions: usize,
+    pub audit_entries: usize,
+    /// Size of the database directory, in bytes.
+    pub db_size: u64,
+    pub flush_intervals: FlushIntervals,
+    pub uptime_secs: u64,
+    pub read_only: bool,
+}
+
+/// Reports record counts, storage size and runtime state for operations.
+///
+/// Papers are decoded to skip the stale copies left by moves, while
+/// questions and audit entries are never moved and counted by ids only.
+pub async fn diag<Io: IoHandle>(State(state): State<Global<Io>>) -> Json<DiagRes> {
+    let mut papers = Vec::with_capacity(paper::Status::all().len());
+    for &status in paper::Status::all() {
+        let count = paper::count(&state.papers, &state.metrics, status).await;
+        papers.push(paper::StatusCount { status, count });
+    }
+    let config = &state.config;
+    Json(DiagRes {
+        papers,
+        questions: count_ids(&state.questions).await,
+        audit_entries: count_ids(&state.audit).await,
+        db_size: compact::dir_size(config.db_path.clone()).await,
+        flush_intervals: FlushIntervals {
+            papers: crate::PAPERS_FLUSH_INTERVAL.as_secs(),
+            questions: crate::QUESTIONS_FLUSH_INTERVAL.as_secs(),
+            audit: crate::AUDIT_FLUSH_INTERVAL.as_secs(),
+            views: config
+                .count_views
+                .then_some(config.views_flush_interval_secs),
+        },
+        uptime_secs: state.started.elapsed().as_secs(),
+        read_only: state.read_only.load(Ordering::Acquire),
+    })
+}
+
+/// Counts records of the given world without decoding them.
+async fn count_ids<T: dmds::Data, const DIMS: usize, Io: IoHandle>(
+    world: &dmds::World<T, DIMS, Io>,
+) -> usize {
+    let select = world.select_all();
+    let count = select.iter().filter(Result::is_ok).count().await;
+    count
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dmds::IoHandle;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{json::Json, locale::Localize, request::ErrContext, Global};
+
+/// Header carrying the one-time nonce of a management mutation.
+pub const X_NONCE: HeaderName = HeaderName::from_static("x-nonce");
+
+/// Maximum length of nonces in bytes.
+pub const MAX_LEN: usize = 128;
+
+/// Nonces of management mutations seen recently.
+///
+/// Expired nonces are pruned on every hit, oldest first.
+#[derive(Debug, Default)]
+pub struct Nonces {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    seen: HashSet<String>,
+    /// Seen nonces in the order they were seen.
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Nonces {
+    /// Records the given nonce, failing if it was seen within the TTL.
+    ///
+    /// Once `capacity` nonces are remembered, new nonces are rejected
+    /// until older ones expire, rather than forgetting nonces early
+    /// and allowing their replays.
+    pub fn hit(&self, nonce: &str, ttl: Duration, capacity: usize) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        while let Some((time, _)) = inner.order.front() {
+            if now.duration_since(*time) < ttl {
+                break;
+            }
+            let (_, expired) = inner.order.pop_front().unwrap();
+            inner.seen.remove(&expired);
+        }
+
+        if inner.seen.contains(nonce) {
+            return Err(Error::Replayed);
+        }
+        if inner.seen.len() >= capacity {
+            return Err(Error::Full);
+        }
+        inner.seen.insert(nonce.to_owned());
+        inner.order.push_back((now, nonce.to_owned()));
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("a unique nonce of at most {MAX_LEN} bytes is required")]
+    Invalid,
+    #[error("nonce was used already")]
+    Replayed,
+    #[error("too many recent nonces, retry later")]
+    Full,
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Invalid => format!("需要不超过 {MAX_LEN} 字节的一次性随机数"),
+            Error::Replayed => "随机数已被使用".to_owned(),
+            Error::Full => "近期随机数过多，请稍后重试".to_owned(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        (
+            match self {
+                Error::Invalid => StatusCode::BAD_REQUEST,
+                Error::Replayed => StatusCode::CONFLICT,
+                Error::Full => StatusCode::SERVICE_UNAVAILABLE,
+            },
+            Json(JErr {
+                error: self.localized(),
+                context: ErrContext::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Middleware requiring management mutations to carry a nonce unseen
+/// within [`crate::Config::nonce_ttl_secs`], if configured, so captured
+/// requests can't be replayed.
+///
+/// Reads and public routes are never checked.
+pub async fn middleware<Io: IoHandle>(
+    State(Global { config, nonces, .. }): State<Global<Io>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(ttl) = config.nonce_ttl_secs else {
+        return next.run(req).await;
+    };
+    let mutation = !(req.method().is_safe() || req.method() == axum::http::Method::OPTIONS);
+    let management = req
+        .uri()
+        .path()
+        .strip_prefix('/')
+        .and_then(|path| path.strip_prefix(config.mng_secret.as_str()))
+        .is_some_and(|rest| rest.starts_with('/'));
+    if !mutation || !management {
+        return next.run(req).await;
+    }
+
+    let nonce = req
+        .headers()
+        .get(X_NONCE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|nonce| !nonce.is_empty() && nonce.len() <= MAX_LEN);
+    let Some(nonce) = nonce else {
+        return Error::Invalid.into_response();
+    };
+    if let Err(err) = nonces.hit(nonce, Duration::from_secs(ttl), config.nonce_capacity) {
+        warn!("rejected management request to {}: {err}", req.uri().path());
+        return err.into_response();
+    }
+    next.run(req).await
+}
@@ -1,11 +1,48 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use chrono::{DateTime, Utc};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    convert::Infallible,
+    net::IpAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use bincode::Options as _;
+use chrono::{DateTime, Datelike as _, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
 use dmds::{IoHandle, StreamExt};
 use serde::{Deserialize, Serialize};
 use siphasher::sip::SipHasher24;
-use tracing::{error, info};
+use tokio_stream::{
+    wrappers::{BroadcastStream, ReceiverStream},
+    Stream,
+};
+use tracing::{error, info, instrument, warn, Span};
 
-use crate::Global;
+use crate::{
+    audit, bincode_options, cooldown,
+    crypt::{self, Cipher, StoredEmail},
+    ip::ClientIp,
+    json::{self, Json},
+    locale::Localize,
+    mail::{self, Mailer, Smtp, Template},
+    mng::EmailQuery,
+    new_pid,
+    pid::{Pid, PidFormat},
+    request::ErrContext,
+    scan::{self, Cursor, Scan},
+    secret::Actor,
+    sign, Config, Global, Metrics, SubmissionsClosed,
+};
 
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, serde_repr::Serialize_repr, serde_repr::Deserialize_repr,
@@ -14,6 +51,49 @@ use crate::Global;
 pub enum Status {
     Pending,
     Approved,
+    /// Rejected papers are kept for auditing until purged by retention.
+    Rejected,
+}
+
+impl Status {
+    /// All statuses, in order of their dimension values.
+    #[inline]
+    pub const fn all() -> &'static [Status] {
+        &[Self::Pending, Self::Approved, Self::Rejected]
+    }
+
+    /// Dimension value of this status.
+    #[inline]
+    pub const fn as_dim(self) -> u64 {
+        self as u8 as u64
+    }
+
+    /// Selects chunks of papers in this status.
+    ///
+    /// Each status has its own chunks along dimension `1`, so this never
+    /// reads papers of other statuses, except stale copies of papers
+    /// moved out of this status, which are told apart by decoding.
+    #[inline]
+    pub fn select<Io: IoHandle>(
+        self,
+        papers: &dmds::World<Paper, 2, Io>,
+    ) -> dmds::Select<'_, Paper, 2, Io> {
+        papers.select(1, self.as_dim())
+    }
+
+    /// Maximum dimension value of all statuses.
+    #[inline]
+    pub const fn max_dim() -> u64 {
+        Self::all().len() as u64 - 1
+    }
+
+    /// Gets the status from its dimension value.
+    fn from_dim(dim: u64) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|status| status.as_dim() == dim)
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -26,32 +106,203 @@ pub struct Paper {
     pub email: Option<lettre::Address>,
 
     /// Only identifier of this paper.
-    pub pid: u64,
+    pub pid: Pid,
     /// Post time
-    time: DateTime<Utc>,
+    pub time: DateTime<Utc>,
 
     pub status: Status,
     pub color: String,
+
+    /// IP address of the client who posted this paper.
+    pub ip: Option<IpAddr>,
+    /// Revision of this paper, bumped on every mutation.
+    pub rev: u64,
+    /// Count of fetches of this paper, which is not a mutation.
+    pub views: u64,
+    /// Whether this paper is pinned to the top of the review queue.
+    pub priority: bool,
+    /// URL of an image attached to this paper, which is never fetched.
+    pub image_url: Option<String>,
+    /// Time this paper was approved, or `None` if it's not approved,
+    /// or was approved before approval times were recorded.
+    pub approved_at: Option<DateTime<Utc>>,
+    /// Time this paper was received by the server, which is never
+    /// supplied by clients unlike [`Paper::time`], so duplicate and
+    /// range checks use this instead.
+    ///
+    /// This is the post time for papers stored before receive times
+    /// were recorded.
+    pub received_at: DateTime<Utc>,
 }
 
 /// Paper from frontend.
-#[derive(Debug, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct In {
     pub name: String,
     pub info: String,
+    /// Email of the author, which may be in the `Name <address>` form.
+    #[serde(default, deserialize_with = "mail::deserialize_address")]
     pub email: Option<lettre::Address>,
     pub color: String,
+    /// URL of an image hosted by one of [`Config::allowed_image_hosts`].
+    #[serde(default)]
+    pub image_url: Option<String>,
+    /// Time the paper was written, for clients posting papers later,
+    /// within [`Config::max_submitted_age_secs`].
+    #[serde(default)]
+    pub submitted_at: Option<DateTime<Utc>>,
 }
 
-/// Paper to frontend.
-#[derive(Debug, Serialize, Deserialize)]
+/// Paper to frontend, without the email of its author,
+/// which is responded to management clients only by [`MngOut`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Out {
     pub name: String,
     pub info: String,
-    pub email: Option<lettre::Address>,
-    pub pid: u64,
+    pub pid: Pid,
     color: String,
     time: DateTime<Utc>,
+    #[serde(default)]
+    pub views: u64,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+/// Borrowed [`Out`], which is serialized the same.
+///
+/// Papers are borrowed from their chunks only while iterating,
+/// so this should be serialized before the iteration ends.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct OutRef<'a> {
+    pub name: &'a str,
+    pub info: &'a str,
+    pub pid: Pid,
+    color: &'a str,
+    time: DateTime<Utc>,
+    pub views: u64,
+    pub image_url: Option<&'a str>,
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+/// Paper shown when there is no approved paper, which is never persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultPaper {
+    pub name: String,
+    pub info: String,
+    #[serde(default = "DefaultPaper::default_color")]
+    pub color: String,
+}
+
+impl DefaultPaper {
+    #[inline]
+    fn default_color() -> String {
+        "#ffffcc".to_owned()
+    }
+
+    /// Converts this paper to [`Out`] with the reserved pid `0`.
+    pub fn to_out(&self) -> Out {
+        Out {
+            name: self.name.clone(),
+            info: self.info.clone(),
+            pid: Pid(0),
+            color: self.color.clone(),
+            time: Utc::now(),
+            views: 0,
+            image_url: None,
+            approved_at: None,
+        }
+    }
+}
+
+/// Paper to management clients.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MngOut {
+    pub name: String,
+    pub info: String,
+    pub email: Option<lettre::Address>,
+    pub pid: Pid,
+    color: String,
+    time: DateTime<FixedOffset>,
+    pub ip: Option<IpAddr>,
+    pub rev: u64,
+    #[serde(default)]
+    pub priority: bool,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub approved_at: Option<DateTime<FixedOffset>>,
+    /// Whether `info` is truncated to a preview.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl MngOut {
+    /// Truncates info to the given length in characters,
+    /// appending an ellipsis if truncated.
+    fn truncate_info(&mut self, len: usize) {
+        if let Some((end, _)) = self.info.char_indices().nth(len) {
+            self.info.truncate(end);
+            self.info.push('…');
+            self.truncated = true;
+        }
+    }
+}
+
+/// Query projecting papers to management clients to some of their fields.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FieldsQuery {
+    /// Comma-separated names of fields of [`MngOut`] to respond,
+    /// or all fields if absent. Unknown names are ignored.
+    #[serde(default)]
+    pub fields: Option<String>,
+    /// Whether to respond full info, instead of previews of
+    /// [`Config::list_preview_len`] characters.
+    #[serde(default)]
+    pub full: bool,
+}
+
+impl FieldsQuery {
+    /// Converts the given paper to [`MngOut`], with its info
+    /// truncated to a preview unless full info is queried.
+    fn to_mng_out(&self, paper: &Paper, config: &Config) -> MngOut {
+        let mut out = paper.to_mng_out(config.display_timezone);
+        if !self.full {
+            out.truncate_info(config.list_preview_len);
+        }
+        out
+    }
+
+    /// Queried fields, or `None` if no projection is requested.
+    #[inline]
+    fn fields(&self) -> Option<&str> {
+        self.fields.as_deref().filter(|fields| !fields.is_empty())
+    }
+
+    /// Keeps only the queried fields of the given serialized paper.
+    fn project(&self, paper: &mut serde_json::Value) {
+        let Some(fields) = self.fields() else {
+            return;
+        };
+        if let Some(paper) = paper.as_object_mut() {
+            paper.retain(|key, _| fields.split(',').any(|field| field.trim() == key));
+        }
+    }
+
+    /// Serializes the given paper, keeping only the queried fields.
+    fn to_value(&self, paper: &MngOut) -> serde_json::Value {
+        let mut value = serde_json::to_value(paper).expect("papers should serialize");
+        self.project(&mut value);
+        value
+    }
+}
+
+/// Event pushed to management clients when a new paper is posted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub pid: Pid,
+    pub name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -71,223 +322,2271 @@ struct StoreV2 {
     color: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV3 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV4 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV5 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV6 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV7 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+    image_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV8 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+    image_url: Option<String>,
+    approved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV9 {
+    name: String,
+    info: String,
+    email: Option<StoredEmail>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+    image_url: Option<String>,
+    approved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV10 {
+    name: String,
+    info: String,
+    email: Option<StoredEmail>,
+    time: DateTime<Utc>,
+    color: String,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+    image_url: Option<String>,
+    approved_at: Option<DateTime<Utc>>,
+    received_at: DateTime<Utc>,
+}
+
+/// Borrowed [`StoreV10`] to encode papers without cloning,
+/// which is serialized the same.
+#[derive(Debug, Serialize)]
+struct StoreRef<'a> {
+    name: &'a str,
+    info: &'a str,
+    email: Option<StoredEmail<&'a str>>,
+    time: DateTime<Utc>,
+    color: &'a str,
+    ip: Option<IpAddr>,
+    rev: u64,
+    views: u64,
+    priority: bool,
+    image_url: Option<&'a str>,
+    approved_at: Option<DateTime<Utc>>,
+    received_at: DateTime<Utc>,
+}
+
+impl In {
+    /// Validates this paper against the given configuration.
+    ///
+    /// This is shared by [`post`] and [`validate`] so they never drift apart.
+    pub fn validate(&self, config: &Config) -> Result<(), Error> {
+        let name = self.name.trim();
+        let info = self.info.trim();
+        if name.is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if info.is_empty() {
+            return Err(Error::EmptyInfo);
+        }
+        if name.chars().count() > config.max_name_len {
+            return Err(Error::NameTooLong);
+        }
+        if info.chars().count() > config.max_info_len {
+            return Err(Error::InfoTooLong);
+        }
+        if info.chars().count() < config.min_info_len {
+            return Err(Error::InfoTooShort);
+        }
+        if config.max_links > 0 && count_links(name) + count_links(info) > config.max_links {
+            return Err(Error::TooManyLinks);
+        }
+        if config.require_email && self.email.is_none() {
+            return Err(Error::EmailRequired);
+        }
+        if !is_hex_color(&self.color) {
+            return Err(Error::InvalidColor);
+        }
+        if config.contains_banned_word(name) || config.contains_banned_word(info) {
+            return Err(Error::Banned);
+        }
+        if let Some(url) = &self.image_url {
+            check_image_url(url, &config.allowed_image_hosts)?;
+        }
+        self.time(config, Utc::now())?;
+        Ok(())
+    }
+
+    /// Time the paper was written, which is the given current time
+    /// unless the client supplied one.
+    ///
+    /// Supplied times should be neither in the future, tolerating
+    /// [`SUBMITTED_AT_SKEW_SECS`] of clock skew, nor older than
+    /// [`Config::max_submitted_age_secs`].
+    fn time(&self, config: &Config, now: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+        let Some(time) = self.submitted_at else {
+            return Ok(now);
+        };
+        let oldest = i64::try_from(config.max_submitted_age_secs)
+            .ok()
+            .and_then(chrono::Duration::try_seconds)
+            .and_then(|age| now.checked_sub_signed(age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let latest = now + chrono::Duration::seconds(SUBMITTED_AT_SKEW_SECS);
+        if time < oldest || time > latest {
+            return Err(Error::InvalidSubmittedAt);
+        }
+        Ok(time.min(now))
+    }
+
+    /// Converts this paper into a new pending paper with a new pid,
+    /// validating its supplied time if any.
+    pub fn into_paper(self, config: &Config) -> Result<Paper, Error> {
+        let now = Utc::now();
+        let time = self.time(config, now)?;
+        Ok(Paper {
+            name: self.name,
+            info: self.info,
+            email: self.email,
+            pid: new_pid(),
+            time,
+            status: Status::Pending,
+            color: self.color,
+            ip: None,
+            rev: 0,
+            views: 0,
+            priority: false,
+            image_url: self.image_url,
+            approved_at: None,
+            received_at: now,
+        })
+    }
+}
+
+/// Seconds of clock skew tolerated for supplied times of papers.
+const SUBMITTED_AT_SKEW_SECS: i64 = 60;
+
+/// Maximum length of image URLs, in bytes.
+const MAX_IMAGE_URL_LEN: usize = 512;
+
+/// Checks that the given URL is an `http(s)` URL without credentials,
+/// hosted by one of the given hosts or their subdomains.
+fn check_image_url(url: &str, allowed_hosts: &[String]) -> Result<(), Error> {
+    if url.len() > MAX_IMAGE_URL_LEN {
+        return Err(Error::InvalidImageUrl);
+    }
+    let uri: axum::http::Uri = url.parse().map_err(|_| Error::InvalidImageUrl)?;
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        return Err(Error::InvalidImageUrl);
+    }
+    let authority = uri.authority().ok_or(Error::InvalidImageUrl)?;
+    // credentials disguise hosts, like `https://allowed.com@evil.com`
+    if authority.as_str().contains('@') {
+        return Err(Error::InvalidImageUrl);
+    }
+    let host = authority.host().to_ascii_lowercase();
+    let allowed = allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        host == allowed
+            || host
+                .strip_suffix(&allowed)
+                .is_some_and(|sub| sub.ends_with('.'))
+    });
+    if allowed {
+        Ok(())
+    } else {
+        Err(Error::DisallowedImageHost)
+    }
+}
+
+/// Counts links in the given text by their `http(s)://` schemes.
+fn count_links(text: &str) -> usize {
+    let text = text.to_ascii_lowercase();
+    text.matches("http://").count() + text.matches("https://").count()
+}
+
+/// Whether the given string is a `#rgb` or `#rrggbb` color.
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
 impl Paper {
     #[inline]
     fn approve(&mut self) {
         self.status = Status::Approved;
+        self.approved_at = Some(Utc::now());
+        self.rev += 1;
+    }
+
+    #[inline]
+    fn reject(&mut self) {
+        self.status = Status::Rejected;
+        self.rev += 1;
+    }
+
+    #[inline]
+    fn restore(&mut self) {
+        self.status = Status::Pending;
+        self.rev += 1;
+    }
+
+    #[inline]
+    fn set_priority(&mut self, priority: bool) {
+        self.priority = priority;
+        self.rev += 1;
     }
 
-    fn to_out(&self) -> Out {
+    /// Checks the expected revision of this paper, if any.
+    #[inline]
+    fn check_rev(&self, rev: Option<u64>) -> Result<(), Error> {
+        if rev.is_some_and(|rev| rev != self.rev) {
+            Err(Error::RevConflict)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn to_out(&self) -> Out {
         Out {
             name: self.name.clone(),
             info: self.info.clone(),
-            email: self.email.clone(),
             pid: self.pid,
             time: self.time,
             color: self.color.clone(),
+            views: self.views,
+            image_url: self.image_url.clone(),
+            approved_at: self.approved_at,
         }
     }
 
-    fn to_store(&self) -> StoreV1 {
-        StoreV1 {
-            name: self.name.clone(),
-            info: self.info.clone(),
-            email: self.email.clone(),
+    /// Borrows this paper as [`OutRef`], for responding it
+    /// without cloning while it's still borrowed.
+    pub fn to_out_ref(&self) -> OutRef<'_> {
+        OutRef {
+            name: &self.name,
+            info: &self.info,
+            pid: self.pid,
+            color: &self.color,
             time: self.time,
+            views: self.views,
+            image_url: self.image_url.as_deref(),
+            approved_at: self.approved_at,
         }
     }
-}
 
-impl From<In> for Paper {
-    fn from(value: In) -> Self {
-        let hash = {
-            use std::hash::{Hash, Hasher};
+    /// Entity tag of this paper, derived from its pid and content.
+    fn etag(&self) -> String {
+        use std::hash::{Hash, Hasher};
 
-            let mut hasher = SipHasher24::new();
-            value.hash(&mut hasher);
-            hasher.finish()
-        };
+        let mut hasher = SipHasher24::new();
+        self.name.hash(&mut hasher);
+        self.info.hash(&mut hasher);
+        self.email
+            .as_ref()
+            .map(AsRef::<str>::as_ref)
+            .hash(&mut hasher);
+        self.color.hash(&mut hasher);
+        self.time.hash(&mut hasher);
+        self.views.hash(&mut hasher);
+        format!("\"{:x}-{:x}\"", self.pid.0, hasher.finish())
+    }
 
-        Self {
-            name: value.name,
-            info: value.info,
-            email: value.email,
-            pid: hash,
-            time: Utc::now(),
-            status: Status::Pending,
-            color: value.color,
+    /// Fingerprint of the content of a paper, which is its name and info.
+    fn content_hash(name: &str, info: &str) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = SipHasher24::new();
+        name.trim().hash(&mut hasher);
+        info.trim().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Converts this paper to [`MngOut`], with its time
+    /// displayed in the given timezone, or UTC if absent.
+    fn to_mng_out(&self, tz: Option<Tz>) -> MngOut {
+        MngOut {
+            name: self.name.clone(),
+            info: self.info.clone(),
+            email: self.email.clone(),
+            pid: self.pid,
+            time: tz.map_or_else(
+                || self.time.fixed_offset(),
+                |tz| self.time.with_timezone(&tz).fixed_offset(),
+            ),
+            color: self.color.clone(),
+            ip: self.ip,
+            rev: self.rev,
+            priority: self.priority,
+            image_url: self.image_url.clone(),
+            approved_at: self.approved_at.map(|time| match tz {
+                Some(tz) => time.with_timezone(&tz).fixed_offset(),
+                None => time.fixed_offset(),
+            }),
+            truncated: false,
         }
     }
+
+    fn to_store(&self, cipher: Option<&Cipher>) -> std::io::Result<StoreRef<'_>> {
+        Ok(StoreRef {
+            name: &self.name,
+            info: &self.info,
+            email: self
+                .email
+                .as_ref()
+                .map(|email| crypt::seal(cipher, self.pid, email))
+                .transpose()?,
+            time: self.time,
+            color: &self.color,
+            ip: self.ip,
+            rev: self.rev,
+            views: self.views,
+            priority: self.priority,
+            image_url: self.image_url.as_deref(),
+            approved_at: self.approved_at,
+            received_at: self.received_at,
+        })
+    }
+}
+
+#[inline]
+fn unknown_status() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown paper status")
 }
 
 impl dmds::Data for Paper {
     const DIMS: usize = 2;
-    const VERSION: u32 = 2;
+    const VERSION: u32 = 10;
 
     #[inline]
     fn dim(&self, dim: usize) -> u64 {
         match dim {
-            0 => self.pid,
-            1 => self.status as u8 as u64,
+            0 => self.pid.0,
+            1 => self.status.as_dim(),
             _ => unreachable!(),
         }
     }
 
+    #[inline]
     fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        Self::decode_with(version, dims, buf, crypt::cipher())
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        self.encode_with(buf, crypt::cipher())
+    }
+}
+
+impl Paper {
+    /// Decodes a paper the same as [`dmds::Data::decode`], opening
+    /// sealed fields with the given cipher.
+    pub fn decode_with<B: bytes::Buf>(
+        version: u32,
+        dims: &[u64],
+        buf: B,
+        cipher: Option<&Cipher>,
+    ) -> std::io::Result<Self> {
         match version {
             1 => {
-                let inner: StoreV1 = bincode::deserialize_from(buf.reader())
-                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                let inner: StoreV1 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
                 Ok(Self {
                     name: inner.name,
                     info: inner.info,
                     email: inner.email,
                     time: inner.time,
-                    pid: dims[0],
-                    status: if dims[1] as u8 == Status::Pending as u8 {
-                        Status::Pending
-                    } else {
-                        Status::Approved
-                    },
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
                     color: "#ffffcc".to_owned(),
+                    ip: None,
+                    rev: 0,
+                    views: 0,
+                    priority: false,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
                 })
             }
             2 => {
-                let inner: StoreV2 = bincode::deserialize_from(buf.reader())
-                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                let inner: StoreV2 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
                 Ok(Self {
                     name: inner.name,
                     info: inner.info,
                     email: inner.email,
                     time: inner.time,
-                    pid: dims[0],
-                    status: if dims[1] as u8 == Status::Pending as u8 {
-                        Status::Pending
-                    } else {
-                        Status::Approved
-                    },
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
                     color: inner.color,
+                    ip: None,
+                    rev: 0,
+                    views: 0,
+                    priority: false,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
                 })
             }
-            _ => unreachable!(),
-        }
-    }
-
-    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
-        bincode::serialize_into(buf.writer(), &self.to_store())
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
-    }
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    #[error("internal database error")]
-    Db,
-    #[error("pid conflicted")]
-    PidConflict,
-    #[error("paper repository is empty")]
-    NoPaper,
-    #[error("requiring paper not found")]
-    NotFound,
-}
-
-impl IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
-        #[derive(Serialize)]
-        struct JErr {
-            error: String,
-        }
-
-        (
-            match self {
-                Error::Db => StatusCode::INTERNAL_SERVER_ERROR,
-                Error::PidConflict => StatusCode::CONFLICT,
-                Error::NoPaper | Error::NotFound => StatusCode::NOT_FOUND,
-            },
-            Json(JErr {
-                error: self.to_string(),
-            }),
-        )
-            .into_response()
-    }
-}
-
-pub async fn post<Io: IoHandle>(
-    State(Global { papers, .. }): State<Global<Io>>,
-    Json(paper): Json<In>,
-) -> Result<(), Error> {
-    let paper: Paper = paper.into();
-    let pid = paper.pid;
+            3 => {
+                let inner: StoreV3 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: 0,
+                    views: 0,
+                    priority: false,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
+                })
+            }
+            4 => {
+                let inner: StoreV4 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: 0,
+                    priority: false,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
+                })
+            }
+            5 => {
+                let inner: StoreV5 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: false,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
+                })
+            }
+            6 => {
+                let inner: StoreV6 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: inner.priority,
+                    image_url: None,
+                    approved_at: None,
+                    received_at: inner.time,
+                })
+            }
+            7 => {
+                let inner: StoreV7 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: inner.priority,
+                    image_url: inner.image_url,
+                    approved_at: None,
+                    received_at: inner.time,
+                })
+            }
+            8 => {
+                let inner: StoreV8 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    time: inner.time,
+                    pid: Pid(dims[0]),
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: inner.priority,
+                    image_url: inner.image_url,
+                    approved_at: inner.approved_at,
+                    received_at: inner.time,
+                })
+            }
+            9 => {
+                let inner: StoreV9 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                let pid = Pid(dims[0]);
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner
+                        .email
+                        .map(|email| crypt::open(cipher, pid, email))
+                        .transpose()?,
+                    time: inner.time,
+                    pid,
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: inner.priority,
+                    image_url: inner.image_url,
+                    approved_at: inner.approved_at,
+                    received_at: inner.time,
+                })
+            }
+            10 => {
+                let inner: StoreV10 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                let pid = Pid(dims[0]);
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner
+                        .email
+                        .map(|email| crypt::open(cipher, pid, email))
+                        .transpose()?,
+                    time: inner.time,
+                    pid,
+                    status: Status::from_dim(dims[1]).ok_or_else(unknown_status)?,
+                    color: inner.color,
+                    ip: inner.ip,
+                    rev: inner.rev,
+                    views: inner.views,
+                    priority: inner.priority,
+                    image_url: inner.image_url,
+                    approved_at: inner.approved_at,
+                    received_at: inner.received_at,
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Encodes this paper the same as [`dmds::Data::encode`], sealing
+    /// personal fields with the given cipher.
+    pub fn encode_with<B: bytes::BufMut>(
+        &self,
+        buf: B,
+        cipher: Option<&Cipher>,
+    ) -> std::io::Result<()> {
+        bincode_options()
+            .serialize_into(buf.writer(), &self.to_store(cipher)?)
+            .map_err(std::io::Error::other)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("internal database error")]
+    Db,
+    #[error("pid conflicted")]
+    PidConflict,
+    #[error("paper repository is empty")]
+    NoPaper,
+    #[error("requiring paper not found")]
+    NotFound,
+    #[error("name should not be empty")]
+    EmptyName,
+    #[error("info should not be empty")]
+    EmptyInfo,
+    #[error("name is too long")]
+    NameTooLong,
+    #[error("info is too long")]
+    InfoTooLong,
+    #[error("info is too short")]
+    InfoTooShort,
+    #[error("paper contains too many links")]
+    TooManyLinks,
+    #[error("email is required")]
+    EmailRequired,
+    #[error("invalid color")]
+    InvalidColor,
+    #[error("paper contains disallowed content")]
+    Banned,
+    #[error("service is read-only for maintenance")]
+    ReadOnly,
+    #[error("submitting too frequently, retry after {0} seconds")]
+    Cooldown(u64),
+    #[error(transparent)]
+    Closed(#[from] SubmissionsClosed),
+    #[error("confirmation mismatched")]
+    Unconfirmed,
+    #[error("too many papers, at most {0} are allowed in a batch")]
+    BatchTooLarge(usize),
+    #[error("paper was changed by others")]
+    RevConflict,
+    #[error("start of the range is after its end")]
+    InvalidRange,
+    #[error("the same paper was posted recently")]
+    Duplicate,
+    #[error("invalid image URL")]
+    InvalidImageUrl,
+    #[error("image host is not allowed")]
+    DisallowedImageHost,
+    #[error("paper was not saved, retry later")]
+    Unsaved,
+    #[error("too many pids, at most {MAX_GET_MANY} are allowed")]
+    TooManyPids,
+    #[error("submission time is in the future or too old")]
+    InvalidSubmittedAt,
+    #[error("a paper of the same author was approved recently, retry after {0} seconds")]
+    AuthorCooldown(u64),
+    #[error("paper is not pending anymore")]
+    NotPending,
+    #[error("email of the paper was re-sent recently, retry after {0} seconds")]
+    ResendCooldown(u64),
+    #[error("email was not sent")]
+    EmailUnsent,
+}
+
+impl Error {
+    /// Machine-readable code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Db => "db",
+            Error::PidConflict => "pid_conflict",
+            Error::NoPaper => "no_paper",
+            Error::NotFound => "not_found",
+            Error::EmptyName => "empty_name",
+            Error::EmptyInfo => "empty_info",
+            Error::NameTooLong => "name_too_long",
+            Error::InfoTooLong => "info_too_long",
+            Error::InfoTooShort => "too_short",
+            Error::TooManyLinks => "too_many_links",
+            Error::EmailRequired => "email_required",
+            Error::InvalidColor => "invalid_color",
+            Error::Banned => "banned",
+            Error::ReadOnly => "read_only",
+            Error::Cooldown(_) => cooldown::RATE_LIMITED,
+            Error::Closed(_) => "closed",
+            Error::Unconfirmed => "unconfirmed",
+            Error::BatchTooLarge(_) => "batch_too_large",
+            Error::RevConflict => "rev_conflict",
+            Error::InvalidRange => "invalid_range",
+            Error::Duplicate => "duplicate",
+            Error::InvalidImageUrl => "invalid_image_url",
+            Error::DisallowedImageHost => "disallowed_image_host",
+            Error::Unsaved => "unsaved",
+            Error::TooManyPids => "too_many_pids",
+            Error::InvalidSubmittedAt => "invalid_submitted_at",
+            Error::AuthorCooldown(_) => "author_cooldown",
+            Error::NotPending => "not_pending",
+            Error::ResendCooldown(_) => cooldown::RATE_LIMITED,
+            Error::EmailUnsent => "email_unsent",
+        }
+    }
+}
+
+impl From<dmds::Error> for Error {
+    /// Logs the given database error, which is only reported
+    /// to clients as [`Error::Db`].
+    fn from(err: dmds::Error) -> Self {
+        error!("database error: {err}");
+        Error::Db
+    }
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Db => "数据库内部错误".to_owned(),
+            Error::PidConflict => "编号冲突".to_owned(),
+            Error::NoPaper => "暂无小纸条".to_owned(),
+            Error::NotFound => "未找到该小纸条".to_owned(),
+            Error::EmptyName => "名字不能为空".to_owned(),
+            Error::EmptyInfo => "内容不能为空".to_owned(),
+            Error::NameTooLong => "名字过长".to_owned(),
+            Error::InfoTooLong => "内容过长".to_owned(),
+            Error::InfoTooShort => "内容过短".to_owned(),
+            Error::TooManyLinks => "链接过多".to_owned(),
+            Error::EmailRequired => "需要填写邮箱".to_owned(),
+            Error::InvalidColor => "颜色无效".to_owned(),
+            Error::Banned => "包含不允许的内容".to_owned(),
+            Error::ReadOnly => "服务维护中，暂时只读".to_owned(),
+            Error::Cooldown(secs) => format!("提交过于频繁，请在 {secs} 秒后重试"),
+            Error::Closed(err) => err.zh(),
+            Error::Unconfirmed => "确认信息不匹配".to_owned(),
+            Error::BatchTooLarge(max) => format!("小纸条过多，每批最多 {max} 张"),
+            Error::RevConflict => "小纸条已被他人修改".to_owned(),
+            Error::InvalidRange => "范围的开始晚于结束".to_owned(),
+            Error::Duplicate => "近期已有相同的小纸条".to_owned(),
+            Error::InvalidImageUrl => "图片链接无效".to_owned(),
+            Error::DisallowedImageHost => "不允许该图片来源".to_owned(),
+            Error::Unsaved => "小纸条未能保存，请稍后重试".to_owned(),
+            Error::TooManyPids => format!("编号过多，最多 {MAX_GET_MANY} 个"),
+            Error::InvalidSubmittedAt => "提交时间晚于当前或过早".to_owned(),
+            Error::AuthorCooldown(secs) => {
+                format!("该作者近期已有小纸条通过，请在 {secs} 秒后重试")
+            }
+            Error::NotPending => "小纸条已被处理".to_owned(),
+            Error::ResendCooldown(secs) => {
+                format!("该小纸条的邮件近期已重发，请在 {secs} 秒后重试")
+            }
+            Error::EmailUnsent => "邮件未能发送".to_owned(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            code: &'static str,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        if let Error::Cooldown(secs) | Error::ResendCooldown(secs) = self {
+            return cooldown::rate_limited(secs, self.localized());
+        }
+        let retry_after = match self {
+            Error::PidConflict | Error::Unsaved => Some(1),
+            Error::AuthorCooldown(secs) => Some(secs),
+            _ => None,
+        };
+        (
+            match self {
+                Error::Db => StatusCode::INTERNAL_SERVER_ERROR,
+                Error::NoPaper | Error::NotFound => StatusCode::NOT_FOUND,
+                Error::EmptyName
+                | Error::EmptyInfo
+                | Error::NameTooLong
+                | Error::InfoTooLong
+                | Error::InfoTooShort
+                | Error::TooManyLinks
+                | Error::EmailRequired
+                | Error::InvalidColor
+                | Error::Banned
+                | Error::Unconfirmed
+                | Error::BatchTooLarge(_)
+                | Error::InvalidRange
+                | Error::InvalidImageUrl
+                | Error::DisallowedImageHost
+                | Error::TooManyPids
+                | Error::InvalidSubmittedAt => StatusCode::BAD_REQUEST,
+                Error::PidConflict | Error::ReadOnly | Error::Unsaved | Error::EmailUnsent => {
+                    StatusCode::SERVICE_UNAVAILABLE
+                }
+                Error::Cooldown(_) | Error::ResendCooldown(_) => StatusCode::TOO_MANY_REQUESTS,
+                Error::Closed(_) => StatusCode::FORBIDDEN,
+                Error::RevConflict
+                | Error::Duplicate
+                | Error::AuthorCooldown(_)
+                | Error::NotPending => StatusCode::CONFLICT,
+            },
+            retry_after.map(|secs| [(header::RETRY_AFTER, secs)]),
+            Json(JErr {
+                error: self.localized(),
+                code: self.code(),
+                context: ErrContext::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostRes {
+    pub pid: Pid,
+    /// Token letting the author edit the paper while it's pending,
+    /// if edits are enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_token: Option<String>,
+    /// Time the edit token expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_expires: Option<DateTime<Utc>>,
+}
+
+#[instrument(skip_all, fields(pid))]
+pub async fn post<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    ClientIp(ip): ClientIp,
+    Json(paper): Json<In>,
+) -> Result<Json<PostRes>, Error> {
+    insert_new(&state, ip, paper).await.map(Json)
+}
+
+/// Result of a paper of a batch, in the order of the request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BatchItem {
+    Posted(PostRes),
+    Failed { error: String, code: String },
+}
+
+/// Posts papers in a batch, at most [`Config::max_batch_len`] of them,
+/// each validated and inserted the same as posting one.
+///
+/// Papers failed to post don't affect other papers, and are
+/// responded with their errors in place.
+#[instrument(skip_all, fields(pid))]
+pub async fn post_batch<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    ClientIp(ip): ClientIp,
+    Json(batch): Json<Vec<In>>,
+) -> Result<Json<Vec<BatchItem>>, Error> {
+    if batch.len() > state.config.max_batch_len {
+        return Err(Error::BatchTooLarge(state.config.max_batch_len));
+    }
+
+    let mut ret = Vec::with_capacity(batch.len());
+    for paper in batch {
+        ret.push(match insert_new(&state, ip, paper).await {
+            Ok(res) => BatchItem::Posted(res),
+            Err(err) => BatchItem::Failed {
+                error: err.localized(),
+                code: err.code().to_owned(),
+            },
+        });
+    }
+    Ok(Json(ret))
+}
+
+/// Validates and inserts a new paper, retrying on pid conflicts.
+async fn insert_new<Io: IoHandle>(
+    Global {
+        papers,
+        config,
+        metrics,
+        paper_events,
+        read_only,
+        email_cooldown,
+        recent_posts,
+        ..
+    }: &Global<Io>,
+    ip: Option<IpAddr>,
+    paper: In,
+) -> Result<PostRes, Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    config.check_submissions_open(Utc::now())?;
+    paper.validate(config)?;
+    let hash = Paper::content_hash(&paper.name, &paper.info);
+    if config.dedup_window_secs > 0 {
+        let since = i64::try_from(config.dedup_window_secs)
+            .ok()
+            .and_then(chrono::Duration::try_seconds)
+            .and_then(|window| Utc::now().checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        if posted_since(papers, metrics, recent_posts, hash, since).await? {
+            return Err(Error::Duplicate);
+        }
+    }
+    let email = paper.email.clone();
+    let mut paper = paper.into_paper(config)?;
+    paper.ip = ip;
+    // cleared if the paper is not inserted, so failed posts don't count
+    if let Some(email) = &email {
+        email_cooldown
+            .hit(email, Duration::from_secs(config.email_cooldown_secs))
+            .map_err(Error::Cooldown)?;
+    }
+    Span::current().record("pid", paper.pid.0);
     info!("inserting new paper: {:?}", paper);
-    papers.try_insert(paper).await.map_err(|_| {
-        error!("papers with pid {pid} conflicted");
-        Error::PidConflict
-    })
+
+    for attempt in 0..=config.pid_retries {
+        if attempt > 0 {
+            metrics.pid_retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(config.pid_retry_backoff(attempt)).await;
+            paper.pid = new_pid();
+            Span::current().record("pid", paper.pid.0);
+        }
+
+        let event = Event {
+            pid: paper.pid,
+            name: paper.name.clone(),
+        };
+        let received_at = paper.received_at;
+        if let Err(err) = load_chunk_checked(papers, &paper).await {
+            error!("failed to load chunk of paper {}: {err}", paper.pid);
+            if let Some(email) = &email {
+                email_cooldown.clear(email);
+            }
+            return Err(Error::Unsaved);
+        }
+        match papers.try_insert(paper).await {
+            Ok(()) => {
+                let pid = event.pid;
+                recent_posts.record(hash, pid, received_at).await;
+                // Sending fails only if there is no subscriber.
+                let _ = paper_events.send(event);
+                let edit = config.edit_key.as_deref().map(|key| {
+                    let expires =
+                        Utc::now() + chrono::Duration::seconds(config.edit_grace_secs as i64);
+                    (sign::edit_token(key, pid, expires), expires)
+                });
+                return Ok(PostRes {
+                    pid,
+                    edit_token: edit.as_ref().map(|(token, _)| token.clone()),
+                    edit_expires: edit.map(|(_, expires)| expires),
+                });
+            }
+            Err(p) => {
+                warn!("paper pid {} conflicted, attempt {attempt}", p.pid);
+                paper = p;
+            }
+        }
+    }
+
+    error!(
+        "failed to insert paper after {} retries",
+        config.pid_retries
+    );
+    if let Some(email) = &email {
+        email_cooldown.clear(email);
+    }
+    Err(Error::PidConflict)
+}
+
+/// Replaces the info of the given paper by its author, validated the
+/// same as posting, only while the paper is pending.
+///
+/// Callers should authenticate the author first, as [`sign::edit`] does.
+pub async fn edit_pending<Io: IoHandle>(
+    Global {
+        papers,
+        config,
+        metrics,
+        read_only,
+        recent_posts,
+        ..
+    }: &Global<Io>,
+    pid: Pid,
+    info: String,
+) -> Result<(), Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let select = pid.select(papers);
+    let mut papers_iter = select.iter();
+
+    while let Some(Ok(mut lazy)) = papers_iter.next().await {
+        if lazy.id() == pid {
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                if paper.status != Status::Pending {
+                    return Err(Error::NotPending);
+                }
+                let edited = In {
+                    name: paper.name.clone(),
+                    info,
+                    email: paper.email.clone(),
+                    color: paper.color.clone(),
+                    image_url: paper.image_url.clone(),
+                    submitted_at: None,
+                };
+                edited.validate(config)?;
+                info!("editing pending paper {pid} by its author");
+                paper.info = edited.info;
+                paper.rev += 1;
+                let hash = Paper::content_hash(&paper.name, &paper.info);
+                let received_at = paper.received_at;
+                lazy.close().await?;
+                recent_posts.record(hash, pid, received_at).await;
+                return Ok(());
+            }
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Pids of papers received recently by their content fingerprints,
+/// so duplicates are checked without scanning papers.
+///
+/// This is filled by one scan of pending and approved papers on first
+/// use, and entries are pruned once older than the window, oldest first.
+/// Entries are only candidates confirmed by reading their papers, so
+/// papers rejected, edited or removed since are never duplicates.
+#[derive(Debug, Default)]
+pub struct RecentPosts {
+    /// Recent posts, or `None` if not scanned yet.
+    inner: tokio::sync::Mutex<Option<RecentInner>>,
+}
+
+#[derive(Debug, Default)]
+struct RecentInner {
+    by_hash: HashMap<u64, Vec<Pid>>,
+    /// Entries in the order of their receive times.
+    order: VecDeque<(DateTime<Utc>, u64, Pid)>,
+}
+
+impl RecentInner {
+    fn push(&mut self, received_at: DateTime<Utc>, hash: u64, pid: Pid) {
+        self.by_hash.entry(hash).or_default().push(pid);
+        self.order.push_back((received_at, hash, pid));
+    }
+}
+
+impl RecentPosts {
+    /// Pids of papers of the given content received at or after the
+    /// given time, scanning papers first if not scanned yet.
+    ///
+    /// Nothing is remembered if the scan fails, so it's retried.
+    async fn candidates<Io: IoHandle>(
+        &self,
+        papers: &dmds::World<Paper, 2, Io>,
+        metrics: &Metrics,
+        hash: u64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Pid>, dmds::Error> {
+        let mut inner = self.inner.lock().await;
+        let inner = match &mut *inner {
+            Some(inner) => inner,
+            None => inner.insert(Self::scan(papers, metrics, since).await?),
+        };
+        while let Some(&(time, hash, pid)) = inner.order.front() {
+            if time >= since {
+                break;
+            }
+            inner.order.pop_front();
+            if let Some(pids) = inner.by_hash.get_mut(&hash) {
+                pids.retain(|&other| other != pid);
+                if pids.is_empty() {
+                    inner.by_hash.remove(&hash);
+                }
+            }
+        }
+        Ok(inner.by_hash.get(&hash).cloned().unwrap_or_default())
+    }
+
+    /// Records a paper of the given content received at the given time.
+    ///
+    /// Nothing is recorded if papers are not scanned yet,
+    /// as the scan finds the paper.
+    async fn record(&self, hash: u64, pid: Pid, received_at: DateTime<Utc>) {
+        if let Some(inner) = &mut *self.inner.lock().await {
+            inner.push(received_at, hash, pid);
+        }
+    }
+
+    async fn scan<Io: IoHandle>(
+        papers: &dmds::World<Paper, 2, Io>,
+        metrics: &Metrics,
+        since: DateTime<Utc>,
+    ) -> Result<RecentInner, dmds::Error> {
+        let mut recent = vec![];
+        for status in [Status::Pending, Status::Approved] {
+            let select = status.select(papers);
+            let mut papers_iter = select.iter();
+            while let Some(lazy) = papers_iter.next().await {
+                // iterators can't be resumed after failed reads
+                let lazy = lazy?;
+                if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+                    if paper.status == status && paper.received_at >= since {
+                        recent.push((
+                            paper.received_at,
+                            Paper::content_hash(&paper.name, &paper.info),
+                            paper.pid,
+                        ));
+                    }
+                }
+            }
+        }
+        recent.sort_unstable();
+        let mut inner = RecentInner::default();
+        for (received_at, hash, pid) in recent {
+            inner.push(received_at, hash, pid);
+        }
+        info!("found {} papers received since {since}", inner.order.len());
+        Ok(inner)
+    }
+}
+
+/// Whether a pending or approved paper of the same content
+/// was received at or after the given time.
+async fn posted_since<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    recent_posts: &RecentPosts,
+    hash: u64,
+    since: DateTime<Utc>,
+) -> Result<bool, dmds::Error> {
+    for pid in recent_posts
+        .candidates(papers, metrics, hash, since)
+        .await?
+    {
+        let select = pid.select(papers);
+        let mut papers_iter = select.iter();
+        while let Some(lazy) = papers_iter.next().await {
+            // iterators can't be resumed after failed reads
+            let lazy = lazy?;
+            if lazy.id() == pid
+                && metrics
+                    .read(lazy.id(), lazy.get().await)
+                    .is_some_and(|paper| {
+                        matches!(paper.status, Status::Pending | Status::Approved)
+                            && paper.received_at >= since
+                            && Paper::content_hash(&paper.name, &paper.info) == hash
+                    })
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Validation {
+    pub ok: bool,
+}
+
+/// Validates a paper without posting it.
+pub async fn validate<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+    Json(paper): Json<In>,
+) -> Result<Json<Validation>, Error> {
+    paper.validate(&config)?;
+    Ok(Json(Validation { ok: true }))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GetQuery {
+    /// Pid of the paper not to get, usually the one being displayed.
+    #[serde(default)]
+    pub exclude: Option<Pid>,
+    /// Whether to prefer recent papers, with weights halving every
+    /// [`crate::Config::weighted_half_life_secs`] of their age.
+    #[serde(default)]
+    pub weighted: bool,
+}
+
+/// Cache of pids of approved papers, shared by concurrent random gets
+/// so they reuse one scan of approved papers.
+#[derive(Debug, Default)]
+pub struct ApprovedPids {
+    /// Cached pids and the time they were scanned.
+    cached: tokio::sync::Mutex<Option<(Instant, Arc<[Pid]>)>>,
+}
+
+impl ApprovedPids {
+    /// Gets pids scanned within the given TTL, or scans them again,
+    /// where concurrent callers wait for the same scan.
+    ///
+    /// Nothing is cached if the TTL is zero, or if the scan fails.
+    async fn get<Io: IoHandle>(
+        &self,
+        papers: &dmds::World<Paper, 2, Io>,
+        ttl: Duration,
+    ) -> Result<Arc<[Pid]>, dmds::Error> {
+        if ttl.is_zero() {
+            return scan_approved(papers).await.map(Into::into);
+        }
+        let mut cached = self.cached.lock().await;
+        if let Some((time, pids)) = &*cached {
+            if time.elapsed() < ttl {
+                return Ok(pids.clone());
+            }
+        }
+        let pids: Arc<[Pid]> = scan_approved(papers).await?.into();
+        *cached = Some((Instant::now(), pids.clone()));
+        Ok(pids)
+    }
+
+    /// Drops the cached pids, so the next get scans again.
+    pub async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+    }
+}
+
+/// Pids of all approved papers, without decoding them.
+async fn scan_approved<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+) -> Result<Vec<Pid>, dmds::Error> {
+    let select = Status::Approved.select(papers);
+    let mut iter = select.iter();
+    let mut pids = Vec::new();
+    while let Some(lazy) = iter.next().await {
+        // iterators can't be resumed after failed reads
+        pids.push(Pid(lazy?.id()));
+    }
+    Ok(pids)
+}
+
+/// Gets a random approved paper, uniformly or weighted by recency.
+///
+/// The excluded paper is still responded if it is the only approved one,
+/// unless `strict_exclude` is configured.
+/// If there is no approved paper, the configured default paper
+/// is responded with the reserved pid `0` if there is any.
+pub async fn get<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        approved_pids,
+        ..
+    }): State<Global<Io>>,
+    Query(GetQuery { exclude, weighted }): Query<GetQuery>,
+) -> Result<Response, Error> {
+    if weighted {
+        return get_weighted(&papers, &metrics, &config, exclude).await;
+    }
+    let mut pids = approved_pids
+        .get(&papers, Duration::from_secs(config.paper_get_cache_secs))
+        .await?
+        .to_vec();
+    if let Some(exclude) = exclude {
+        if pids.len() > 1 || config.strict_exclude {
+            pids.retain(|&pid| pid != exclude);
+            if pids.is_empty() {
+                return Err(Error::NoPaper);
+            }
+        }
+    }
+    if pids.is_empty() {
+        return config
+            .default_paper
+            .as_ref()
+            .map(|paper| Json(paper.to_out()).into_response())
+            .ok_or(Error::NoPaper);
+    }
+
+    // falls through to other candidates if the chosen one fails to decode
+    fastrand::shuffle(&mut pids);
+    for pid in pids {
+        let select = pid.select(&papers);
+        let mut papers_iter = select.iter();
+        while let Some(Ok(lazy)) = papers_iter.next().await {
+            if lazy.id() == pid {
+                if let Some(val) = metrics.read(lazy.id(), lazy.get().await) {
+                    return Ok(Json(val.to_out_ref()).into_response());
+                }
+            }
+        }
+    }
+
+    Err(Error::Db)
+}
+
+/// Gets a random approved paper weighted by its post time, in a single pass
+/// of weighted reservoir sampling, so papers are decoded but not collected.
+///
+/// The weight of a paper halves every half-life of its age, and the paper
+/// of the minimum key `ln(E) - ln(weight)` is chosen, where `E` is drawn
+/// from the standard exponential distribution, which is the same as drawing
+/// with probabilities proportional to weights, without underflowing them.
+async fn get_weighted<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    config: &Config,
+    exclude: Option<Pid>,
+) -> Result<Response, Error> {
+    let now = Utc::now();
+    let half_life = config.weighted_half_life_secs as f64;
+    // minimum keys and their papers, of those not excluded and of the excluded one
+    let mut chosen: Option<(f64, Out)> = None;
+    let mut excluded: Option<(f64, Out)> = None;
+    let mut count = 0_usize;
+
+    let select = Status::Approved.select(papers);
+    let mut papers_iter = select.iter();
+    while let Some(Ok(lazy)) = papers_iter.next().await {
+        let Some(paper) = metrics.read(lazy.id(), lazy.get().await) else {
+            continue;
+        };
+        if paper.status != Status::Approved {
+            continue;
+        }
+        count += 1;
+        let age = (now - paper.time).num_seconds().max(0) as f64;
+        // 1 - u is in (0, 1], so the logarithm is finite
+        let key = (-(1.0 - fastrand::f64()).ln()).ln() + age / half_life * std::f64::consts::LN_2;
+        let slot = if Some(paper.pid) == exclude {
+            &mut excluded
+        } else {
+            &mut chosen
+        };
+        if slot.as_ref().is_none_or(|(min, _)| key < *min) {
+            *slot = Some((key, paper.to_out()));
+        }
+    }
+
+    match (chosen, excluded) {
+        (Some((_, paper)), _) => Ok(Json(paper).into_response()),
+        (None, Some((_, paper))) if count == 1 && !config.strict_exclude => {
+            Ok(Json(paper).into_response())
+        }
+        (None, Some(_)) => Err(Error::NoPaper),
+        (None, None) => config
+            .default_paper
+            .as_ref()
+            .map(|paper| Json(paper.to_out()).into_response())
+            .ok_or(Error::NoPaper),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentQuery {
+    /// Count of papers to get, capped by [`Config::max_recent`].
+    #[serde(default = "RecentQuery::default_limit")]
+    pub limit: usize,
+}
+
+impl RecentQuery {
+    #[inline]
+    fn default_limit() -> usize {
+        10
+    }
+}
+
+/// Gets the most recently approved papers, latest first.
+///
+/// Papers approved before approval times were recorded are ordered
+/// by their post times instead.
+pub async fn recent<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(RecentQuery { limit }): Query<RecentQuery>,
+) -> Json<Vec<Out>> {
+    let limit = limit.clamp(1, config.max_recent);
+    let mut ret = Least::new(limit);
+
+    let select = Status::Approved.select(&papers);
+    let mut papers_iter = select.iter();
+    while let Some(Ok(lazy)) = papers_iter.next().await {
+        let Some(paper) = metrics.read(lazy.id(), lazy.get().await) else {
+            continue;
+        };
+        if paper.status != Status::Approved {
+            continue;
+        }
+        let key = Reverse((paper.approved_at.unwrap_or(paper.time), paper.pid));
+        ret.offer(key, || paper.to_out());
+    }
+    Json(ret.into_values().collect())
+}
+
+/// Header carrying the count of approved papers.
+const X_PAPER_COUNT: &str = "x-paper-count";
+
+/// Reports whether any approved paper exists, without a body.
+///
+/// Responds `200` if there is any, or `404` otherwise, with the
+/// count of approved papers in the `X-Paper-Count` header.
+/// Only ids are counted, so no paper is decoded.
+pub async fn exists<Io: IoHandle>(State(Global { papers, .. }): State<Global<Io>>) -> Response {
+    let select = Status::Approved.select(&papers);
+    let count = select.iter().filter(Result::is_ok).count().await;
+    (
+        if count > 0 {
+            StatusCode::OK
+        } else {
+            StatusCode::NOT_FOUND
+        },
+        [(X_PAPER_COUNT, count)],
+    )
+        .into_response()
+}
+
+/// Gets an approved paper by its pid.
+///
+/// Approved papers are immutable except their views, so the response
+/// carries an `ETag` and `If-None-Match` is honored with `304 Not Modified`.
+/// Fetches are counted as views if `count_views` is configured.
+#[instrument(skip_all, fields(pid = pid.0))]
+pub async fn get_one<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        views,
+        ..
+    }): State<Global<Io>>,
+    Path(pid): Path<Pid>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let select = pid.select(&papers);
+    let mut papers_iter = select.iter();
+    while let Some(lazy) = papers_iter.next().await {
+        let lazy = lazy?;
+        if lazy.id() == pid {
+            let Some(paper) = metrics.read(lazy.id(), lazy.get().await) else {
+                continue;
+            };
+            if paper.status != Status::Approved {
+                break;
+            }
+            if config.count_views {
+                views.hit(pid);
+            }
+            let etag = paper.etag();
+            let matched = headers
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|val| val.to_str().ok())
+                .flat_map(|val| val.split(','))
+                .any(|tag| {
+                    let tag = tag.trim();
+                    tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+                });
+            let etag = HeaderValue::from_str(&etag).map_err(|_| Error::Db)?;
+
+            return Ok(if matched {
+                (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response()
+            } else {
+                ([(header::ETAG, etag)], Json(paper.to_out_ref())).into_response()
+            });
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Content type of newline delimited JSON.
+const NDJSON: &str = "application/x-ndjson";
+
+/// Capacity of buffered lines when streaming NDJSON.
+const NDJSON_BUFFER: usize = 16;
+
+/// Gets all unprocessed papers, projected to the queried fields.
+///
+/// If the client accepts `application/x-ndjson`, papers are streamed
+/// one JSON object per line as they are read, keeping memory flat
+/// regardless of the queue size. Errors while reading terminate the
+/// stream, so clients may see a truncated response.
+pub async fn unprocessed<Io: IoHandle + 'static>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(fields): Query<FieldsQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let ndjson = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .any(|val| val.contains(NDJSON));
+    if !ndjson {
+        let papers = unprocessed_json(&papers, &metrics, &config, &fields).await;
+        return if fields.fields().is_some() {
+            Json(
+                papers
+                    .0
+                    .iter()
+                    .map(|paper| fields.to_value(paper))
+                    .collect::<Vec<_>>(),
+            )
+            .into_response()
+        } else {
+            papers.into_response()
+        };
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(NDJSON_BUFFER);
+    let format = PidFormat::current();
+    tokio::spawn(format.scope(async move {
+        let select = Status::Pending.select(&papers);
+        let mut papers_iter = select.iter();
+        while let Some(Ok(lazy)) = papers_iter.next().await {
+            let Some(val) = metrics.read(lazy.id(), lazy.get().await) else {
+                continue;
+            };
+            let Ok(mut line) =
+                serde_json::to_vec(&fields.to_value(&fields.to_mng_out(val, &config)))
+            else {
+                break;
+            };
+            line.push(b'\n');
+            if tx.send(Ok::<_, Infallible>(line)).await.is_err() {
+                // client disconnected
+                break;
+            }
+        }
+    }));
+    (
+        [(header::CONTENT_TYPE, NDJSON)],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+async fn unprocessed_json<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    config: &Config,
+    fields: &FieldsQuery,
+) -> Json<Vec<MngOut>> {
+    let select = Status::Pending.select(papers);
+    let mut papers_iter = select.iter();
+
+    let mut ret = Vec::new();
+    while let Some(Ok(lazy)) = papers_iter.next().await {
+        if let Some(val) = metrics.read(lazy.id(), lazy.get().await) {
+            ret.push(fields.to_mng_out(val, config));
+        }
+    }
+    Json(ret)
+}
+
+/// Values of the least keys offered, in ascending order of keys,
+/// for paging papers sorted across chunks while iterating them.
+///
+/// Papers are borrowed from their chunks only while iterating, so only
+/// papers kept are converted to owned outputs, instead of all of them.
+struct Least<K, V> {
+    kept: BTreeMap<K, V>,
+    cap: usize,
+}
+
+impl<K: Ord, V> Least<K, V> {
+    #[inline]
+    fn new(cap: usize) -> Self {
+        Self {
+            kept: BTreeMap::new(),
+            cap,
+        }
+    }
+
+    /// Offers a value of the given key, which is made only if kept.
+    fn offer(&mut self, key: K, value: impl FnOnce() -> V) {
+        if self.kept.len() >= self.cap
+            && self
+                .kept
+                .last_key_value()
+                .is_none_or(|(last, _)| key >= *last)
+        {
+            return;
+        }
+        self.kept.insert(key, value());
+        if self.kept.len() > self.cap {
+            self.kept.pop_last();
+        }
+    }
+
+    #[inline]
+    fn into_values(self) -> impl Iterator<Item = V> {
+        self.kept.into_values()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Status of papers to list, or all papers if absent.
+    #[serde(default)]
+    pub status: Option<Status>,
+    /// Lists papers posted at or after this time.
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Lists papers posted at or before this time.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default = "ListQuery::default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    /// Pid to continue a truncated scan from.
+    #[serde(default)]
+    pub from: u64,
+    /// Cursor to page papers in ascending pids from, instead of paging
+    /// by `offset` in time order, which scans only the requested page.
+    #[serde(default)]
+    pub after: Option<Cursor>,
+}
+
+impl ListQuery {
+    #[inline]
+    fn default_limit() -> usize {
+        50
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListRes {
+    /// Count of all matching papers, regardless of pagination,
+    /// or of papers in the page if paging by cursor.
+    ///
+    /// Only scanned papers are counted if the scan is truncated.
+    pub total: usize,
+    pub papers: Vec<MngOut>,
+    #[serde(flatten)]
+    pub scan: Scan,
+    /// Cursor of the next page if paging by cursor, or `None`
+    /// if there is no more paper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Lists papers filtered by status and post time, sorted by time,
+/// projected to the queried fields.
+///
+/// Papers are sorted and paged by pid instead if a cursor is queried.
+pub async fn list<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(query): Query<ListQuery>,
+    Query(fields): Query<FieldsQuery>,
+) -> Response {
+    let after = query.after.map(|cursor| cursor.0);
+    let from = match query.after.map(Cursor::from) {
+        Some(Some(from)) => from.max(query.from),
+        Some(None) => u64::MAX,
+        None => query.from,
+    };
+    // papers are kept by their pids if paging by cursor,
+    // with one more paper telling whether there is more
+    let mut ret = Least::new(if after.is_some() {
+        query.limit.saturating_add(1)
+    } else {
+        query.offset.saturating_add(query.limit)
+    });
+    let mut total = 0;
+    let scan = scan::scan_while(
+        &papers,
+        config.layout.papers_items_per_chunk(),
+        from..=u64::MAX,
+        config.max_scan,
+        |id, val| {
+            if let Some(val) = metrics.read(id, val) {
+                if after.is_none_or(|after| val.pid > after)
+                    && query.status.is_none_or(|status| val.status == status)
+                    && query.since.is_none_or(|since| val.time >= since)
+                    && query.until.is_none_or(|until| val.time <= until)
+                {
+                    total += 1;
+                    let key = (after.is_none().then_some(val.time), val.pid);
+                    ret.offer(key, || fields.to_mng_out(val, &config));
+                }
+            }
+            // pages by cursor stop once the page is filled
+            after.is_none() || total <= query.limit
+        },
+    )
+    .await;
+
+    let res = if after.is_some() {
+        let mut ret: Vec<_> = ret.into_values().collect();
+        let more = ret.len() > query.limit;
+        ret.truncate(query.limit);
+        let next_cursor = if more {
+            ret.last().map(|paper| Cursor(paper.pid))
+        } else {
+            // continues after all scanned papers
+            scan.next.map(|next| Cursor(Pid(next - 1)))
+        };
+        ListRes {
+            total: ret.len(),
+            papers: ret,
+            scan,
+            next_cursor,
+        }
+    } else {
+        ListRes {
+            total,
+            papers: ret.into_values().skip(query.offset).collect(),
+            scan,
+            next_cursor: None,
+        }
+    };
+    if fields.fields().is_none() {
+        return Json(res).into_response();
+    }
+    let mut value = serde_json::to_value(res).expect("papers should serialize");
+    if let Some(papers) = value["papers"].as_array_mut() {
+        papers.iter_mut().for_each(|paper| fields.project(paper));
+    }
+    Json(value).into_response()
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchRes {
+    pub papers: Vec<Paper>,
+    #[serde(flatten)]
+    pub scan: Scan,
+}
+
+/// Searches papers in all statuses posted by the given email,
+/// sorted by time.
+pub async fn search_by_email<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(query): Query<EmailQuery>,
+) -> Json<SearchRes> {
+    let mut ret = Vec::new();
+    let scan = scan::scan(
+        &papers,
+        config.layout.papers_items_per_chunk(),
+        query.from,
+        config.max_scan,
+        |id, paper| {
+            if let Some(paper) = metrics.read(id, paper) {
+                if paper
+                    .email
+                    .as_ref()
+                    .is_some_and(|email| query.matches(email))
+                {
+                    ret.push(paper.clone());
+                }
+            }
+        },
+    )
+    .await;
+    ret.sort_by_key(|paper| (paper.time, paper.pid));
+    Json(SearchRes { papers: ret, scan })
+}
+
+/// Maximum count of pids fetched by [`get_many`] at once.
+pub const MAX_GET_MANY: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetManyReq {
+    pub pids: Vec<Pid>,
 }
 
-pub async fn get<Io: IoHandle>(
-    State(Global { papers, .. }): State<Global<Io>>,
-) -> Result<Json<Paper>, Error> {
-    let select = papers.select(1, Status::Approved as u8 as u64);
-    let pid = fastrand::choice(
-        select
-            .iter()
-            .filter_map(|e| e.ok().map(|lazy| lazy.id()))
-            .collect::<Vec<u64>>()
-            .await
-            .into_iter(),
+#[derive(Debug, Serialize)]
+pub struct GetManyRes {
+    /// Papers found by their pids, omitting missing ones.
+    pub papers: BTreeMap<Pid, Paper>,
+}
+
+/// Gets papers in all statuses by their pids.
+///
+/// Pids are deduplicated, and pids in the same chunk are read in
+/// one pass of the chunk.
+pub async fn get_many<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Json(GetManyReq { pids }): Json<GetManyReq>,
+) -> Result<Json<GetManyRes>, Error> {
+    let pids: BTreeSet<Pid> = pids.into_iter().collect();
+    if pids.len() > MAX_GET_MANY {
+        return Err(Error::TooManyPids);
+    }
+    let papers = read_many(
+        &papers,
+        &metrics,
+        config.layout.papers_items_per_chunk(),
+        &pids,
     )
-    .ok_or(Error::NoPaper)?;
+    .await;
+    Ok(Json(GetManyRes { papers }))
+}
+
+/// Reads papers of the given pids, reading pids in the same chunk
+/// in one pass of the chunk.
+async fn read_many<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    items_per_chunk: u64,
+    pids: &BTreeSet<Pid>,
+) -> BTreeMap<Pid, Paper> {
+    let mut chunks: BTreeMap<u64, Vec<Pid>> = BTreeMap::new();
+    for &pid in pids {
+        chunks.entry(pid.0 / items_per_chunk).or_default().push(pid);
+    }
+
+    let mut ret = BTreeMap::new();
+    for chunk in chunks.into_values() {
+        let select = papers
+            .select(0, chunk[0].0)
+            .hints(chunk.iter().map(|pid| pid.0));
+        let mut papers_iter = select.iter();
+        while let Some(Ok(lazy)) = papers_iter.next().await {
+            if !pids.contains(&Pid(lazy.id())) {
+                continue;
+            }
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+                ret.insert(paper.pid, paper.clone());
+            }
+        }
+    }
+    ret
+}
 
-    let select = papers.select(0, pid).hint(pid);
+/// Count of papers in a status.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusCount {
+    pub status: Status,
+    pub count: usize,
+}
+
+/// Counts papers in every status.
+pub async fn stats<Io: IoHandle>(
+    State(Global {
+        papers, metrics, ..
+    }): State<Global<Io>>,
+) -> Json<Vec<StatusCount>> {
+    let mut ret = Vec::with_capacity(Status::all().len());
+    for &status in Status::all() {
+        let count = count(&papers, &metrics, status).await;
+        ret.push(StatusCount { status, count });
+    }
+    Json(ret)
+}
+
+/// Counts papers in the given status.
+pub async fn count<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    status: Status,
+) -> usize {
+    let select = status.select(papers);
     let mut papers_iter = select.iter();
+    let mut count = 0;
     while let Some(Ok(lazy)) = papers_iter.next().await {
-        if lazy.id() == pid {
-            if let Ok(val) = lazy.get().await {
-                return Ok(Json(val.clone()));
-            }
+        // moved values are left in their previous chunks
+        if metrics
+            .read(lazy.id(), lazy.get().await)
+            .is_some_and(|paper| paper.status == status)
+        {
+            count += 1;
         }
     }
+    count
+}
 
-    Err(Error::Db)
+/// Period of buckets in a histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    #[default]
+    Day,
+    /// Weeks starting on Monday.
+    Week,
+    Month,
 }
 
-pub async fn unprocessed<Io: IoHandle>(
-    State(Global { papers, .. }): State<Global<Io>>,
-) -> Json<Vec<Out>> {
-    let select = papers.select(1, Status::Pending as u8 as u64);
+impl GroupBy {
+    /// First day of the bucket containing the given date.
+    fn bucket(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GroupBy::Day => date,
+            GroupBy::Week => date - chrono::Days::new(date.weekday().num_days_from_monday().into()),
+            GroupBy::Month => date.with_day(1).expect("first day of a month should exist"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistogramQuery {
+    /// Status of papers to count, or all papers if absent.
+    #[serde(default)]
+    pub status: Option<Status>,
+    /// Counts papers posted on or after this day.
+    #[serde(default)]
+    pub since: Option<NaiveDate>,
+    /// Counts papers posted on or before this day.
+    #[serde(default)]
+    pub until: Option<NaiveDate>,
+    #[serde(default)]
+    pub group_by: GroupBy,
+    /// Pid to continue a truncated scan from.
+    #[serde(default)]
+    pub from: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistogramRes {
+    /// Counts of papers keyed by the first day of their buckets,
+    /// omitting empty buckets.
+    pub buckets: BTreeMap<NaiveDate, usize>,
+    #[serde(flatten)]
+    pub scan: Scan,
+}
+
+/// Counts papers by the UTC day, week or month they were posted in.
+///
+/// This scans all papers, so the scan is bounded by
+/// [`crate::Config::max_scan`], and counts of a truncated scan
+/// should be summed with those continued from `next`.
+pub async fn histogram<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(query): Query<HistogramQuery>,
+) -> Result<Json<HistogramRes>, Error> {
+    if query
+        .since
+        .zip(query.until)
+        .is_some_and(|(since, until)| since > until)
+    {
+        return Err(Error::InvalidRange);
+    }
+
+    let mut buckets = BTreeMap::new();
+    let scan = scan::scan(
+        &papers,
+        config.layout.papers_items_per_chunk(),
+        query.from,
+        config.max_scan,
+        |id, val| {
+            let Some(val) = metrics.read(id, val) else {
+                return;
+            };
+            let date = val.time.date_naive();
+            if query.status.is_none_or(|status| val.status == status)
+                && query.since.is_none_or(|since| date >= since)
+                && query.until.is_none_or(|until| date <= until)
+            {
+                *buckets.entry(query.group_by.bucket(date)).or_default() += 1;
+            }
+        },
+    )
+    .await;
+    Ok(Json(HistogramRes { buckets, scan }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueQuery {
+    #[serde(default = "QueueQuery::default_limit")]
+    pub limit: usize,
+}
+
+impl QueueQuery {
+    /// Maximum count of papers in a queue.
+    const MAX_LIMIT: usize = 100;
+
+    #[inline]
+    fn default_limit() -> usize {
+        20
+    }
+}
+
+/// Gets the oldest pending papers, sorted by time ascending,
+/// so older papers are never starved by newer ones.
+///
+/// Prioritized papers are sorted ahead of the others.
+pub async fn queue<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(QueueQuery { limit }): Query<QueueQuery>,
+) -> Json<Vec<MngOut>> {
+    let select = Status::Pending.select(&papers);
     let mut papers_iter = select.iter();
 
     let mut ret = Vec::new();
     while let Some(Ok(lazy)) = papers_iter.next().await {
-        if let Ok(val) = lazy.get().await {
-            ret.push(val.to_out());
+        if let Some(val) = metrics.read(lazy.id(), lazy.get().await) {
+            ret.push(val.to_mng_out(config.display_timezone));
         }
     }
+    ret.sort_by_key(|paper| (!paper.priority, paper.time, paper.pid));
+    ret.truncate(limit.min(QueueQuery::MAX_LIMIT));
     Json(ret)
 }
 
+/// Streams newly posted papers as server-sent events.
+///
+/// Receivers lagging behind are dropped, and clients should
+/// reconnect and refetch unprocessed papers.
+pub async fn stream<Io: IoHandle>(
+    State(Global { paper_events, .. }): State<Global<Io>>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let format = PidFormat::current();
+    let stream = BroadcastStream::new(paper_events.subscribe())
+        .take_while(Result::is_ok)
+        .filter_map(move |event| {
+            let event = event.ok()?;
+            format.sync_scope(|| sse::Event::default().event("paper").json_data(event).ok())
+        })
+        .map(Ok);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApprRejReq {
-    pub pid: u64,
+    pub pid: Pid,
+    /// Expected revision of the paper, which is not checked if absent.
+    #[serde(default)]
+    pub rev: Option<u64>,
 }
 
+#[instrument(skip_all, fields(pid = pid.0))]
 pub async fn approve<Io: IoHandle>(
-    State(Global { papers, .. }): State<Global<Io>>,
-    Json(ApprRejReq { pid }): Json<ApprRejReq>,
+    State(global): State<Global<Io>>,
+    actor: Actor,
+    Json(ApprRejReq { pid, rev }): Json<ApprRejReq>,
+) -> Result<(), Error> {
+    let Global {
+        papers,
+        audit,
+        config,
+        read_only,
+        metrics,
+        ..
+    } = &global;
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    if config.author_approve_interval_secs > 0 {
+        let items_per_chunk = config.layout.papers_items_per_chunk();
+        let pids = BTreeSet::from([pid]);
+        // read ahead, as the chunk is locked while approving
+        if let Some(paper) = read_many(papers, metrics, items_per_chunk, &pids)
+            .await
+            .remove(&pid)
+            .filter(|paper| paper.status == Status::Pending)
+        {
+            check_author_interval(papers, audit, metrics, config, &paper).await?;
+        }
+    }
+    info!("approving paper {pid} as {actor}");
+    if process_pending(
+        &global,
+        pid,
+        rev,
+        Status::Approved,
+        audit::Action::Approve,
+        actor,
+    )
+    .await?
+    {
+        Ok(())
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// Checks that no other paper of the same name or email as the given
+/// paper was approved within [`Config::author_approve_interval_secs`],
+/// by approvals recorded in the audit log.
+///
+/// Approvals beyond [`Config::max_scan`] are not checked.
+async fn check_author_interval<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    audit: &dmds::World<audit::AuditEntry, 1, Io>,
+    metrics: &Metrics,
+    config: &Config,
+    paper: &Paper,
 ) -> Result<(), Error> {
-    let select = papers.select(0, pid).hint(pid);
+    let now = Utc::now();
+    let interval = i64::try_from(config.author_approve_interval_secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .unwrap_or(chrono::Duration::MAX);
+    let since = now
+        .checked_sub_signed(interval)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let (approvals, scan) = audit::approvals_since(audit, metrics, since, config.max_scan).await;
+    if scan.truncated {
+        warn!("approvals since {since} exceeded the scan cap, checking the earliest only");
+    }
+
+    let pids = approvals
+        .iter()
+        .map(|&(pid, _)| pid)
+        .filter(|&pid| pid != paper.pid)
+        .collect();
+    let approved = read_many(
+        papers,
+        metrics,
+        config.layout.papers_items_per_chunk(),
+        &pids,
+    )
+    .await;
+    let last = approvals
+        .into_iter()
+        .filter(|(pid, _)| {
+            approved.get(pid).is_some_and(|other| {
+                other.name == paper.name || (other.email.is_some() && other.email == paper.email)
+            })
+        })
+        .map(|(_, time)| time)
+        .max();
+    match last {
+        Some(last) => {
+            let until = last
+                .checked_add_signed(interval)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            let remaining = (until - now).num_seconds().max(1);
+            Err(Error::AuthorCooldown(remaining as u64))
+        }
+        None => Ok(()),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PriorityReq {
+    pub pid: Pid,
+    pub priority: bool,
+    /// Expected revision of the paper, which is not checked if absent.
+    #[serde(default)]
+    pub rev: Option<u64>,
+}
+
+/// Pins a pending paper to the top of the review queue, or unpins it.
+#[instrument(skip_all, fields(pid = pid.0))]
+pub async fn set_priority<Io: IoHandle>(
+    State(Global {
+        papers,
+        audit,
+        read_only,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(PriorityReq { pid, priority, rev }): Json<PriorityReq>,
+) -> Result<(), Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let select = pid.select(&papers);
     let mut papers_iter = select.iter();
 
     while let Some(Ok(mut lazy)) = papers_iter.next().await {
         if lazy.id() == pid {
-            if let Ok(paper) = lazy.get_mut().await {
-                info!("approving paper {pid}");
-                paper.approve();
-                return lazy.close().await.map_err(|err| {
-                    error!("failed to approve paper: {err}");
-                    Error::Db
-                });
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                if paper.status != Status::Pending {
+                    break;
+                }
+                paper.check_rev(rev)?;
+                info!("setting priority of paper {pid} to {priority} as {actor}");
+                paper.set_priority(priority);
+                lazy.close().await?;
+                let action = if priority {
+                    audit::Action::Prioritize
+                } else {
+                    audit::Action::Deprioritize
+                };
+                audit::record(&audit, action, pid, actor).await;
+                return Ok(());
             }
         }
     }
@@ -295,22 +2594,910 @@ pub async fn approve<Io: IoHandle>(
     Err(Error::NotFound)
 }
 
+/// Notifies the author of a processed paper by the given template,
+/// logging failures as the processing itself is done.
+async fn notify(
+    mailer: &dyn Mailer,
+    smtp: &Smtp,
+    email: lettre::Address,
+    template: &Template,
+    paper: &Paper,
+    config: &Config,
+) -> bool {
+    let msg = match mail::notification(smtp.from.clone(), email, template, paper, config) {
+        Ok(msg) => msg,
+        Err(err) => {
+            error!(
+                "failed to build notification email of paper {}: {err}",
+                paper.pid
+            );
+            return false;
+        }
+    };
+    if let Err(err) = mailer.send(msg).await {
+        error!(
+            "failed to send notification email of paper {}: {err}",
+            paper.pid
+        );
+        return false;
+    }
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResendEmailReq {
+    pub pid: Pid,
+}
+
+/// Re-sends the notification email of a processed paper, which is the
+/// approval email of approved papers, or the rejection email of rejected
+/// papers if its template is configured.
+///
+/// Re-sends of the same paper are limited by
+/// [`Config::resend_email_cooldown_secs`].
+#[instrument(skip_all, fields(pid = pid.0))]
+pub async fn resend_email<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        mailer,
+        templates,
+        resend_cooldown,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(ResendEmailReq { pid }): Json<ResendEmailReq>,
+) -> Result<(), Error> {
+    let paper = read_many(
+        &papers,
+        &metrics,
+        config.layout.papers_items_per_chunk(),
+        &BTreeSet::from([pid]),
+    )
+    .await
+    .remove(&pid)
+    .ok_or(Error::NotFound)?;
+    let template = match paper.status {
+        Status::Approved => Some(&templates.approval),
+        Status::Rejected => templates.rejection.as_ref(),
+        Status::Pending => None,
+    };
+    let (Some(template), Some(email)) = (template, paper.email.clone()) else {
+        return Err(Error::NotFound);
+    };
+    let (Some(mailer), Some(smtp)) = (mailer, &config.smtp) else {
+        return Err(Error::EmailUnsent);
+    };
+    resend_cooldown
+        .hit(&pid, Duration::from_secs(config.resend_email_cooldown_secs))
+        .map_err(Error::ResendCooldown)?;
+    info!("re-sending notification email of paper {pid} as {actor}");
+    if notify(&*mailer, smtp, email, template, &paper, &config).await {
+        Ok(())
+    } else {
+        Err(Error::EmailUnsent)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PreviewEmailQuery {
+    /// Pid of the paper to preview the email of, in any status.
+    #[serde(default)]
+    pub pid: Option<Pid>,
+    /// Author name of a sample paper, used if no pid is given.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PreviewEmailRes {
+    /// Email of the author, or absent if no email would be sent.
+    pub to: Option<lettre::Address>,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Renders the approval email of the given paper, or of a sample paper,
+/// without sending anything.
+pub async fn preview_email<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        metrics,
+        templates,
+        ..
+    }): State<Global<Io>>,
+    Query(PreviewEmailQuery { pid, name }): Query<PreviewEmailQuery>,
+) -> Result<Json<PreviewEmailRes>, Error> {
+    let paper = match pid {
+        Some(pid) => read_many(
+            &papers,
+            &metrics,
+            config.layout.papers_items_per_chunk(),
+            &BTreeSet::from([pid]),
+        )
+        .await
+        .remove(&pid)
+        .ok_or(Error::NotFound)?,
+        None => Paper {
+            name: name.unwrap_or_else(|| "Author".to_owned()),
+            info: String::new(),
+            email: None,
+            pid: Pid(0),
+            time: Utc::now(),
+            status: Status::Pending,
+            color: String::new(),
+            ip: None,
+            rev: 0,
+            views: 0,
+            priority: false,
+            image_url: None,
+            approved_at: None,
+            received_at: Utc::now(),
+        },
+    };
+    let (subject, body) = templates.approval.render(&paper, &config);
+    Ok(Json(PreviewEmailRes {
+        to: paper.email,
+        subject,
+        body,
+    }))
+}
+
+/// Paper exported from the legacy board.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Legacy {
+    pub author: String,
+    pub text: String,
+    pub approved: bool,
+    pub created: DateTime<Utc>,
+}
+
+impl From<Legacy> for Paper {
+    fn from(value: Legacy) -> Self {
+        Self {
+            name: value.author,
+            info: value.text,
+            email: None,
+            pid: new_pid(),
+            time: value.created,
+            status: if value.approved {
+                Status::Approved
+            } else {
+                Status::Pending
+            },
+            color: "#ffffcc".to_owned(),
+            ip: None,
+            rev: 0,
+            views: 0,
+            priority: false,
+            image_url: None,
+            approved_at: None,
+            received_at: value.created,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportRes {
+    pub imported: usize,
+    pub skipped: Vec<Skipped>,
+    /// Error which stopped the import early, such as a truncated body.
+    ///
+    /// Entries before the error are still imported.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Entry skipped when importing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Skipped {
+    /// Index of the entry in the request.
+    pub index: usize,
+    pub error: String,
+}
+
+/// Interval of entries between progress logs of imports.
+const IMPORT_PROGRESS_INTERVAL: usize = 1000;
+
+/// Imports papers exported from the legacy board, with fresh pids.
+///
+/// The body is a JSON array, which is parsed and imported one entry
+/// at a time as it streams in, so it is never held in memory as a whole.
+/// Malformed entries and entries failed to save are skipped and
+/// reported instead of aborting the whole import.
+pub async fn import_legacy<Io: IoHandle>(
+    State(Global {
+        papers,
+        config,
+        read_only,
+        approved_pids,
+        recent_posts,
+        ..
+    }): State<Global<Io>>,
+    body: Body,
+) -> Result<Json<ImportRes>, Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+
+    let mut res = ImportRes {
+        imported: 0,
+        skipped: vec![],
+        error: None,
+    };
+    let mut elements = json::Elements::default();
+    let mut parsed = vec![];
+    let mut index = 0;
+    let mut stream = body.into_data_stream();
+    loop {
+        let chunk = match stream.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(err)) => {
+                res.error = Some(err.to_string());
+                break;
+            }
+            None => {
+                if let Err(err) = elements.finish() {
+                    res.error = Some(err.to_string());
+                }
+                break;
+            }
+        };
+        let split = elements.feed(&chunk, &mut parsed);
+
+        'entries: for entry in parsed.drain(..) {
+            let entry_index = index;
+            index += 1;
+            if index % IMPORT_PROGRESS_INTERVAL == 0 {
+                info!("importing legacy papers, {index} entries parsed");
+            }
+            let mut paper: Paper = match serde_json::from_slice::<Legacy>(&entry) {
+                Ok(legacy) => legacy.into(),
+                Err(err) => {
+                    res.skipped.push(Skipped {
+                        index: entry_index,
+                        error: err.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let hash = Paper::content_hash(&paper.name, &paper.info);
+            let received_at = paper.received_at;
+            for _ in 0..=config.pid_retries {
+                let pid = paper.pid;
+                if let Err(err) = load_chunk_checked(&papers, &paper).await {
+                    error!("failed to load chunk of imported paper {pid}: {err}");
+                    res.skipped.push(Skipped {
+                        index: entry_index,
+                        error: Error::Unsaved.to_string(),
+                    });
+                    continue 'entries;
+                }
+                match papers.try_insert(paper).await {
+                    Ok(()) => {
+                        recent_posts.record(hash, pid, received_at).await;
+                        res.imported += 1;
+                        continue 'entries;
+                    }
+                    Err(p) => {
+                        paper = p;
+                        paper.pid = new_pid();
+                    }
+                }
+            }
+            res.skipped.push(Skipped {
+                index: entry_index,
+                error: Error::PidConflict.to_string(),
+            });
+        }
+
+        if let Err(err) = split {
+            res.error = Some(err.to_string());
+            break;
+        }
+    }
+
+    approved_pids.invalidate().await;
+    info!(
+        "imported {} legacy papers, skipped {}",
+        res.imported,
+        res.skipped.len()
+    );
+    if let Some(err) = &res.error {
+        warn!("legacy import stopped early: {err}");
+    }
+    Ok(Json(res))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmQuery {
+    pub confirm: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApproveAllRes {
+    pub approved: usize,
+    pub failed: usize,
+}
+
+/// Approves all pending papers, notifying their authors the same as
+/// [`approve`].
+///
+/// Papers are checked against [`Config::author_approve_interval_secs`]
+/// one by one, including approvals earlier in the same call, so only the
+/// first paper of each author is approved and the others are counted as
+/// failed, to be approved later.
+///
+/// The `confirm` query parameter should match the configured
+/// confirmation. Failures of single papers don't stop the others.
+pub async fn approve_all<Io: IoHandle>(
+    State(global): State<Global<Io>>,
+    actor: Actor,
+    Query(ConfirmQuery { confirm }): Query<ConfirmQuery>,
+) -> Result<Json<ApproveAllRes>, Error> {
+    let Global {
+        papers,
+        audit,
+        config,
+        read_only,
+        metrics,
+        ..
+    } = &global;
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    if config.approve_all_confirm.as_ref() != Some(&confirm) {
+        return Err(Error::Unconfirmed);
+    }
+    let mut res = ApproveAllRes {
+        approved: 0,
+        failed: 0,
+    };
+    // collected first, as chunks are locked while approving
+    let mut pending = vec![];
+    let select = Status::Pending.select(papers);
+    let mut papers_iter = select.iter();
+    while let Some(lazy) = papers_iter.next().await {
+        let Ok(lazy) = lazy else {
+            // iterators can't be resumed after failed reads
+            res.failed += 1;
+            break;
+        };
+        if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+            if paper.status == Status::Pending {
+                pending.push(paper.clone());
+            }
+        }
+    }
+    drop(papers_iter);
+
+    for paper in pending {
+        let pid = paper.pid;
+        if config.author_approve_interval_secs > 0 {
+            if let Err(err) = check_author_interval(papers, audit, metrics, config, &paper).await {
+                info!("skipping paper {pid}: {err}");
+                res.failed += 1;
+                continue;
+            }
+        }
+        match process_pending(
+            &global,
+            pid,
+            None,
+            Status::Approved,
+            audit::Action::ApproveAll,
+            actor,
+        )
+        .await
+        {
+            Ok(true) => res.approved += 1,
+            // processed by others since collected
+            Ok(false) => {}
+            Err(err) => {
+                error!("failed to approve paper {pid}: {err}");
+                res.failed += 1;
+            }
+        }
+    }
+
+    info!(
+        "approved {} pending papers, {} failed",
+        res.approved, res.failed
+    );
+    Ok(Json(res))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RangeReq {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectRangeRes {
+    pub rejected: usize,
+    pub failed: usize,
+}
+
+/// Rejects all pending papers received in the given time range,
+/// both ends inclusive, for cleaning up spam waves.
+///
+/// Papers are matched by [`Paper::received_at`], so supplied
+/// times of papers don't move them out of the range. Authors are
+/// notified the same as [`reject`].
+///
+/// The `confirm` query parameter should match the configured
+/// confirmation. Failures of single papers don't stop the others.
+pub async fn reject_range<Io: IoHandle>(
+    State(global): State<Global<Io>>,
+    actor: Actor,
+    Query(ConfirmQuery { confirm }): Query<ConfirmQuery>,
+    Json(RangeReq { since, until }): Json<RangeReq>,
+) -> Result<Json<RejectRangeRes>, Error> {
+    let Global {
+        papers,
+        config,
+        read_only,
+        metrics,
+        ..
+    } = &global;
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    if config.reject_range_confirm.as_ref() != Some(&confirm) {
+        return Err(Error::Unconfirmed);
+    }
+    if since > until {
+        return Err(Error::InvalidRange);
+    }
+    let mut res = RejectRangeRes {
+        rejected: 0,
+        failed: 0,
+    };
+    // collected first, as chunks are locked while rejecting
+    let mut pids = vec![];
+    let select = Status::Pending.select(papers);
+    let mut papers_iter = select.iter();
+    while let Some(lazy) = papers_iter.next().await {
+        let Ok(lazy) = lazy else {
+            // iterators can't be resumed after failed reads
+            res.failed += 1;
+            break;
+        };
+        if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+            if paper.status == Status::Pending && (since..=until).contains(&paper.received_at) {
+                info!(
+                    "rejecting paper {} received at {} in range as {actor}",
+                    paper.pid, paper.received_at
+                );
+                pids.push(paper.pid);
+            }
+        }
+    }
+    drop(papers_iter);
+
+    for pid in pids {
+        match process_pending(
+            &global,
+            pid,
+            None,
+            Status::Rejected,
+            audit::Action::RejectRange,
+            actor,
+        )
+        .await
+        {
+            Ok(true) => res.rejected += 1,
+            // processed by others since collected
+            Ok(false) => {}
+            Err(err) => {
+                error!("failed to reject paper {pid}: {err}");
+                res.failed += 1;
+            }
+        }
+    }
+
+    info!(
+        "rejected {} pending papers received from {since} to {until}, {} failed",
+        res.rejected, res.failed
+    );
+    Ok(Json(res))
+}
+
+#[instrument(skip_all, fields(pid = pid.0))]
 pub async fn reject<Io: IoHandle>(
-    State(Global { papers, .. }): State<Global<Io>>,
-    Json(ApprRejReq { pid }): Json<ApprRejReq>,
+    State(Global {
+        papers,
+        audit,
+        config,
+        read_only,
+        metrics,
+        mailer,
+        templates,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(ApprRejReq { pid, rev }): Json<ApprRejReq>,
 ) -> Result<(), Error> {
-    let select = papers.select(0, pid).hint(pid);
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let select = pid.select(&papers);
     let mut papers_iter = select.iter();
 
-    while let Some(Ok(lazy)) = papers_iter.next().await {
+    while let Some(Ok(mut lazy)) = papers_iter.next().await {
         if lazy.id() == pid {
-            info!("rejecting paper {pid}");
-            return lazy.destroy().await.map_err(|err| {
-                error!("failed to remove paper: {err}");
-                Error::Db
-            });
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                if paper.status != Status::Pending {
+                    break;
+                }
+                paper.check_rev(rev)?;
+                info!("rejecting paper {pid} as {actor}");
+                paper.reject();
+                clear_tombstone(&papers, paper).await?;
+                let notify_to = paper.email.clone().map(|email| (email, paper.clone()));
+                lazy.close().await?;
+                audit::record(&audit, audit::Action::Reject, pid, actor).await;
+
+                if let (Some(mailer), Some(smtp), Some(template), Some((email, paper))) =
+                    (mailer, &config.smtp, &templates.rejection, notify_to)
+                {
+                    notify(&*mailer, smtp, email, template, &paper, &config).await;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ByContentReq {
+    pub name: String,
+    pub info: String,
+    /// Pid to continue a truncated scan from.
+    #[serde(default)]
+    pub from: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ByContentRes {
+    /// Count of papers processed.
+    pub count: usize,
+    /// Count of matched papers failed to process.
+    #[serde(default)]
+    pub failed: usize,
+    #[serde(flatten)]
+    pub scan: Scan,
+}
+
+/// Approves all pending papers with the given content,
+/// for when the pid is unknown.
+pub async fn approve_by_content<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    actor: Actor,
+    Json(req): Json<ByContentReq>,
+) -> Result<Json<ByContentRes>, Error> {
+    by_content(state, actor, req, Status::Approved).await
+}
+
+/// Rejects all pending papers with the given content,
+/// for when the pid is unknown.
+pub async fn reject_by_content<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    actor: Actor,
+    Json(req): Json<ByContentReq>,
+) -> Result<Json<ByContentRes>, Error> {
+    by_content(state, actor, req, Status::Rejected).await
+}
+
+/// Moves pending papers matching the content fingerprint
+/// of the given request to the given status.
+///
+/// There is no dimension of content, so this scans papers bounded by
+/// [`Config::max_scan`] the same as [`list`]. Failures of single papers
+/// don't stop the others, and processed papers are still notified.
+async fn by_content<Io: IoHandle>(
+    global: Global<Io>,
+    actor: Actor,
+    ByContentReq { name, info, from }: ByContentReq,
+    status: Status,
+) -> Result<Json<ByContentRes>, Error> {
+    let Global {
+        papers,
+        config,
+        read_only,
+        metrics,
+        ..
+    } = &global;
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let hash = Paper::content_hash(&name, &info);
+    let mut pids = vec![];
+    let scan = scan::scan(
+        papers,
+        config.layout.papers_items_per_chunk(),
+        from,
+        config.max_scan,
+        |id, paper| {
+            if let Some(paper) = metrics.read(id, paper) {
+                if paper.status == Status::Pending
+                    && Paper::content_hash(&paper.name, &paper.info) == hash
+                {
+                    pids.push(paper.pid);
+                }
+            }
+        },
+    )
+    .await;
+
+    let mut res = ByContentRes {
+        count: 0,
+        failed: 0,
+        scan,
+    };
+    let action = match status {
+        Status::Approved => audit::Action::ApproveByContent,
+        _ => audit::Action::RejectByContent,
+    };
+    for pid in pids {
+        info!("moving paper {pid} to {status:?} by content as {actor}");
+        match process_pending(&global, pid, None, status, action, actor).await {
+            Ok(true) => res.count += 1,
+            // processed by others since scanned
+            Ok(false) => {}
+            Err(err) => {
+                error!("failed to move paper {pid} to {status:?}: {err}");
+                res.failed += 1;
+            }
+        }
+    }
+
+    if res.count == 0 && res.failed == 0 && !res.scan.truncated {
+        return Err(Error::NotFound);
+    }
+    Ok(Json(res))
+}
+
+/// Moves the given paper to the given status if it's pending, then
+/// records the action and notifies the author of the paper, returning
+/// whether the paper was pending.
+///
+/// This is shared by processing of single and many papers, so each
+/// processed paper is published, audited and notified the same.
+async fn process_pending<Io: IoHandle>(
+    Global {
+        papers,
+        audit,
+        config,
+        metrics,
+        mailer,
+        templates,
+        ticker,
+        approved_pids,
+        ..
+    }: &Global<Io>,
+    pid: Pid,
+    rev: Option<u64>,
+    status: Status,
+    action: audit::Action,
+    actor: Actor,
+) -> Result<bool, Error> {
+    let Some(paper) = move_pending(papers, metrics, pid, rev, status).await? else {
+        return Ok(false);
+    };
+    let template = match status {
+        Status::Approved => {
+            approved_pids.invalidate().await;
+            ticker.publish(paper.to_out());
+            Some(&templates.approval)
+        }
+        _ => templates.rejection.as_ref(),
+    };
+    audit::record(audit, action, pid, actor).await;
+
+    if let (Some(mailer), Some(smtp), Some(template), Some(email)) =
+        (mailer, &config.smtp, template, paper.email.clone())
+    {
+        notify(&**mailer, smtp, email, template, &paper, config).await;
+    }
+    Ok(true)
+}
+
+/// Moves the given paper to the given status if it's pending,
+/// returning the moved paper, or `None` if it's not pending.
+async fn move_pending<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    pid: Pid,
+    rev: Option<u64>,
+    status: Status,
+) -> Result<Option<Paper>, Error> {
+    let select = pid.select(papers);
+    let mut papers_iter = select.iter();
+    while let Some(lazy) = papers_iter.next().await {
+        // iterators can't be resumed after failed reads
+        let mut lazy = lazy?;
+        if lazy.id() != pid {
+            continue;
+        }
+        let Some(paper) = metrics.read(lazy.id(), lazy.get_mut().await) else {
+            return Ok(None);
+        };
+        if paper.status != Status::Pending {
+            return Ok(None);
+        }
+        paper.check_rev(rev)?;
+        let before = paper.clone();
+        match status {
+            Status::Approved => paper.approve(),
+            Status::Rejected => paper.reject(),
+            Status::Pending => unreachable!("papers are already pending"),
+        }
+        if let Err(err) = clear_tombstone(papers, paper).await {
+            // nothing is moved yet, so the paper is left pending
+            error!("failed to move paper {pid} to {status:?}: {err}");
+            *paper = before;
+            return Err(Error::Unsaved);
+        }
+        let moved = paper.clone();
+        // the chunk is buffered, so this fails only if it's evicted
+        lazy.close().await?;
+        return Ok(Some(moved));
+    }
+    Ok(None)
+}
+
+/// Removes the tombstone of the given paper from the chunk of its status,
+/// which is left there if the paper was moved out of that status before.
+///
+/// Values moved into chunks holding a tombstone of the same id are silently
+/// dropped by dmds, so this must be called before closing a status change.
+///
+/// This also buffers the chunk, so closing the change finds it buffered,
+/// and failures of reading the chunk are retried once.
+async fn clear_tombstone<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    paper: &Paper,
+) -> Result<(), dmds::Error> {
+    let chunk = match load_chunk_checked(papers, paper).await {
+        Err(dmds::Error::Io(err)) => {
+            warn!("retrying loading chunk of paper {}: {err}", paper.pid);
+            load_chunk_checked(papers, paper).await?
+        }
+        result => result?,
+    };
+    chunk.remove(paper.pid.0).await;
+    Ok(())
+}
+
+/// Loads the chunk the given paper should be stored in.
+///
+/// dmds loads chunks failed to read as empty ones, which would overwrite
+/// the stored chunks once flushed, so unbuffered chunks are read first
+/// to fail instead. Chunks not stored yet are loaded empty as usual.
+async fn load_chunk_checked<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    paper: &Paper,
+) -> Result<Arc<dmds::Chunk<Paper, 2>>, dmds::Error> {
+    let pos = papers.chunk_pos_of_data(paper)?;
+    if papers.chunk_buf_of_pos(pos).is_none() {
+        match papers.io_handle().read_chunk(pos).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(dmds::Error::Io(err)),
+        }
+    }
+    papers.chunk_buf_of_pos_or_load(pos).await
+}
+
+/// Brings a rejected paper back to pending.
+#[instrument(skip_all, fields(pid = pid.0))]
+pub async fn restore_rejected<Io: IoHandle>(
+    State(Global {
+        papers,
+        audit,
+        read_only,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(ApprRejReq { pid, rev }): Json<ApprRejReq>,
+) -> Result<(), Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let select = pid.select(&papers);
+    let mut papers_iter = select.iter();
+
+    while let Some(Ok(mut lazy)) = papers_iter.next().await {
+        if lazy.id() == pid {
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                if paper.status != Status::Rejected {
+                    break;
+                }
+                paper.check_rev(rev)?;
+                info!("restoring rejected paper {pid} as {actor}");
+                paper.restore();
+                clear_tombstone(&papers, paper).await?;
+                lazy.close().await?;
+                audit::record(&audit, audit::Action::Restore, pid, actor).await;
+                return Ok(());
+            }
         }
     }
 
     Err(Error::NotFound)
 }
+
+/// Removes rejected papers posted before the given time,
+/// returning the count of removed papers.
+pub async fn purge_rejected<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+    before: DateTime<Utc>,
+) -> Result<usize, Error> {
+    let expired = {
+        let select = Status::Rejected.select(papers);
+        let mut papers_iter = select.iter();
+        let mut expired = Vec::new();
+        while let Some(Ok(lazy)) = papers_iter.next().await {
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+                if paper.status == Status::Rejected && paper.time < before {
+                    expired.push(paper.clone());
+                }
+            }
+        }
+        expired
+    };
+
+    for paper in &expired {
+        remove(papers, paper).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Removes all papers in every status, returning the count of removed papers.
+pub async fn purge_all<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    metrics: &Metrics,
+) -> Result<usize, Error> {
+    let all = {
+        let select = papers.select_all();
+        let mut papers_iter = select.iter();
+        let mut all = Vec::new();
+        while let Some(lazy) = papers_iter.next().await {
+            let lazy = lazy?;
+            if let Some(paper) = metrics.read(lazy.id(), lazy.get().await) {
+                all.push(paper.clone());
+            }
+        }
+        all
+    };
+
+    for paper in &all {
+        remove(papers, paper).await?;
+    }
+    Ok(all.len())
+}
+
+/// Removes the given paper from the world.
+///
+/// This should be called without any living iterator of the paper's chunk,
+/// as removing requires writing the chunk, which is why `Lazy::destroy`
+/// deadlocks on the read guard it holds itself.
+async fn remove<Io: IoHandle>(
+    papers: &dmds::World<Paper, 2, Io>,
+    paper: &Paper,
+) -> Result<(), Error> {
+    papers
+        .chunk_buf_of_data_or_load(paper)
+        .await?
+        .remove(paper.pid.0)
+        .await;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::{fmt::Display, future::Future};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use dmds::{IoHandle, Select, World};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Identifier of a paper or a question, which is dimension `0`
+/// of their worlds.
+///
+/// This is serialized as a plain number, or a string in human readable
+/// formats if [`PidFormat::String`] is in effect, as pids may exceed
+/// the safe integers of JavaScript. Both are accepted when deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Pid(pub u64);
+
+/// Format of pids in JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PidFormat {
+    #[default]
+    Number,
+    String,
+}
+
+tokio::task_local! {
+    static FORMAT: PidFormat;
+}
+
+impl PidFormat {
+    /// Format in effect, which is [`PidFormat::Number`] out of any scope.
+    #[inline]
+    pub fn current() -> Self {
+        FORMAT.try_with(|format| *format).unwrap_or_default()
+    }
+
+    /// Runs the given future with this format in effect.
+    #[inline]
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        FORMAT.scope(self, f).await
+    }
+
+    /// Runs the given function with this format in effect,
+    /// for serializing out of the request, like in streamed bodies.
+    #[inline]
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        FORMAT.sync_scope(self, f)
+    }
+}
+
+/// Middleware serializing pids of every request in the given format.
+pub async fn middleware(State(format): State<PidFormat>, req: Request, next: Next) -> Response {
+    format.scope(next.run(req)).await
+}
+
+impl Serialize for Pid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() && PidFormat::current() == PidFormat::String {
+            serializer.collect_str(&self.0)
+        } else {
+            serializer.serialize_u64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Pid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Pid;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a pid as a number or a string")
+            }
+
+            #[inline]
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Pid, E> {
+                Ok(Pid(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Pid, E> {
+                u64::try_from(v)
+                    .map(Pid)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Pid, E> {
+                v.parse()
+                    .map(Pid)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        // binary formats like bincode are not self-describing
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(Visitor)
+        } else {
+            deserializer.deserialize_u64(Visitor)
+        }
+    }
+}
+
+impl From<u64> for Pid {
+    #[inline]
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Pid> for u64 {
+    #[inline]
+    fn from(value: Pid) -> Self {
+        value.0
+    }
+}
+
+/// Compares raw ids of dmds, like [`dmds::Lazy::id`], with pids.
+impl PartialEq<Pid> for u64 {
+    #[inline]
+    fn eq(&self, other: &Pid) -> bool {
+        *self == other.0
+    }
+}
+
+impl Display for Pid {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Pid {
+    /// Selects the chunk of this pid in the given world, hinting this pid.
+    #[inline]
+    pub fn select<T, const DIMS: usize, Io: IoHandle>(
+        self,
+        world: &World<T, DIMS, Io>,
+    ) -> Select<'_, T, DIMS, Io> {
+        world.select(0, self.0).hint(self.0)
+    }
+}
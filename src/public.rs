@@ -0,0 +1,35 @@
+use axum::extract::State;
+use dmds::IoHandle;
+use serde::{Deserialize, Serialize};
+
+use crate::{json::Json, Global};
+
+/// Client-relevant settings of the deployment.
+///
+/// Only non-sensitive settings should be added here.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicConfig {
+    pub board_title: Option<String>,
+    pub instructions: Option<String>,
+    pub max_name_len: usize,
+    pub max_info_len: usize,
+    pub min_info_len: usize,
+    /// Maximum count of links in a paper, or `0` for no limit.
+    pub max_links: usize,
+    pub require_email: bool,
+}
+
+/// Gets public settings, so frontends can adapt to the deployment.
+pub async fn config<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+) -> Json<PublicConfig> {
+    Json(PublicConfig {
+        board_title: config.board_title.clone(),
+        instructions: config.instructions.clone(),
+        max_name_len: config.max_name_len,
+        max_info_len: config.max_info_len,
+        min_info_len: config.min_info_len,
+        max_links: config.max_links,
+        require_email: config.require_email,
+    })
+}
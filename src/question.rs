@@ -1,21 +1,43 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::{net::IpAddr, sync::atomic::Ordering, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use bincode::Options as _;
 use chrono::{DateTime, Utc};
-use dmds::IoHandle;
-use serde::{Deserialize, Serialize};
-use siphasher::sip::SipHasher24;
+use dmds::{IoHandle, StreamExt};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::Global;
+use crate::{
+    bincode_options, cooldown,
+    crypt::{self, Cipher, StoredEmail},
+    ip::ClientIp,
+    json::Json,
+    locale::Localize,
+    mail,
+    mng::EmailQuery,
+    new_pid,
+    pid::Pid,
+    request::ErrContext,
+    scan::{self, Scan},
+    secret::Actor,
+    Global, Metrics, SubmissionsClosed,
+};
 
 /// Question from frontend.
 #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct In {
     pub name: String,
     pub info: String,
+    /// Email of the questioner, which may be in the `Name <address>` form.
+    #[serde(default, deserialize_with = "mail::deserialize_address")]
     pub email: Option<lettre::Address>,
 }
 
 /// Feedback to SubIT.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Question {
     /// Name of the questioner.
     pub name: String,
@@ -24,79 +46,202 @@ pub struct Question {
     /// Email address of the questioner.
     pub email: Option<lettre::Address>,
 
-    pub pid: u64,
+    pub pid: Pid,
+    /// Time of asking.
+    pub time: DateTime<Utc>,
+
+    /// IP address of the questioner.
+    pub ip: Option<IpAddr>,
+    /// Whether this question has been resolved.
+    pub resolved: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV1 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
+    time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV2 {
+    name: String,
+    info: String,
+    email: Option<lettre::Address>,
     time: DateTime<Utc>,
+    ip: Option<IpAddr>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Store {
+struct StoreV3 {
     name: String,
     info: String,
     email: Option<lettre::Address>,
     time: DateTime<Utc>,
+    ip: Option<IpAddr>,
+    resolved: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreV4 {
+    name: String,
+    info: String,
+    email: Option<StoredEmail>,
+    time: DateTime<Utc>,
+    ip: Option<IpAddr>,
+    resolved: bool,
+}
+
+/// Borrowed [`StoreV4`] to encode questions without cloning,
+/// which is serialized the same.
+#[derive(Debug, Serialize)]
+struct StoreRef<'a> {
+    name: &'a str,
+    info: &'a str,
+    email: Option<StoredEmail<&'a str>>,
+    time: DateTime<Utc>,
+    ip: Option<IpAddr>,
+    resolved: bool,
 }
 
 impl Question {
-    fn to_store(&self) -> Store {
-        Store {
-            name: self.name.clone(),
-            info: self.info.clone(),
-            email: self.email.clone(),
+    fn to_store(&self, cipher: Option<&Cipher>) -> std::io::Result<StoreRef<'_>> {
+        Ok(StoreRef {
+            name: &self.name,
+            info: &self.info,
+            email: self
+                .email
+                .as_ref()
+                .map(|email| crypt::seal(cipher, self.pid, email))
+                .transpose()?,
             time: self.time,
-        }
+            ip: self.ip,
+            resolved: self.resolved,
+        })
     }
 }
 
 impl From<In> for Question {
     fn from(value: In) -> Self {
-        let hash = {
-            use std::hash::{Hash, Hasher};
-            let mut hasher = SipHasher24::new();
-            value.hash(&mut hasher);
-            hasher.finish()
-        };
-
         Self {
             name: value.name,
             info: value.info,
             email: value.email,
-            pid: hash,
+            pid: new_pid(),
             time: Utc::now(),
+            ip: None,
+            resolved: false,
         }
     }
 }
 
 impl dmds::Data for Question {
     const DIMS: usize = 1;
-    const VERSION: u32 = 1;
+    const VERSION: u32 = 4;
 
     fn dim(&self, dim: usize) -> u64 {
         match dim {
-            0 => self.pid,
+            0 => self.pid.0,
             _ => unreachable!(),
         }
     }
 
+    #[inline]
     fn decode<B: bytes::Buf>(version: u32, dims: &[u64], buf: B) -> std::io::Result<Self> {
+        Self::decode_with(version, dims, buf, crypt::cipher())
+    }
+
+    #[inline]
+    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
+        self.encode_with(buf, crypt::cipher())
+    }
+}
+
+impl Question {
+    /// Decodes a question the same as [`dmds::Data::decode`], opening
+    /// sealed fields with the given cipher.
+    pub fn decode_with<B: bytes::Buf>(
+        version: u32,
+        dims: &[u64],
+        buf: B,
+        cipher: Option<&Cipher>,
+    ) -> std::io::Result<Self> {
         match version {
             1 => {
-                let inner: Store = bincode::deserialize_from(buf.reader())
-                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+                let inner: StoreV1 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
                 Ok(Self {
                     name: inner.name,
                     info: inner.info,
                     email: inner.email,
-                    pid: dims[0],
+                    pid: Pid(dims[0]),
                     time: inner.time,
+                    ip: None,
+                    resolved: false,
+                })
+            }
+            2 => {
+                let inner: StoreV2 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    pid: Pid(dims[0]),
+                    time: inner.time,
+                    ip: inner.ip,
+                    resolved: false,
+                })
+            }
+            3 => {
+                let inner: StoreV3 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner.email,
+                    pid: Pid(dims[0]),
+                    time: inner.time,
+                    ip: inner.ip,
+                    resolved: inner.resolved,
+                })
+            }
+            4 => {
+                let inner: StoreV4 = bincode_options()
+                    .deserialize_from(buf.reader())
+                    .map_err(std::io::Error::other)?;
+                let pid = Pid(dims[0]);
+                Ok(Self {
+                    name: inner.name,
+                    info: inner.info,
+                    email: inner
+                        .email
+                        .map(|email| crypt::open(cipher, pid, email))
+                        .transpose()?,
+                    pid,
+                    time: inner.time,
+                    ip: inner.ip,
+                    resolved: inner.resolved,
                 })
             }
             _ => unreachable!(),
         }
     }
 
-    fn encode<B: bytes::BufMut>(&self, buf: B) -> std::io::Result<()> {
-        bincode::serialize_into(buf.writer(), &self.to_store())
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+    /// Encodes this question the same as [`dmds::Data::encode`], sealing
+    /// personal fields with the given cipher.
+    pub fn encode_with<B: bytes::BufMut>(
+        &self,
+        buf: B,
+        cipher: Option<&Cipher>,
+    ) -> std::io::Result<()> {
+        bincode_options()
+            .serialize_into(buf.writer(), &self.to_store(cipher)?)
+            .map_err(std::io::Error::other)
     }
 }
 
@@ -106,6 +251,66 @@ pub enum Error {
     Db,
     #[error("pid conflicted")]
     PidConflict,
+    #[error("question contains disallowed content")]
+    Banned,
+    #[error("requiring question not found")]
+    NotFound,
+    #[error("name should not be empty")]
+    EmptyName,
+    #[error("info should not be empty")]
+    EmptyInfo,
+    #[error("email is required")]
+    EmailRequired,
+    #[error("service is read-only for maintenance")]
+    ReadOnly,
+    #[error("submitting too frequently, retry after {0} seconds")]
+    Cooldown(u64),
+    #[error(transparent)]
+    Closed(#[from] SubmissionsClosed),
+}
+
+impl Error {
+    /// Machine-readable code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Db => "db",
+            Error::PidConflict => "pid_conflict",
+            Error::Banned => "banned",
+            Error::NotFound => "not_found",
+            Error::EmptyName => "empty_name",
+            Error::EmptyInfo => "empty_info",
+            Error::EmailRequired => "email_required",
+            Error::ReadOnly => "read_only",
+            Error::Cooldown(_) => cooldown::RATE_LIMITED,
+            Error::Closed(_) => "closed",
+        }
+    }
+}
+
+impl From<dmds::Error> for Error {
+    /// Logs the given database error, which is only reported
+    /// to clients as [`Error::Db`].
+    fn from(err: dmds::Error) -> Self {
+        tracing::error!("database error: {err}");
+        Error::Db
+    }
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Db => "数据库内部错误".to_owned(),
+            Error::PidConflict => "编号冲突".to_owned(),
+            Error::Banned => "问题包含不允许的内容".to_owned(),
+            Error::NotFound => "未找到该问题".to_owned(),
+            Error::EmptyName => "名字不能为空".to_owned(),
+            Error::EmptyInfo => "内容不能为空".to_owned(),
+            Error::EmailRequired => "需要填写邮箱".to_owned(),
+            Error::ReadOnly => "服务维护中，暂时只读".to_owned(),
+            Error::Cooldown(secs) => format!("提交过于频繁，请在 {secs} 秒后重试"),
+            Error::Closed(err) => err.zh(),
+        }
+    }
 }
 
 impl IntoResponse for Error {
@@ -113,15 +318,30 @@ impl IntoResponse for Error {
         #[derive(Serialize)]
         struct JErr {
             error: String,
+            code: &'static str,
+            #[serde(flatten)]
+            context: ErrContext,
         }
 
+        if let Error::Cooldown(secs) = self {
+            return cooldown::rate_limited(secs, self.localized());
+        }
         (
             match self {
                 Error::Db => StatusCode::INTERNAL_SERVER_ERROR,
                 Error::PidConflict => StatusCode::CONFLICT,
+                Error::Banned | Error::EmptyName | Error::EmptyInfo | Error::EmailRequired => {
+                    StatusCode::BAD_REQUEST
+                }
+                Error::NotFound => StatusCode::NOT_FOUND,
+                Error::ReadOnly => StatusCode::SERVICE_UNAVAILABLE,
+                Error::Cooldown(_) => StatusCode::TOO_MANY_REQUESTS,
+                Error::Closed(_) => StatusCode::FORBIDDEN,
             },
             Json(JErr {
-                error: self.to_string(),
+                error: self.localized(),
+                code: self.code(),
+                context: ErrContext::current(),
             }),
         )
             .into_response()
@@ -129,16 +349,348 @@ impl IntoResponse for Error {
 }
 
 pub async fn new<Io: IoHandle>(
-    State(Global { questions, .. }): State<Global<Io>>,
+    State(Global {
+        questions,
+        config,
+        metrics,
+        read_only,
+        email_cooldown,
+        ..
+    }): State<Global<Io>>,
+    ClientIp(ip): ClientIp,
     Json(question): Json<In>,
 ) -> Result<(), Error> {
-    let result = questions.insert(question.into()).await.map_err(|err| {
-        tracing::error!("insert question failed: {}", err);
-        Error::Db
-    })?;
-    if result.is_some() {
-        Err(Error::PidConflict)
-    } else {
-        Ok(())
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    config.check_submissions_open(Utc::now())?;
+    if config.contains_banned_word(&question.name) || config.contains_banned_word(&question.info) {
+        return Err(Error::Banned);
     }
+    if config.require_email && question.email.is_none() {
+        return Err(Error::EmailRequired);
+    }
+    let email = question.email.clone();
+    // cleared if the question is not inserted, so failed posts don't count
+    if let Some(email) = &email {
+        email_cooldown
+            .hit(email, Duration::from_secs(config.email_cooldown_secs))
+            .map_err(Error::Cooldown)?;
+    }
+    let mut question: Question = question.into();
+    question.ip = ip;
+
+    for attempt in 0..=config.pid_retries {
+        if attempt > 0 {
+            metrics.pid_retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(config.pid_retry_backoff(attempt)).await;
+            question.pid = new_pid();
+        }
+        match questions.try_insert(question).await {
+            Ok(()) => return Ok(()),
+            Err(q) => {
+                tracing::warn!("question pid {} conflicted, attempt {attempt}", q.pid);
+                question = q;
+            }
+        }
+    }
+
+    tracing::error!(
+        "failed to insert question after {} retries",
+        config.pid_retries
+    );
+    if let Some(email) = &email {
+        email_cooldown.clear(email);
+    }
+    Err(Error::PidConflict)
+}
+
+/// Gets a question by its pid, with all its details.
+pub async fn get_one<Io: IoHandle>(
+    State(Global {
+        questions, metrics, ..
+    }): State<Global<Io>>,
+    Path(pid): Path<Pid>,
+) -> Result<Json<Question>, Error> {
+    let select = pid.select(&questions);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy.id() == pid {
+            if let Some(question) = metrics.read(lazy.id(), lazy.get().await) {
+                return Ok(Json(question.clone()));
+            }
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Whether to include resolved questions.
+    #[serde(default)]
+    pub include_resolved: bool,
+    /// Pid to continue a truncated scan from.
+    #[serde(default)]
+    pub from: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListRes {
+    pub questions: Vec<Question>,
+    #[serde(flatten)]
+    pub scan: Scan,
+}
+
+/// Lists questions, sorted by time.
+///
+/// Resolved questions are excluded unless requested.
+pub async fn list<Io: IoHandle>(
+    State(Global {
+        questions,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(ListQuery {
+        include_resolved,
+        from,
+    }): Query<ListQuery>,
+) -> Json<ListRes> {
+    let mut ret = Vec::new();
+    let scan = scan::scan(
+        &questions,
+        config.layout.questions_items_per_chunk(),
+        from,
+        config.max_scan,
+        |id, question| {
+            if let Some(question) = metrics.read(id, question) {
+                if include_resolved || !question.resolved {
+                    ret.push(question.clone());
+                }
+            }
+        },
+    )
+    .await;
+    ret.sort_by_key(|question| question.time);
+    Json(ListRes {
+        questions: ret,
+        scan,
+    })
+}
+
+/// Searches questions submitted by the given email, sorted by time.
+pub async fn search_by_email<Io: IoHandle>(
+    State(Global {
+        questions,
+        config,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    Query(query): Query<EmailQuery>,
+) -> Json<ListRes> {
+    let mut ret = Vec::new();
+    let scan = scan::scan(
+        &questions,
+        config.layout.questions_items_per_chunk(),
+        query.from,
+        config.max_scan,
+        |id, question| {
+            if let Some(question) = metrics.read(id, question) {
+                if question
+                    .email
+                    .as_ref()
+                    .is_some_and(|email| query.matches(email))
+                {
+                    ret.push(question.clone());
+                }
+            }
+        },
+    )
+    .await;
+    ret.sort_by_key(|question| question.time);
+    Json(ListRes {
+        questions: ret,
+        scan,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveReq {
+    pub pid: Pid,
+}
+
+/// Marks a question as resolved.
+///
+/// The question is kept for auditing.
+pub async fn resolve<Io: IoHandle>(
+    State(Global {
+        questions,
+        read_only,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(ResolveReq { pid }): Json<ResolveReq>,
+) -> Result<(), Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    let select = pid.select(&questions);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.id() == pid {
+            if let Some(question) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                tracing::info!("resolving question {pid} as {actor}");
+                question.resolved = true;
+                return Ok(lazy.close().await?);
+            }
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Partial update of a question.
+///
+/// Omitted fields are left unchanged, and an explicit `null`
+/// email clears the stored email.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateReq {
+    pub pid: Pid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub email: Option<Option<lettre::Address>>,
+}
+
+/// Deserializes a present value into `Some`, so an explicit `null`
+/// is distinguished from an omitted field.
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Updates fields of a question, for example redacting its email.
+pub async fn update<Io: IoHandle>(
+    State(Global {
+        questions,
+        config,
+        read_only,
+        metrics,
+        ..
+    }): State<Global<Io>>,
+    actor: Actor,
+    Json(req): Json<UpdateReq>,
+) -> Result<(), Error> {
+    if read_only.load(Ordering::Acquire) {
+        return Err(Error::ReadOnly);
+    }
+    for (text, empty) in [(&req.name, Error::EmptyName), (&req.info, Error::EmptyInfo)] {
+        if let Some(text) = text {
+            if text.trim().is_empty() {
+                return Err(empty);
+            }
+            if config.contains_banned_word(text) {
+                return Err(Error::Banned);
+            }
+        }
+    }
+
+    let pid = req.pid;
+    let select = pid.select(&questions);
+    let mut iter = select.iter();
+    while let Some(Ok(mut lazy)) = iter.next().await {
+        if lazy.id() == pid {
+            if let Some(question) = metrics.read(lazy.id(), lazy.get_mut().await) {
+                tracing::info!("updating question {pid} as {actor}");
+                if let Some(name) = req.name {
+                    question.name = name;
+                }
+                if let Some(info) = req.info {
+                    question.info = info;
+                }
+                if let Some(email) = req.email {
+                    question.email = email;
+                }
+                return Ok(lazy.close().await?);
+            }
+        }
+    }
+
+    Err(Error::NotFound)
+}
+
+/// Removes resolved questions asked before the given time,
+/// returning the count of removed questions.
+pub async fn purge_resolved<Io: IoHandle>(
+    questions: &dmds::World<Question, 1, Io>,
+    metrics: &Metrics,
+    before: DateTime<Utc>,
+) -> Result<usize, Error> {
+    // collect first, as removing requires writing chunks under iteration
+    let expired = {
+        let select = questions.select_all();
+        let mut iter = select.iter();
+        let mut expired = Vec::new();
+        while let Some(Ok(lazy)) = iter.next().await {
+            if let Some(question) = metrics.read(lazy.id(), lazy.get().await) {
+                if question.resolved && question.time < before {
+                    expired.push(question.clone());
+                }
+            }
+        }
+        expired
+    };
+
+    for question in &expired {
+        remove(questions, question).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Removes all questions, returning the count of removed questions.
+pub async fn purge_all<Io: IoHandle>(
+    questions: &dmds::World<Question, 1, Io>,
+    metrics: &Metrics,
+) -> Result<usize, Error> {
+    let all = {
+        let select = questions.select_all();
+        let mut iter = select.iter();
+        let mut all = Vec::new();
+        while let Some(lazy) = iter.next().await {
+            let lazy = lazy?;
+            if let Some(question) = metrics.read(lazy.id(), lazy.get().await) {
+                all.push(question.clone());
+            }
+        }
+        all
+    };
+
+    for question in &all {
+        remove(questions, question).await?;
+    }
+    Ok(all.len())
+}
+
+/// Removes the given question from the world.
+async fn remove<Io: IoHandle>(
+    questions: &dmds::World<Question, 1, Io>,
+    question: &Question,
+) -> Result<(), Error> {
+    questions
+        .chunk_buf_of_data_or_load(question)
+        .await?
+        .remove(question.pid.0)
+        .await;
+    Ok(())
 }
@@ -0,0 +1,78 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::locale::Lang;
+
+/// Header carrying the id of a request.
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Context of the request being handled.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Unique id of the request.
+    pub id: Uuid,
+    /// Language preferred by the client, from the `Accept-Language` header.
+    pub lang: Lang,
+}
+
+tokio::task_local! {
+    static CONTEXT: Context;
+}
+
+impl Context {
+    /// Runs the given function with the context of the current request,
+    /// or returns `None` if not handling any request.
+    #[inline]
+    pub fn with<T>(f: impl FnOnce(&Self) -> T) -> Option<T> {
+        CONTEXT.try_with(f).ok()
+    }
+}
+
+/// Middleware scoping every request within a new [`Context`].
+///
+/// The request id is recorded in the tracing span,
+/// and echoed in the `X-Request-Id` response header.
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let id = Uuid::new_v4();
+    let lang = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or_else(Lang::default, Lang::from_accept_language);
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut res = CONTEXT
+        .scope(Context { id, lang }, next.run(req))
+        .instrument(span)
+        .await;
+    res.headers_mut().insert(
+        X_REQUEST_ID,
+        HeaderValue::from_str(&id.to_string()).expect("uuid should be a valid header value"),
+    );
+    res
+}
+
+/// Request context carried in error responses, so users can quote it.
+#[derive(Debug, Serialize)]
+pub struct ErrContext {
+    pub request_id: Option<Uuid>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ErrContext {
+    /// Captures the context of the current request.
+    #[inline]
+    pub fn current() -> Self {
+        Self {
+            request_id: Context::with(|cx| cx.id),
+            timestamp: Utc::now(),
+        }
+    }
+}
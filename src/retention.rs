@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use dmds::IoHandle;
+use tracing::{error, info};
+
+use crate::{paper, question, Global};
+
+/// Purges rejected papers and resolved questions older than the
+/// given count of days, returning counts of purged papers and questions.
+///
+/// Pending and approved papers are never touched.
+pub async fn purge<Io: IoHandle>(state: &Global<Io>, days: u64) -> (usize, usize) {
+    let before = Utc::now() - chrono::Duration::days(days as i64);
+    let papers = paper::purge_rejected(&state.papers, &state.metrics, before)
+        .await
+        .unwrap_or_else(|err| {
+            error!("failed to purge rejected papers: {err}");
+            0
+        });
+    let questions = question::purge_resolved(&state.questions, &state.metrics, before)
+        .await
+        .unwrap_or_else(|err| {
+            error!("failed to purge resolved questions: {err}");
+            0
+        });
+    (papers, questions)
+}
+
+/// Runs retention purges periodically.
+pub async fn daemon<Io: IoHandle>(state: Global<Io>, days: u64, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let (papers, questions) = purge(&state, days).await;
+        info!("retention purged {papers} rejected papers and {questions} resolved questions");
+    }
+}
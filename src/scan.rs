@@ -0,0 +1,136 @@
+use std::ops::RangeInclusive;
+
+use dmds::{IoHandle, StreamExt, World};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::pid::Pid;
+
+/// Outcome of a bounded scan.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Scan {
+    /// Whether the scan stopped before reaching the end,
+    /// because [`crate::Config::max_scan`] was exceeded.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Pid to continue a truncated scan from, as the `from` query parameter.
+    #[serde(default)]
+    pub next: Option<u64>,
+}
+
+/// Scans records of the given world in ascending pid chunks from the
+/// given pid, calling `f` with the id and value of every record read.
+///
+/// Chunks of pid dimension are always scanned as a whole, and no more chunks
+/// are scanned after `max_scan` records are read, so the scan can be
+/// continued from the start of the next chunk.
+///
+/// Other dimensions are not selected, as dmds fails to intersect
+/// selections of single chunks, so callers should filter records instead.
+pub async fn scan<T, const DIMS: usize, Io, F>(
+    world: &World<T, DIMS, Io>,
+    items_per_chunk: u64,
+    from: u64,
+    max_scan: Option<usize>,
+    mut f: F,
+) -> Scan
+where
+    T: dmds::Data,
+    Io: IoHandle,
+    F: FnMut(u64, Result<&T, dmds::Error>),
+{
+    scan_while(
+        world,
+        items_per_chunk,
+        from..=u64::MAX,
+        max_scan,
+        |id, val| {
+            f(id, val);
+            true
+        },
+    )
+    .await
+}
+
+/// Scans records the same as [`scan`], but only chunks overlapping the
+/// given range, and stops after the current chunk once `f` returns `false`,
+/// which is not reported as truncated.
+///
+/// Records out of the range in the scanned chunks are still passed to `f`.
+pub async fn scan_while<T, const DIMS: usize, Io, F>(
+    world: &World<T, DIMS, Io>,
+    items_per_chunk: u64,
+    range: RangeInclusive<u64>,
+    max_scan: Option<usize>,
+    mut f: F,
+) -> Scan
+where
+    T: dmds::Data,
+    Io: IoHandle,
+    F: FnMut(u64, Result<&T, dmds::Error>) -> bool,
+{
+    let (from, until) = range.into_inner();
+    let mut start = from - from % items_per_chunk;
+    let mut read = 0;
+    let mut more = true;
+    loop {
+        let end = start.saturating_add(items_per_chunk - 1);
+        let select = world.select(0, start..=end);
+        let mut iter = select.iter();
+        while let Some(lazy) = iter.next().await {
+            let Ok(lazy) = lazy else {
+                // stops at the first error, as other scans do
+                return Scan::default();
+            };
+            read += 1;
+            more &= f(lazy.id(), lazy.get().await);
+        }
+
+        let Some(next) = end.checked_add(1).filter(|&next| next <= until) else {
+            return Scan::default();
+        };
+        if !more {
+            return Scan::default();
+        }
+        if max_scan.is_some_and(|max| read >= max) {
+            return Scan {
+                truncated: true,
+                next: Some(next),
+            };
+        }
+        start = next;
+    }
+}
+
+/// Opaque cursor of pagination in ascending pids, which is
+/// the last pid of the previous page.
+///
+/// This is serialized as a hexadecimal string, and an empty
+/// string starts from the first page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cursor(pub Pid);
+
+impl Cursor {
+    /// Pid to scan from, or `None` if the previous page ended at the last pid.
+    #[inline]
+    pub fn from(self) -> Option<u64> {
+        self.0 .0.checked_add(1)
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:x}", self.0 .0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+        u64::from_str_radix(&s, 16)
+            .map(|pid| Self(Pid(pid)))
+            .map_err(serde::de::Error::custom)
+    }
+}
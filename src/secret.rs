@@ -0,0 +1,344 @@
+use std::{convert::Infallible, fmt, sync::Arc};
+
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use dmds::IoHandle;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tower::ServiceExt as _;
+use tracing::warn;
+
+use crate::{
+    compact::FlushChunk, json::Json, locale::Localize, request::ErrContext, Config, Global,
+};
+
+/// Minimum length of generated secrets.
+const GENERATED_LEN: usize = 32;
+
+/// Application serving requests, rebuilt when management secrets
+/// are rotated, as they are segments of management routes.
+#[derive(Debug)]
+pub struct LiveApp {
+    router: ArcSwap<Router>,
+    /// Latest configuration, which also serializes rotations.
+    config: Mutex<Arc<Config>>,
+}
+
+impl LiveApp {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            router: ArcSwap::from_pointee(Router::new()),
+            config: Mutex::new(config),
+        }
+    }
+
+    /// Replaces the application serving requests.
+    #[inline]
+    pub fn install(&self, router: Router) {
+        self.router.store(Arc::new(router));
+    }
+}
+
+/// Installs the application of the given state, returning a router
+/// which serves requests by the live application.
+pub fn serve<Io: FlushChunk + 'static>(state: Global<Io>) -> Router {
+    state.live.install(crate::app(state.clone()));
+    let live = state.live.clone();
+    Router::new().fallback(move |req: Request| {
+        let router = Router::clone(&live.router.load());
+        async move {
+            let Ok::<_, Infallible>(res) = router.oneshot(req).await;
+            res
+        }
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unknown management secret")]
+    Unknown,
+    #[error("unrecognized management secret")]
+    Unrecognized,
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::Unknown => "未知的管理密钥".to_owned(),
+            Error::Unrecognized => "无法识别的管理密钥".to_owned(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        (
+            match self {
+                Error::Unknown => StatusCode::BAD_REQUEST,
+                Error::Unrecognized => StatusCode::UNAUTHORIZED,
+            },
+            Json(JErr {
+                error: self.localized(),
+                context: ErrContext::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateReq {
+    /// Name of the secret, as in the configuration.
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateRes {
+    pub name: String,
+    /// The new secret, which is responded only once.
+    pub secret: String,
+}
+
+/// Replaces the given management secret with a random one,
+/// taking effect for subsequent requests immediately.
+///
+/// The new secret is kept in memory only, so the configuration
+/// file should be updated before restarting.
+pub async fn rotate<Io: FlushChunk + 'static>(
+    State(state): State<Global<Io>>,
+    Json(RotateReq { name }): Json<RotateReq>,
+) -> Result<impl IntoResponse, Error> {
+    let mut latest = state.live.config.lock().await;
+    let mut config = Config::clone(&latest);
+    let len = config.min_secret_len.max(GENERATED_LEN);
+    let secret = config.secret_mut(&name).ok_or(Error::Unknown)?;
+    *secret = std::iter::repeat_with(fastrand::alphanumeric)
+        .take(len)
+        .collect();
+    let secret = secret.clone();
+
+    let config = Arc::new(config);
+    state.live.install(crate::app(Global {
+        config: config.clone(),
+        ..state.clone()
+    }));
+    *latest = config;
+    warn!("rotated management secret {name}");
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(RotateRes { name, secret }),
+    ))
+}
+
+/// Compares the given secrets in time independent of their contents.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let diff = (0..a.len().max(b.len())).fold(a.len() ^ b.len(), |diff, i| {
+        diff | (a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0)) as usize
+    });
+    diff == 0
+}
+
+/// Capability acting on a management request, by the management
+/// secret it's authorized by.
+///
+/// This is extracted from the path of management routes, whose
+/// segments are the secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    /// Holder of the management secret.
+    Root,
+    PaperGetter,
+    PaperApprover,
+    PaperRejecter,
+    QuestionGetter,
+    QuestionResolver,
+}
+
+impl Actor {
+    /// Actor of the management secret of the given field name.
+    fn of_secret(name: &str) -> Option<Self> {
+        match name {
+            "mng_secret" => Some(Self::Root),
+            "mng_get_papers_secret" => Some(Self::PaperGetter),
+            "mng_approve_papers_secret" => Some(Self::PaperApprover),
+            "mng_reject_papers_secret" => Some(Self::PaperRejecter),
+            "mng_get_questions_secret" => Some(Self::QuestionGetter),
+            "mng_resolve_questions_secret" => Some(Self::QuestionResolver),
+            _ => None,
+        }
+    }
+
+    /// Actor of the given label, as of [`Self::as_str`].
+    fn of_label(label: &str) -> Option<Self> {
+        [
+            Self::Root,
+            Self::PaperGetter,
+            Self::PaperApprover,
+            Self::PaperRejecter,
+            Self::QuestionGetter,
+            Self::QuestionResolver,
+        ]
+        .into_iter()
+        .find(|actor| actor.as_str() == label)
+    }
+
+    /// Label of this actor, which is `root` for the management secret,
+    /// or the name of a sub-secret like `get_papers`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Root => "root",
+            Self::PaperGetter => "get_papers",
+            Self::PaperApprover => "approve_papers",
+            Self::PaperRejecter => "reject_papers",
+            Self::QuestionGetter => "get_questions",
+            Self::QuestionResolver => "resolve_questions",
+        }
+    }
+}
+
+impl fmt::Display for Actor {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<Io: IoHandle> FromRequestParts<Global<Io>> for Actor {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        Global { config, .. }: &Global<Io>,
+    ) -> Result<Self, Self::Rejection> {
+        // authorized by a bearer token, which may be the management secret
+        if let Some(actor) = parts.extensions.get::<Self>() {
+            return Ok(*actor);
+        }
+        let mut segments = parts.uri.path().trim_start_matches('/').split('/');
+        if segments.next() != Some(config.mng_secret.as_str()) {
+            return Err(Error::Unrecognized);
+        }
+        let sub = segments.next().unwrap_or_default();
+        Ok(config
+            .secrets()
+            .into_iter()
+            .skip(1)
+            .find(|(_, secret)| *secret == sub)
+            .and_then(|(name, _)| Self::of_secret(name))
+            .unwrap_or(Self::Root))
+    }
+}
+
+/// Bearer token of the given headers, if any.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Middleware serving management routes at `/admin/{capability}/...`,
+/// authorized by a bearer token instead of secrets in the path.
+///
+/// Capabilities are labels as of [`Actor::as_str`], and the token is
+/// either the secret of the capability or the management secret,
+/// which implies all capabilities. Requests are rewritten to the paths
+/// of the secrets, so they are routed the same as before.
+pub async fn bearer<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some((actor, rest)) = req
+        .uri()
+        .path()
+        .strip_prefix("/admin/")
+        .map(|path| path.split_once('/').unwrap_or((path, "")))
+        .and_then(|(label, rest)| Some((Actor::of_label(label)?, rest.to_owned())))
+    else {
+        // other admin routes like `whoami` authorize themselves
+        return next.run(req).await;
+    };
+    let secret = config
+        .secrets()
+        .into_iter()
+        .find(|(name, _)| Actor::of_secret(name) == Some(actor))
+        .map(|(_, secret)| secret)
+        .expect("all actors should have secrets");
+    let Some(token) = bearer_token(req.headers()) else {
+        return Error::Unrecognized.into_response();
+    };
+    // both are compared so the time taken doesn't tell which one matched
+    let root = constant_time_eq(token, &config.mng_secret);
+    if !(constant_time_eq(token, secret) | root) {
+        return Error::Unrecognized.into_response();
+    }
+
+    let mut path = format!("/{}", config.mng_secret);
+    if actor != Actor::Root {
+        path.push('/');
+        path.push_str(secret);
+    }
+    if !rest.is_empty() {
+        path.push('/');
+        path.push_str(&rest);
+    }
+    if let Some(query) = req.uri().query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    let Ok(uri) = path.parse() else {
+        return Error::Unrecognized.into_response();
+    };
+    *req.uri_mut() = uri;
+    if root {
+        req.extensions_mut().insert(Actor::Root);
+    }
+    next.run(req).await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WhoamiRes {
+    /// Capability of the secret, which is `root` for the management
+    /// secret, or the name of a sub-secret like `get_papers`.
+    pub capability: String,
+}
+
+/// Identifies the management secret given as a bearer token,
+/// without performing any action.
+///
+/// All secrets are compared so the time taken doesn't tell which
+/// one matched, and the secret is never responded.
+pub async fn whoami<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+    headers: HeaderMap,
+) -> Result<Json<WhoamiRes>, Error> {
+    let token = bearer_token(&headers).ok_or(Error::Unrecognized)?;
+    let matched = config
+        .secrets()
+        .into_iter()
+        .fold(None, |matched, (name, secret)| {
+            let eq = constant_time_eq(token, secret);
+            matched.or(eq.then_some(name))
+        })
+        .ok_or(Error::Unrecognized)?;
+    let actor = Actor::of_secret(matched).expect("all secrets should have actors");
+    Ok(Json(WhoamiRes {
+        capability: actor.as_str().to_owned(),
+    }))
+}
@@ -0,0 +1,157 @@
+use std::{convert::Infallible, future::Future, pin::pin, sync::Arc, time::Duration};
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn, Request};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{watch, Semaphore},
+    task::JoinSet,
+};
+use tower::ServiceExt as _;
+use tracing::{debug, error, info, warn};
+
+use crate::Config;
+
+/// Serves the given router on the given listener, tuning connections
+/// by the configuration, until the given shutdown future completes.
+///
+/// This works the same as [`axum::serve`] with connect info of
+/// [`std::net::SocketAddr`]. On shutdown, new connections are refused
+/// and open connections are closed once their in-flight requests are
+/// done, and connections still open, such as streams, after
+/// [`Config::shutdown_drain_secs`] are closed forcibly.
+///
+/// Returns the count of connections closed forcibly.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> usize {
+    let limit = config
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max)));
+    let http2 = config.http2;
+    let keepalive = Duration::from_secs(config.tcp_keepalive_secs);
+    // connections are told to shut down once the sender is dropped
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut conns = JoinSet::new();
+    let mut shutdown = pin!(shutdown);
+
+    loop {
+        // reaps closed connections so they are not tracked forever
+        while conns.try_join_next().is_some() {}
+
+        // waits for a slot before accepting, leaving connections in the backlog
+        let permit = async {
+            match &limit {
+                Some(limit) => Some(
+                    limit
+                        .clone()
+                        .acquire_owned()
+                        .await
+                        .expect("connection limit should never be closed"),
+                ),
+                None => None,
+            }
+        };
+        let accept = async { (permit.await, listener.accept().await) };
+        let (permit, accepted) = tokio::select! {
+            accepted = accept => accepted,
+            () = &mut shutdown => break,
+        };
+        let (stream, remote) = match accepted {
+            Ok(conn) => conn,
+            Err(err) => {
+                // mostly running out of file descriptors, which needs a break
+                error!("failed to accept connection: {err}");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        if let Err(err) = tune(&stream, keepalive) {
+            debug!("failed to tune connection from {remote}: {err}");
+        }
+
+        let router = router.clone();
+        let service = service_fn(move |mut req: Request<Incoming>| {
+            req.extensions_mut().insert(ConnectInfo(remote));
+            let res = router.clone().oneshot(req);
+            async move {
+                let Ok::<_, Infallible>(res) = res.await;
+                Ok::<_, Infallible>(res)
+            }
+        });
+        let mut shutdown = shutdown_rx.clone();
+        conns.spawn(async move {
+            let io = TokioIo::new(stream);
+            // HTTP/1 responses in flight are sent with `Connection: close`,
+            // and HTTP/2 connections are sent GOAWAY
+            let result = if http2 {
+                let builder = auto::Builder::new(TokioExecutor::new());
+                let mut conn = pin!(builder.serve_connection_with_upgrades(io, service));
+                tokio::select! {
+                    result = conn.as_mut() => result,
+                    _ = shutdown.changed() => {
+                        conn.as_mut().graceful_shutdown();
+                        conn.await
+                    }
+                }
+            } else {
+                let mut conn = pin!(http1::Builder::new()
+                    .serve_connection(io, service)
+                    .with_upgrades());
+                tokio::select! {
+                    result = conn.as_mut() => result,
+                    _ = shutdown.changed() => {
+                        conn.as_mut().graceful_shutdown();
+                        conn.await
+                    }
+                }
+                .map_err(Into::into)
+            };
+            if let Err(err) = result {
+                debug!("connection from {remote} closed with error: {err}");
+            }
+            drop(permit);
+        });
+    }
+
+    drop(listener);
+    drop(shutdown_tx);
+    let drain = Duration::from_secs(config.shutdown_drain_secs);
+    info!(
+        "shutting down, draining {} connections for at most {drain:?}",
+        conns.len()
+    );
+    let drained =
+        tokio::time::timeout(drain, async { while conns.join_next().await.is_some() {} }).await;
+    if drained.is_ok() {
+        info!("all connections drained");
+        return 0;
+    }
+    let forced = conns.len();
+    conns.abort_all();
+    while conns.join_next().await.is_some() {}
+    warn!("forcibly closed {forced} connections after draining for {drain:?}");
+    forced
+}
+
+/// Disables Nagle's algorithm for the given connection, and sets
+/// TCP keep-alive probes after the given idle time, if not zero.
+fn tune(stream: &TcpStream, keepalive: Duration) -> std::io::Result<()> {
+    stream.set_nodelay(true)?;
+    if !keepalive.is_zero() {
+        SockRef::from(stream).set_tcp_keepalive(
+            &TcpKeepalive::new()
+                .with_time(keepalive)
+                .with_interval(keepalive),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,223 @@
+use std::fmt::Write as _;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use dmds::IoHandle;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{json::Json, locale::Localize, paper, pid::Pid, request::ErrContext, Global};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Prefix of messages signed for edit tokens, so permalink signatures
+/// are never valid edit tokens of the same key, and vice versa.
+const EDIT_PREFIX: &[u8] = b"edit:";
+
+/// Signs the given pid with an optional expiry in unix seconds,
+/// returning the signature in lowercase hex.
+fn sign(key: &str, prefix: &[u8], pid: Pid, exp: Option<i64>) -> String {
+    let mac = mac(key, prefix, pid, exp).finalize().into_bytes();
+    mac.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn mac(key: &str, prefix: &[u8], pid: Pid, exp: Option<i64>) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(key.as_bytes()).expect("hmac should accept keys of any size");
+    mac.update(prefix);
+    mac.update(pid.to_string().as_bytes());
+    mac.update(b":");
+    if let Some(exp) = exp {
+        mac.update(exp.to_string().as_bytes());
+    }
+    mac
+}
+
+/// Verifies the given hex signature in constant time.
+fn verify(key: &str, prefix: &[u8], pid: Pid, exp: Option<i64>, sig: &str) -> bool {
+    if !sig.len().is_multiple_of(2) || !sig.is_ascii() {
+        return false;
+    }
+    let Ok(sig) = (0..sig.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&sig[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return false;
+    };
+    mac(key, prefix, pid, exp).verify_slice(&sig).is_ok()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("signature expired")]
+    Expired,
+    #[error("ttl of the signature is out of range")]
+    InvalidTtl,
+    #[error(transparent)]
+    Paper(#[from] paper::Error),
+}
+
+impl Localize for Error {
+    fn zh(&self) -> String {
+        match self {
+            Error::InvalidSignature => "签名无效".to_owned(),
+            Error::Expired => "签名已过期".to_owned(),
+            Error::InvalidTtl => "签名有效期超出范围".to_owned(),
+            Error::Paper(err) => err.zh(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct JErr {
+            error: String,
+            #[serde(flatten)]
+            context: ErrContext,
+        }
+
+        let status = match self {
+            Error::Paper(err) => return err.into_response(),
+            Error::InvalidSignature | Error::Expired => StatusCode::FORBIDDEN,
+            Error::InvalidTtl => StatusCode::BAD_REQUEST,
+        };
+        (
+            status,
+            Json(JErr {
+                error: self.localized(),
+                context: ErrContext::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedQuery {
+    /// Expiry of the signature in unix seconds, if any.
+    #[serde(default)]
+    pub exp: Option<i64>,
+    pub sig: String,
+}
+
+/// Gets an approved paper by its pid, if the signature validates.
+///
+/// This works the same as [`paper::get_one`] after verification.
+pub async fn get_signed<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    Path(pid): Path<Pid>,
+    Query(SignedQuery { exp, sig }): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let key = state
+        .config
+        .permalink_key
+        .as_deref()
+        .ok_or(Error::InvalidSignature)?;
+    if !verify(key, b"", pid, exp, &sig) {
+        return Err(Error::InvalidSignature);
+    }
+    if exp.is_some_and(|exp| exp <= Utc::now().timestamp()) {
+        return Err(Error::Expired);
+    }
+    Ok(paper::get_one(State(state), Path(pid), headers).await?)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignReq {
+    pub pid: Pid,
+    /// Seconds before the signature expires, or never if absent.
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRes {
+    /// Path of the signed permalink, relative to the backend.
+    pub url: String,
+    pub expires: Option<DateTime<Utc>>,
+}
+
+/// Mints a signed permalink of the given paper.
+///
+/// The paper is not required to be approved yet,
+/// but the permalink works only after approval.
+pub async fn mint<Io: IoHandle>(
+    State(Global { config, .. }): State<Global<Io>>,
+    Json(SignReq { pid, ttl_secs }): Json<SignReq>,
+) -> Result<Json<SignRes>, Error> {
+    let key = config
+        .permalink_key
+        .as_deref()
+        .expect("permalinks should be routed only if a key is configured");
+    let expires = ttl_secs
+        .map(|secs| {
+            i64::try_from(secs)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+                .ok_or(Error::InvalidTtl)
+        })
+        .transpose()?;
+    let exp = expires.map(|time| time.timestamp());
+    let sig = sign(key, b"", pid, exp);
+    Ok(Json(SignRes {
+        url: match exp {
+            Some(exp) => format!("/paper/signed/{pid}?exp={exp}&sig={sig}"),
+            None => format!("/paper/signed/{pid}?sig={sig}"),
+        },
+        expires,
+    }))
+}
+
+/// Mints a token letting the author of the given paper edit it
+/// until the given time, as the expiry in unix seconds and the
+/// signature joined by a dot.
+pub fn edit_token(key: &str, pid: Pid, expires: DateTime<Utc>) -> String {
+    let exp = expires.timestamp();
+    format!("{exp}.{}", sign(key, EDIT_PREFIX, pid, Some(exp)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditReq {
+    pub pid: Pid,
+    /// Edit token returned on posting the paper.
+    pub token: String,
+    pub info: String,
+}
+
+/// Edits the info of a pending paper by its author, if the edit token
+/// validates and is not expired.
+///
+/// This works the same as [`paper::edit_pending`] after verification.
+pub async fn edit<Io: IoHandle>(
+    State(state): State<Global<Io>>,
+    Json(EditReq { pid, token, info }): Json<EditReq>,
+) -> Result<(), Error> {
+    let key = state
+        .config
+        .edit_key
+        .as_deref()
+        .expect("edits should be routed only if a key is configured");
+    let (exp, sig) = token.split_once('.').ok_or(Error::InvalidSignature)?;
+    let exp = exp.parse().map_err(|_| Error::InvalidSignature)?;
+    if !verify(key, EDIT_PREFIX, pid, Some(exp), sig) {
+        return Err(Error::InvalidSignature);
+    }
+    if exp <= Utc::now().timestamp() {
+        return Err(Error::Expired);
+    }
+    Ok(paper::edit_pending(&state, pid, info).await?)
+}
@@ -9,46 +9,318 @@ use dmds::{mem_io_handle::MemStorage, StreamExt};
 use http_body_util::BodyExt;
 use tower::ServiceExt;
 
-use crate::{paper, question, Config, Global};
+use crate::{
+    alert, audit,
+    compact::{self, FlushChunk},
+    layout::{self, Layout},
+    locale::Lang,
+    mail, mng, paper,
+    pid::{Pid, PidFormat},
+    question, Config, Global,
+};
+
+impl FlushChunk for MemStorage {
+    async fn flush_chunk<T: dmds::Data, const DIMS: usize>(
+        &self,
+        chunk: &dmds::Chunk<T, DIMS>,
+    ) -> std::io::Result<()> {
+        let mut buf = bytes::BytesMut::new();
+        chunk.write_buf(&mut buf).await?;
+        let mut writer = self.write_chunk(T::VERSION, *chunk.pos()).await?;
+        futures_lite::AsyncWriteExt::write_all(&mut writer, &buf).await
+    }
+}
+
+impl<T: FlushChunk + 'static> FlushChunk for Arc<T> {
+    #[inline]
+    async fn flush_chunk<D: dmds::Data, const DIMS: usize>(
+        &self,
+        chunk: &dmds::Chunk<D, DIMS>,
+    ) -> std::io::Result<()> {
+        (**self).flush_chunk(chunk).await
+    }
+}
+
+/// Storage wrapping another, failing reads and writes injected
+/// by their order or by the chunks they hit.
+#[derive(Debug, Default)]
+struct FaultyHandle<Io> {
+    inner: Io,
+    reads: Faults,
+    writes: Faults,
+}
+
+/// Faults injected to reads or writes of a [`FaultyHandle`].
+#[derive(Debug, Default)]
+struct Faults {
+    /// Count of operations so far.
+    count: std::sync::atomic::AtomicUsize,
+    /// Indexes of operations to fail.
+    nth: std::sync::Mutex<std::collections::BTreeSet<usize>>,
+    /// Chunks to fail operations of, for the given times.
+    chunks: std::sync::Mutex<std::collections::HashMap<Vec<usize>, usize>>,
+}
+
+impl Faults {
+    /// Fails the `n`th operation from now on, counting from one.
+    fn fail_nth(&self, n: usize) {
+        assert!(n > 0, "operations are counted from one");
+        let count = self.count.load(std::sync::atomic::Ordering::Acquire);
+        self.nth.lock().unwrap().insert(count + n - 1);
+    }
+
+    /// Fails the next operations of the given chunk for the given times.
+    fn fail_chunk<const DIMS: usize>(&self, pos: [usize; DIMS], times: usize) {
+        self.chunks.lock().unwrap().insert(pos.to_vec(), times);
+    }
+
+    /// Count of operations so far.
+    fn count(&self) -> usize {
+        self.count.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    fn hit(&self, pos: &[usize]) -> std::io::Result<()> {
+        let n = self.count.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+        let nth = self.nth.lock().unwrap().remove(&n);
+        let chunk = match self.chunks.lock().unwrap().get_mut(pos) {
+            Some(times) if *times > 0 => {
+                *times -= 1;
+                true
+            }
+            _ => false,
+        };
+        if nth || chunk {
+            Err(std::io::Error::other("injected failure"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<Io: dmds::IoHandle> dmds::IoHandle for FaultyHandle<Io> {
+    type Read<'a>
+        = Io::Read<'a>
+    where
+        Self: 'a;
+
+    #[inline]
+    fn hint_is_valid(&self, pos: &[usize]) -> bool {
+        self.inner.hint_is_valid(pos)
+    }
+
+    async fn read_chunk<const DIMS: usize>(
+        &self,
+        pos: [usize; DIMS],
+    ) -> std::io::Result<(u32, Self::Read<'_>)> {
+        self.reads.hit(&pos)?;
+        self.inner.read_chunk(pos).await
+    }
+}
+
+impl<Io: FlushChunk> FlushChunk for FaultyHandle<Io> {
+    async fn flush_chunk<T: dmds::Data, const DIMS: usize>(
+        &self,
+        chunk: &dmds::Chunk<T, DIMS>,
+    ) -> std::io::Result<()> {
+        self.writes.hit(chunk.pos())?;
+        self.inner.flush_chunk(chunk).await
+    }
+}
 
 fn router() -> (Global<MemStorage>, Router) {
-    use axum::routing::{get, post};
+    router_with(|_| {})
+}
 
-    let config = Config {
+/// Creates the test configuration.
+fn config() -> Config {
+    Config {
         db_path: PathBuf::new(),
         address: "".to_owned(),
-        static_path: PathBuf::new(),
+        http2: true,
+        max_connections: None,
+        tcp_keepalive_secs: 60,
+        shutdown_drain_secs: 30,
+        static_path: None,
+        static_cache_secs: 0,
+        trusted_proxies: vec![],
         mng_secret: "secret".to_owned(),
         mng_get_papers_secret: "get_papers".to_owned(),
         mng_approve_papers_secret: "approve_papers".to_owned(),
         mng_reject_papers_secret: "reject_papers".to_owned(),
+        mng_get_questions_secret: "get_questions".to_owned(),
+        mng_resolve_questions_secret: "resolve_questions".to_owned(),
+        approve_all_confirm: None,
+        reject_range_confirm: None,
+        purge_all_confirm: None,
+        permalink_key: None,
+        encryption_key: None,
+        edit_key: None,
+        edit_grace_secs: 300,
         log_path: None,
         log_level: None,
-    };
+        log_format: Default::default(),
+        max_name_len: Config::default_max_name_len(),
+        max_info_len: Config::default_max_info_len(),
+        list_preview_len: Config::default_list_preview_len(),
+        min_info_len: Config::default_min_info_len(),
+        max_batch_len: Config::default_max_batch_len(),
+        max_links: Config::default_max_links(),
+        allowed_image_hosts: vec![],
+        pid_retries: Config::default_pid_retries(),
+        pid_retry_backoff_ms: 1,
+        read_only: false,
+        min_secret_len: Config::default_min_secret_len(),
+        board_title: None,
+        instructions: None,
+        display_timezone: None,
+        pid_format: Default::default(),
+        count_views: false,
+        views_flush_interval_secs: Config::default_views_flush_interval_secs(),
+        strict_exclude: false,
+        weighted_half_life_secs: 604_800,
+        // tests insert approved papers directly, bypassing invalidation
+        paper_get_cache_secs: 0,
+        max_recent: 50,
+        max_scan: None,
+        submissions_open_from: None,
+        submissions_open_until: None,
+        default_paper: None,
+        banned_words: vec![],
+        require_email: false,
+        request_timeout_ms: 30_000,
+        route_timeouts: Default::default(),
+        email_cooldown_secs: 0,
+        resend_email_cooldown_secs: 600,
+        author_approve_interval_secs: 0,
+        nonce_ttl_secs: None,
+        nonce_capacity: 10_000,
+        dedup_window_secs: 86_400,
+        max_submitted_age_secs: 604_800,
+        retention_days: None,
+        retention_interval_secs: Config::default_retention_interval_secs(),
+        smtp: None,
+        approval_template_path: None,
+        rejection_template_path: None,
+        admin_alert_email: None,
+        alert_pending_threshold: 100,
+        alert_cooldown_secs: 21_600,
+        alert_interval_secs: 300,
+        layout: Default::default(),
+    }
+}
 
-    let state = Global {
-        config: Arc::new(config),
-        papers: Arc::new(dmds::world! {
-            // 32 chunks, 2 chunk
-            MemStorage::new(), 576460752303423488u64 | ..=u64::MAX, 1 | ..=1
-        }),
-        questions: Arc::new(dmds::world! {
-            // 32 chunks
-            MemStorage::new(), 1152921504606846976u64 | ..=u64::MAX
-        }),
-    };
+/// Creates a router with the test configuration modified by the given function.
+fn router_with(f: impl FnOnce(&mut Config)) -> (Global<MemStorage>, Router) {
+    let mut config = config();
+    f(&mut config);
 
-    (
-        state.clone(),
-        Router::new()
-            .route("/questions/new", post(question::new::<MemStorage>))
-            .route("/paper/post", post(paper::post::<MemStorage>))
-            .route("/paper/get", get(paper::get::<MemStorage>))
-            .route("/secret/get_papers", get(paper::unprocessed::<MemStorage>))
-            .route("/secret/approve_papers", post(paper::approve::<MemStorage>))
-            .route("/secret/reject_papers", post(paper::reject::<MemStorage>))
-            .with_state(state),
-    )
+    let layout = config.layout;
+    let state = Global::new(
+        Arc::new(config),
+        dmds::world! {
+            MemStorage::new(),
+            layout.papers_items_per_chunk() => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        },
+        dmds::world! {
+            MemStorage::new(),
+            layout.questions_items_per_chunk() => ..=u64::MAX,
+        },
+        dmds::world! {
+            MemStorage::new(),
+            crate::audit::ITEMS_PER_CHUNK => ..=u64::MAX,
+        },
+    );
+
+    let router = crate::secret::serve(state.clone());
+    (state, router)
+}
+
+/// Mailer capturing sent messages.
+#[derive(Debug, Default)]
+struct StubMailer {
+    sent: std::sync::Mutex<Vec<lettre::Message>>,
+}
+
+impl mail::Mailer for StubMailer {
+    fn send(
+        &self,
+        msg: lettre::Message,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), mail::Error>> + Send + '_>>
+    {
+        self.sent.lock().unwrap().push(msg);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+/// Seed of datasets generated by [`seed_papers`].
+const SEED: u64 = 0x5eed;
+
+/// Inserts `n` papers of random content, time and pid, with the given
+/// fraction of them approved within a day after posted and the rest
+/// pending, returning their pids.
+///
+/// The dataset is generated by a seeded RNG, so it's the same every run.
+async fn seed_papers(state: &Global<MemStorage>, n: usize, approved_ratio: f64) -> Vec<Pid> {
+    const NAMES: [&str; 4] = ["Yjn024", "JieningYu", "Subit", "Anonymous"];
+    let mut rng = fastrand::Rng::with_seed(SEED);
+    let approved = (n as f64 * approved_ratio).round() as usize;
+    let mut statuses: Vec<_> = (0..n)
+        .map(|i| {
+            if i < approved {
+                paper::Status::Approved
+            } else {
+                paper::Status::Pending
+            }
+        })
+        .collect();
+    rng.shuffle(&mut statuses);
+
+    let base = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let mut pids = Vec::with_capacity(n);
+    for status in statuses {
+        let len = rng.usize(1..=200);
+        let time = base + chrono::Duration::seconds(rng.i64(0..30 * 86_400));
+        let approved_at = time + chrono::Duration::seconds(rng.i64(0..86_400));
+        let paper = paper::Paper {
+            name: NAMES[rng.usize(..NAMES.len())].to_owned(),
+            info: std::iter::repeat_with(|| rng.alphanumeric())
+                .take(len)
+                .collect(),
+            email: None,
+            pid: Pid(rng.u64(1..)),
+            time,
+            status,
+            color: "#ffc".to_owned(),
+            ip: None,
+            rev: 0,
+            views: 0,
+            priority: false,
+            image_url: None,
+            approved_at: (status == paper::Status::Approved).then_some(approved_at),
+            received_at: time,
+        };
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    pids
+}
+
+/// Asserts the given response is in the shared shape of rate-limited
+/// responses, returning the seconds to wait.
+async fn assert_rate_limited(res: axum::response::Response) -> u64 {
+    assert_eq!(res.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    let header: u64 = res.headers()[http::header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["retry_after_secs"], header);
+    assert!(!body["message"].as_str().unwrap().is_empty());
+    header
 }
 
 #[tokio::test]
@@ -100,6 +372,8 @@ async fn post_paper() {
         info: "Hello, world!".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
 
     assert!(route
@@ -116,7 +390,7 @@ async fn post_paper() {
         .status()
         .is_success());
 
-    let select = state.papers.select(1, paper::Status::Pending as u8 as u64);
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
     let mut iter = select.iter();
 
     while let Some(Ok(lazy)) = iter.next().await {
@@ -147,8 +421,14 @@ async fn get_paper() {
         info: "Hello, world!".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
-    state.papers.insert(paper.into()).await.unwrap();
+    state
+        .papers
+        .insert(paper.into_paper(&config()).unwrap())
+        .await
+        .unwrap();
 
     assert!(!route
         .clone()
@@ -169,8 +449,10 @@ async fn get_paper() {
         info: "Genshine Impact".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
-    let mut paper: paper::Paper = paper.into();
+    let mut paper = paper.into_paper(&config()).unwrap();
     paper.status = paper::Status::Approved;
     state.papers.insert(paper).await.unwrap();
 
@@ -185,12 +467,86 @@ async fn get_paper() {
         .await
         .unwrap();
     assert!(res.status().is_success());
-    let paper::Out {
-        name, info, email, ..
-    } = serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let paper::Out { name, info, .. } =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
     assert_eq!(name, "Yjn024");
     assert_eq!(info, "Genshine Impact");
-    assert!(email.is_none());
+}
+
+#[tokio::test]
+async fn public_papers_without_emails() {
+    use futures_lite::StreamExt as _;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (state, route) = router();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(std::future::IntoFuture::into_future(axum::serve(
+        listener,
+        route.clone(),
+    )));
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws/approved"))
+        .await
+        .unwrap();
+
+    let pending: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = pending.pid;
+    state.papers.insert(pending).await.unwrap();
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let get = |uri: String| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            serde_json::from_slice::<serde_json::Value>(
+                &res.into_body().collect().await.unwrap().to_bytes(),
+            )
+            .unwrap()
+        }
+    };
+    let res = get("/paper/get".to_owned()).await;
+    assert_eq!(res["pid"], pid.0);
+    assert!(res.get("email").is_none());
+    let res = get(format!("/paper/get/{pid}")).await;
+    assert!(res.get("email").is_none());
+    let res = get("/paper/recent".to_owned()).await;
+    assert_eq!(res.as_array().unwrap().len(), 1);
+    assert!(res[0].get("email").is_none());
+
+    let res = loop {
+        match ws.next().await.unwrap().unwrap() {
+            Message::Text(text) => break serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            Message::Close(frame) => panic!("closed: {frame:?}"),
+            _ => {}
+        }
+    };
+    assert_eq!(res["pid"], pid.0);
+    assert!(res.get("email").is_none());
 }
 
 #[tokio::test]
@@ -201,8 +557,10 @@ async fn unprocessed_papers() {
         info: "Genshine Impact".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
-    let mut paper: paper::Paper = paper.into();
+    let mut paper = paper.into_paper(&config()).unwrap();
     paper.status = paper::Status::Approved;
     state.papers.insert(paper).await.unwrap();
 
@@ -211,16 +569,28 @@ async fn unprocessed_papers() {
         info: "Hello, world!".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
-    state.papers.insert(paper.into()).await.unwrap();
+    state
+        .papers
+        .insert(paper.into_paper(&config()).unwrap())
+        .await
+        .unwrap();
 
     let paper = paper::In {
         name: "c191239".to_owned(),
         info: "Hello, world!".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     };
-    state.papers.insert(paper.into()).await.unwrap();
+    state
+        .papers
+        .insert(paper.into_paper(&config()).unwrap())
+        .await
+        .unwrap();
 
     let res = route
         .oneshot(
@@ -246,8 +616,11 @@ async fn approve_paper() {
         info: "Genshine Impact".to_owned(),
         email: None,
         color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
     }
-    .into();
+    .into_paper(&config())
+    .unwrap();
     let pid = paper.pid;
     state.papers.insert(paper).await.unwrap();
 
@@ -257,7 +630,7 @@ async fn approve_paper() {
                 .uri("/secret/approve_papers")
                 .method(http::Method::POST)
                 .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-                .body(serde_json::to_string(&paper::ApprRejReq { pid }).unwrap())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
                 .unwrap(),
         )
         .await
@@ -265,7 +638,7 @@ async fn approve_paper() {
         .status()
         .is_success());
 
-    let select = state.papers.select(0, pid);
+    let select = state.papers.select(0, pid.0);
     let mut iter = select.iter();
 
     while let Some(Ok(lazy)) = iter.next().await {
@@ -275,3 +648,6238 @@ async fn approve_paper() {
         }
     }
 }
+
+#[tokio::test]
+async fn reject_paper() {
+    let (state, route) = router();
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let reject = || {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/reject_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    assert!(reject().await.unwrap().status().is_success());
+
+    let select = state.papers.select(1, paper::Status::Rejected.as_dim());
+    let mut iter = select.iter();
+    let mut rejected = false;
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy.id() == pid {
+            if let Ok(paper) = lazy.get().await {
+                assert_eq!(paper.status, paper::Status::Rejected);
+                rejected = true;
+            }
+        }
+    }
+    assert!(rejected, "rejected paper should be kept");
+
+    assert_eq!(
+        reject().await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn validate_paper() {
+    let (state, route) = router();
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/paper/validate")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let paper::Validation { ok } =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(ok);
+
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "   ".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+    assert_eq!(
+        route
+            .oneshot(
+                Request::builder()
+                    .uri("/paper/validate")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(serde_json::to_string(&paper).unwrap())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::BAD_REQUEST
+    );
+
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    assert!(
+        iter.next().await.is_none(),
+        "validation should never insert"
+    );
+}
+
+#[tokio::test]
+async fn client_ip() {
+    use axum::extract::ConnectInfo;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    async fn post(route: Router, info: &str) {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        };
+        assert!(route
+            .oneshot(
+                Request::builder()
+                    .uri("/paper/post")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .header("x-forwarded-for", "1.2.3.4, 10.0.0.1")
+                    .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))))
+                    .body(serde_json::to_string(&paper).unwrap())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+            .is_success());
+    }
+
+    async fn ips(route: Router) -> Vec<Option<IpAddr>> {
+        let res = route
+            .oneshot(
+                Request::builder()
+                    .uri("/secret/get_papers")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let res: Vec<paper::MngOut> =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        res.into_iter().map(|paper| paper.ip).collect()
+    }
+
+    let (_, route) = router();
+    post(route.clone(), "Hello, world!").await;
+    assert_eq!(
+        ips(route).await,
+        [Some(IpAddr::V4(Ipv4Addr::LOCALHOST))],
+        "forwarded header should be ignored when not trusted"
+    );
+
+    let (_, route) =
+        router_with(|config| config.trusted_proxies = vec!["127.0.0.0/8".parse().unwrap()]);
+    post(route.clone(), "Hello, world!").await;
+    assert_eq!(
+        ips(route).await,
+        [Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))],
+        "rightmost untrusted hop should be taken"
+    );
+
+    let (_, route) = router_with(|config| {
+        config.trusted_proxies = vec![
+            "127.0.0.0/8".parse().unwrap(),
+            "10.0.0.0/8".parse().unwrap(),
+        ]
+    });
+    post(route.clone(), "Hello, world!").await;
+    assert_eq!(
+        ips(route).await,
+        [Some(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)))]
+    );
+}
+
+#[test]
+fn client_ip_headers() {
+    use axum::extract::ConnectInfo;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    let trusted = ["10.0.0.0/8".parse().unwrap()];
+    let parts = |peer: [u8; 4], header: (&str, &str)| {
+        Request::builder()
+            .header(header.0, header.1)
+            .extension(ConnectInfo(SocketAddr::from((peer, 8080))))
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    };
+
+    // spoofed by an untrusted peer
+    assert_eq!(
+        crate::ip::client_ip(
+            &parts([1, 1, 1, 1], ("cf-connecting-ip", "2.2.2.2")),
+            &trusted
+        ),
+        Some(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)))
+    );
+    assert_eq!(
+        crate::ip::client_ip(
+            &parts([1, 1, 1, 1], ("x-forwarded-for", "2.2.2.2")),
+            &trusted
+        ),
+        Some(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)))
+    );
+
+    // forwarded by a trusted proxy
+    assert_eq!(
+        crate::ip::client_ip(
+            &parts([10, 0, 0, 1], ("cf-connecting-ip", "2.2.2.2")),
+            &trusted
+        ),
+        Some(IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2)))
+    );
+    assert_eq!(
+        crate::ip::client_ip(
+            &parts(
+                [10, 0, 0, 1],
+                ("x-forwarded-for", "3.3.3.3, 2.2.2.2, 10.0.0.2")
+            ),
+            &trusted
+        ),
+        Some(IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2)))
+    );
+    assert_eq!(
+        crate::ip::client_ip(
+            &parts([10, 0, 0, 1], ("x-forwarded-for", "not an ip")),
+            &trusted
+        ),
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))
+    );
+}
+
+#[tokio::test]
+async fn pid_conflict_retry() {
+    use std::sync::atomic::Ordering;
+
+    let (state, route) = router();
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+
+    // Occupy the pid that the seeded rng generates first.
+    fastrand::seed(1024);
+    let occupied: paper::Paper = paper::In {
+        name: "c191239".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = occupied.pid;
+    state.papers.insert(occupied).await.unwrap();
+
+    fastrand::seed(1024);
+    assert!(route
+        .oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert_eq!(state.metrics.pid_retries.load(Ordering::Relaxed), 1);
+
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut count = 0;
+    while let Some(Ok(lazy)) = iter.next().await {
+        let paper = lazy.get().await.unwrap();
+        assert_eq!(paper.info == "Genshine Impact", lazy.id() == pid);
+        count += 1;
+    }
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn get_one_paper() {
+    let (state, route) = router();
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pending = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.status = paper::Status::Approved;
+    let approved = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let get = |pid: Pid, etag: Option<&str>| {
+        let mut req = Request::builder()
+            .uri(format!("/paper/get/{pid}"))
+            .method(http::Method::GET);
+        if let Some(etag) = etag {
+            req = req.header(http::header::IF_NONE_MATCH, etag);
+        }
+        route.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    assert_eq!(
+        get(pending, None).await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    let res = get(approved, None).await.unwrap();
+    assert!(res.status().is_success());
+    let etag = res.headers()[http::header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let paper::Out { pid, info, .. } =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(pid, approved);
+    assert_eq!(info, "Genshine Impact");
+
+    assert_eq!(
+        get(approved, Some(&etag)).await.unwrap().status(),
+        http::StatusCode::NOT_MODIFIED
+    );
+    assert!(get(approved, Some("\"0-0\""))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+}
+
+#[test]
+fn banned_words() {
+    let (state, _) = router_with(|config| {
+        config.banned_words = vec!["ass".to_owned(), "Genshin Impact".to_owned()];
+    });
+    let config = &state.config;
+
+    assert!(config.contains_banned_word("you ASS!"));
+    assert!(config.contains_banned_word("play genshin  impact"));
+    assert!(!config.contains_banned_word("a classic assessment"));
+    assert!(!config.contains_banned_word("Genshine Impact"));
+    assert!(!config.contains_banned_word("Impact Genshin"));
+
+    let (state, _) = router();
+    assert!(!state.config.contains_banned_word("you ass!"));
+}
+
+#[tokio::test]
+async fn post_banned_paper() {
+    let (state, route) = router_with(|config| config.banned_words = vec!["spam".to_owned()]);
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Buy SPAM now".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert!(
+        !String::from_utf8_lossy(&body)
+            .to_lowercase()
+            .contains("spam"),
+        "matched word should not be echoed"
+    );
+
+    let select = state.papers.select_all();
+    assert!(select.iter().next().await.is_none());
+}
+
+#[tokio::test]
+async fn get_one_question() {
+    let (state, route) = router();
+    let question: question::Question = question::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+    }
+    .into();
+    let pid = question.pid;
+    state.questions.insert(question).await.unwrap();
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/secret/get_questions/{pid}"))
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res["pid"], pid.0);
+    assert_eq!(res["info"], "Hello, world!");
+    assert_eq!(res["email"], "yjn024@example.com");
+
+    assert_eq!(
+        route
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/secret/get_questions/{}", pid.0.wrapping_add(1)))
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn paper_events() {
+    let (state, route) = router();
+    let mut events = state.paper_events.subscribe();
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/stream")
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        res.headers()[http::header::CONTENT_TYPE],
+        mime::TEXT_EVENT_STREAM.as_ref()
+    );
+
+    assert!(route
+        .oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    let paper::Event { pid, name } = events.try_recv().unwrap();
+    assert_eq!(name, "Yjn024");
+    let select = pid.select(&state.papers);
+    assert!(select.iter().next().await.is_some());
+
+    let mut body = res.into_body();
+    let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+    let frame = String::from_utf8(frame.to_vec()).unwrap();
+    assert!(frame.starts_with("event: paper\n"));
+    assert!(frame.contains(&format!("\"pid\":{pid}")));
+}
+
+#[tokio::test]
+async fn json_rejection() {
+    let (_, route) = router();
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::TEXT_PLAIN.as_ref())
+                .body("{}".to_owned())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(res["error"].is_string());
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/questions/new")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body("{\"name\":".to_owned())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(res["error"].is_string());
+}
+
+#[tokio::test]
+async fn route_not_found() {
+    let (_, route) = router();
+
+    for uri in ["/paper/gett", "/questions/old", "/secret/unknown/path"] {
+        let res = route
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+        let res: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(res["code"], "route");
+    }
+
+    // known routes are not shadowed
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/queue")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+}
+
+#[tokio::test]
+async fn resolve_question() {
+    let (state, route) = router();
+    let mut pids = vec![];
+    for info in ["Hello, world!", "Genshine Impact"] {
+        let question: question::Question = question::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+        }
+        .into();
+        pids.push(question.pid);
+        state.questions.insert(question).await.unwrap();
+    }
+
+    assert!(route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/resolve_questions")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&question::ResolveReq { pid: pids[0] }).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    let list = |uri: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .method(http::Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let mut res: serde_json::Value =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            assert_eq!(res["truncated"], false);
+            serde_json::from_value::<Vec<serde_json::Value>>(res["questions"].take()).unwrap()
+        }
+    };
+
+    let res = list("/secret/get_questions").await;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0]["pid"], pids[1].0);
+
+    let res = list("/secret/get_questions?include_resolved=true").await;
+    assert_eq!(res.len(), 2);
+    assert!(res
+        .iter()
+        .any(|question| question["pid"] == pids[0].0 && question["resolved"] == true));
+}
+
+#[test]
+fn check_secrets() {
+    let errors = config().check_secrets().unwrap_err();
+    assert_eq!(errors.len(), 5);
+    assert!(errors
+        .iter()
+        .all(|err| err.ends_with("at least 16 characters")));
+
+    let mut config = config();
+    config.mng_secret = "0123456789abcdef-root".to_owned();
+    config.mng_get_papers_secret = "0123456789abcdef-get".to_owned();
+    config.mng_approve_papers_secret = "0123456789abcdef-approve".to_owned();
+    config.mng_reject_papers_secret = "0123456789abcdef-reject".to_owned();
+    config.mng_get_questions_secret = "0123456789abcdef-questions".to_owned();
+    config.mng_resolve_questions_secret = "0123456789abcdef-resolve".to_owned();
+    assert!(config.check_secrets().is_ok());
+
+    config.mng_reject_papers_secret = config.mng_approve_papers_secret.clone();
+    config.mng_resolve_questions_secret = String::new();
+    let errors = config.check_secrets().unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            "mng_reject_papers_secret should be different from mng_approve_papers_secret",
+            "mng_resolve_questions_secret should not be empty",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn list_papers() {
+    let (state, route) = router();
+    let now = chrono::Utc::now();
+    for (i, status) in [
+        paper::Status::Pending,
+        paper::Status::Approved,
+        paper::Status::Pending,
+        paper::Status::Pending,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {i}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        paper.time = now - chrono::Duration::hours(i as i64);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let list = |query: String| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/secret/get_papers/list?{query}"))
+                        .method(http::Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            if !res.status().is_success() {
+                return Err(res.status());
+            }
+            let res: paper::ListRes =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            Ok((
+                res.total,
+                res.papers
+                    .into_iter()
+                    .map(|paper| paper.info)
+                    .collect::<Vec<_>>(),
+            ))
+        }
+    };
+
+    assert_eq!(
+        list(String::new()).await.unwrap(),
+        (
+            4,
+            vec!["Paper 3", "Paper 2", "Paper 1", "Paper 0"]
+                .into_iter()
+                .map(str::to_owned)
+                .collect()
+        )
+    );
+    assert_eq!(
+        list("status=0&limit=1&offset=1".to_owned()).await.unwrap(),
+        (3, vec!["Paper 2".to_owned()])
+    );
+
+    let since =
+        (now - chrono::Duration::minutes(150)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let until =
+        (now - chrono::Duration::minutes(30)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    assert_eq!(
+        list(format!("since={since}&until={until}")).await.unwrap(),
+        (2, vec!["Paper 2".to_owned(), "Paper 1".to_owned()])
+    );
+    assert_eq!(
+        list(format!("since={until}&until={since}")).await.unwrap(),
+        (0, vec![])
+    );
+    assert_eq!(
+        list("since=yesterday".to_owned()).await.unwrap_err(),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn compression() {
+    let (state, route) = router();
+    for i in 0..8 {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Hello, world! {i}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        };
+        state
+            .papers
+            .insert(paper.into_paper(&config()).unwrap())
+            .await
+            .unwrap();
+    }
+
+    let get = |uri: &'static str| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::GET)
+                .header(http::header::ACCEPT_ENCODING, "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let res = get("/secret/get_papers").await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.headers()[http::header::CONTENT_ENCODING], "gzip");
+
+    let res = get("/secret/get_papers/stream").await.unwrap();
+    assert!(res.status().is_success());
+    assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+}
+
+#[test]
+fn store_layout() {
+    use dmds::Data;
+
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as u64).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    let time = "2024-01-01T00:00:00Z";
+    let mut paper = paper::Paper {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        pid: Pid(1),
+        time: time.parse().unwrap(),
+        status: paper::Status::Approved,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        ip: Some([127, 0, 0, 1].into()),
+        rev: 2,
+        views: 3,
+        priority: true,
+        approved_at: Some(time.parse().unwrap()),
+        received_at: time.parse().unwrap(),
+    };
+    paper.image_url = Some("https://img.example.com/1.png".to_owned());
+
+    let mut expected = vec![];
+    put_str(&mut expected, "Yjn024");
+    put_str(&mut expected, "Hello, world!");
+    // Email: none
+    expected.push(0);
+    put_str(&mut expected, time);
+    put_str(&mut expected, "#ffc");
+    // IP: some, variant 0 (v4), octets
+    expected.push(1);
+    expected.extend(0u32.to_le_bytes());
+    expected.extend([127, 0, 0, 1]);
+    expected.extend(2u64.to_le_bytes());
+    expected.extend(3u64.to_le_bytes());
+    expected.push(1);
+    // Image URL: some
+    expected.push(1);
+    put_str(&mut expected, "https://img.example.com/1.png");
+    // Approval time: some
+    expected.push(1);
+    put_str(&mut expected, time);
+    put_str(&mut expected, time);
+
+    let mut buf = vec![];
+    paper.encode(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+
+    let decoded = paper::Paper::decode(
+        paper::Paper::VERSION,
+        &[paper.pid.0, paper.status.as_dim()],
+        &buf[..],
+    )
+    .unwrap();
+    assert_eq!(decoded.name, paper.name);
+    assert_eq!(decoded.info, paper.info);
+    assert_eq!(decoded.time, paper.time);
+    assert_eq!(decoded.status, paper.status);
+    assert_eq!(decoded.color, paper.color);
+    assert_eq!(decoded.ip, paper.ip);
+    assert_eq!(decoded.rev, paper.rev);
+    assert_eq!(decoded.views, paper.views);
+    assert_eq!(decoded.priority, paper.priority);
+    assert_eq!(decoded.image_url, paper.image_url);
+    assert_eq!(decoded.approved_at, paper.approved_at);
+    assert_eq!(decoded.received_at, paper.received_at);
+
+    // papers stored without receive times were received when posted
+    paper.time = "2023-01-01T00:00:00Z".parse().unwrap();
+    let mut buf = vec![];
+    paper.encode(&mut buf).unwrap();
+    buf.truncate(buf.len() - 8 - time.len());
+    let decoded = paper::Paper::decode(9, &[paper.pid.0, paper.status.as_dim()], &buf[..]).unwrap();
+    assert_eq!(decoded.received_at, paper.time);
+}
+
+#[test]
+fn sealed_emails() {
+    use dmds::Data;
+
+    let cipher = crate::crypt::Cipher::from_hex(
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+    )
+    .unwrap();
+    let other = crate::crypt::Cipher::from_hex(&"ff".repeat(32)).unwrap();
+    assert!(crate::crypt::Cipher::from_hex("0001").is_err());
+    assert!(crate::crypt::Cipher::from_hex(&"zz".repeat(32)).is_err());
+
+    let email = "yjn024@example.com";
+    let contains_email = |buf: &[u8]| buf.windows(email.len()).any(|w| w == email.as_bytes());
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: Some(email.parse().unwrap()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.pid = Pid(1);
+    let dims = [paper.pid.0, paper.status.as_dim()];
+
+    let mut sealed = vec![];
+    paper.encode_with(&mut sealed, Some(&cipher)).unwrap();
+    assert!(!contains_email(&sealed));
+    let decoded =
+        paper::Paper::decode_with(paper::Paper::VERSION, &dims, &sealed[..], Some(&cipher))
+            .unwrap();
+    assert_eq!(decoded.email, paper.email);
+    assert_eq!(decoded.info, paper.info);
+
+    // sealed emails are bound to their keys and records
+    assert!(paper::Paper::decode_with(paper::Paper::VERSION, &dims, &sealed[..], None).is_err());
+    assert!(
+        paper::Paper::decode_with(paper::Paper::VERSION, &dims, &sealed[..], Some(&other)).is_err()
+    );
+    assert!(paper::Paper::decode_with(
+        paper::Paper::VERSION,
+        &[2, dims[1]],
+        &sealed[..],
+        Some(&cipher)
+    )
+    .is_err());
+
+    // plain records stay readable after a key is configured
+    let mut plain = vec![];
+    paper.encode_with(&mut plain, None).unwrap();
+    assert!(contains_email(&plain));
+    let decoded =
+        paper::Paper::decode_with(paper::Paper::VERSION, &dims, &plain[..], Some(&cipher)).unwrap();
+    assert_eq!(decoded.email, paper.email);
+
+    let mut question: question::Question = question::In {
+        name: "Yjn024".to_owned(),
+        info: "What is Genshine Impact?".to_owned(),
+        email: Some(email.parse().unwrap()),
+    }
+    .into();
+    question.pid = Pid(1);
+    let mut sealed = vec![];
+    question.encode_with(&mut sealed, Some(&cipher)).unwrap();
+    assert!(!contains_email(&sealed));
+    let decoded = question::Question::decode_with(
+        question::Question::VERSION,
+        &[1],
+        &sealed[..],
+        Some(&cipher),
+    )
+    .unwrap();
+    assert_eq!(decoded.email, question.email);
+}
+
+#[tokio::test]
+async fn paper_queue() {
+    let (state, route) = router();
+    let now = chrono::Utc::now();
+    for (i, minutes) in [30, 10, 50, 20, 40].into_iter().enumerate() {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {i}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.time = now - chrono::Duration::minutes(minutes);
+        if i == 2 {
+            paper.status = paper::Status::Approved;
+        }
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let queue = |uri: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .method(http::Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let res: Vec<paper::MngOut> =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            res.into_iter().map(|paper| paper.info).collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        queue("/secret/get_papers/queue").await,
+        ["Paper 4", "Paper 0", "Paper 3", "Paper 1"]
+    );
+    assert_eq!(
+        queue("/secret/get_papers/queue?limit=2").await,
+        ["Paper 4", "Paper 0"]
+    );
+
+    let mut pids = vec![];
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        let paper = lazy.get().await.unwrap();
+        if paper.info == "Paper 3" || paper.info == "Paper 1" {
+            pids.push(paper.pid);
+        }
+    }
+    for pid in pids {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/secret/approve_papers/priority")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(
+                        serde_json::to_string(&paper::PriorityReq {
+                            pid,
+                            priority: true,
+                            rev: Some(0),
+                        })
+                        .unwrap(),
+                    )
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+    }
+    // prioritized papers go first, in time order
+    assert_eq!(
+        queue("/secret/get_papers/queue").await,
+        ["Paper 3", "Paper 1", "Paper 4", "Paper 0"]
+    );
+}
+
+#[tokio::test]
+async fn read_only() {
+    let (state, route) = router_with(|config| config.read_only = true);
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.status = paper::Status::Approved;
+    state.papers.insert(paper).await.unwrap();
+
+    let post = || {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Hello, world!".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        };
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper).unwrap())
+                .unwrap(),
+        )
+    };
+    let set_read_only = |read_only: bool| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/read_only")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&mng::ReadOnlyReq { read_only }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(
+        post().await.unwrap().status(),
+        http::StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert!(route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/paper/get")
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    assert!(set_read_only(false).await.unwrap().status().is_success());
+    assert!(post().await.unwrap().status().is_success());
+
+    assert!(set_read_only(true).await.unwrap().status().is_success());
+    assert_eq!(
+        post().await.unwrap().status(),
+        http::StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+#[tokio::test]
+async fn retention() {
+    let (state, _) = router();
+    let old = chrono::Utc::now() - chrono::Duration::days(31);
+
+    let mut kept = vec![];
+    for (status, backdated) in [
+        (paper::Status::Rejected, true),
+        (paper::Status::Rejected, false),
+        (paper::Status::Pending, true),
+        (paper::Status::Approved, true),
+    ] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        if backdated {
+            paper.time = old;
+        }
+        if status != paper::Status::Rejected || !backdated {
+            kept.push(paper.pid);
+        }
+        state.papers.insert(paper).await.unwrap();
+    }
+    for (resolved, backdated) in [(true, true), (true, false), (false, true)] {
+        let mut question: question::Question = question::In {
+            name: "Yjn024".to_owned(),
+            info: format!("resolved: {resolved}, backdated: {backdated}"),
+            email: None,
+        }
+        .into();
+        question.resolved = resolved;
+        if backdated {
+            question.time = old;
+        }
+        state.questions.insert(question).await.unwrap();
+    }
+
+    assert_eq!(crate::retention::purge(&state, 30).await, (1, 1));
+
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    let mut pids = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if let Ok(paper) = lazy.get().await {
+            pids.push(paper.pid);
+        }
+    }
+    pids.sort_unstable();
+    kept.sort_unstable();
+    assert_eq!(pids, kept);
+
+    assert_eq!(crate::retention::purge(&state, 30).await, (0, 0));
+}
+
+#[tokio::test]
+async fn paper_exists() {
+    let (state, route) = router();
+    let head = || {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/get")
+                .method(http::Method::HEAD)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let res = head().await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(res.headers()["x-paper-count"], "0");
+
+    for status in [paper::Status::Approved, paper::Status::Pending] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let res = head().await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::OK);
+    assert_eq!(res.headers()["x-paper-count"], "1");
+    assert!(res
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes()
+        .is_empty());
+}
+
+#[test]
+fn layout() {
+    let db_path = std::env::temp_dir().join(format!("subboard-layout-{}", fastrand::u64(..)));
+    let legacy = Layout::LEGACY;
+    assert_eq!(legacy.papers_items_per_chunk(), 576460752303423488);
+    assert_eq!(legacy.questions_items_per_chunk(), 1152921504606846976);
+
+    let tuned = Layout {
+        papers_chunk_bits: 8,
+        ..legacy
+    };
+    // fresh database persists the configured layout
+    tuned.check(&db_path).unwrap();
+    tuned.check(&db_path).unwrap();
+    assert!(matches!(
+        legacy.check(&db_path),
+        Err(layout::Error::Mismatch { .. })
+    ));
+    std::fs::remove_dir_all(&db_path).unwrap();
+
+    // existing data without marker is in legacy layout
+    std::fs::create_dir_all(db_path.join("papers")).unwrap();
+    std::fs::write(db_path.join("papers").join("chunk"), []).unwrap();
+    assert!(matches!(
+        tuned.check(&db_path),
+        Err(layout::Error::Mismatch { .. })
+    ));
+    legacy.check(&db_path).unwrap();
+    assert!(db_path.join("layout.toml").exists());
+    std::fs::remove_dir_all(&db_path).unwrap();
+
+    assert!(matches!(
+        Layout {
+            questions_chunk_bits: 0,
+            ..legacy
+        }
+        .check(&db_path),
+        Err(layout::Error::InvalidBits(0))
+    ));
+}
+
+#[tokio::test]
+async fn email_cooldown() {
+    let (_, route) = router_with(|config| config.email_cooldown_secs = 60);
+    let post = |info: &str, email: Option<&str>| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::In {
+                        name: "Yjn024".to_owned(),
+                        info: info.to_owned(),
+                        email: email.map(|email| email.parse().unwrap()),
+                        color: "#ffc".to_owned(),
+                        image_url: None,
+                        submitted_at: None,
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+    };
+
+    assert!(post("Hello, world!", Some("yjn024@example.com"))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    let res = post("Genshine Impact", Some("yjn024@example.com"))
+        .await
+        .unwrap();
+    let retry_after = assert_rate_limited(res).await;
+    assert!((1..=60).contains(&retry_after));
+
+    // the cooldown is shared with questions
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/questions/new")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&question::In {
+                        name: "Yjn024".to_owned(),
+                        info: "Hello, world!".to_owned(),
+                        email: Some("yjn024@example.com".parse().unwrap()),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_rate_limited(res).await;
+
+    // other or no emails are not affected
+    assert!(post("Genshine Impact", Some("other@example.com"))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert!(post("Honkai Impact", None)
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+}
+
+#[tokio::test]
+async fn version() {
+    let (_, route) = router();
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/version")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let version: crate::version::Version =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert!(!version.git_sha.is_empty());
+    assert!(version.built_at.is_some());
+}
+
+#[test]
+fn check_static_path() {
+    let mut config = config();
+    config.check_static_path().unwrap();
+
+    config.static_path = Some(std::env::temp_dir());
+    config.check_static_path().unwrap();
+
+    config.static_path =
+        Some(std::env::temp_dir().join(format!("subboard-static-{}", fastrand::u64(..))));
+    assert!(config.check_static_path().is_err());
+}
+
+#[tokio::test]
+async fn approve_all() {
+    let (state, _) = router_with(|config| {
+        config.approve_all_confirm = Some("approve them all".to_owned());
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        });
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+    let route = crate::routes(&state).with_state(state.clone());
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    for (status, email) in [
+        (paper::Status::Pending, Some(email.clone())),
+        (paper::Status::Pending, None),
+        (paper::Status::Rejected, Some(email.clone())),
+    ] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+    let approve_all = |confirm: &str| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(format!("/secret/approve_all?confirm={confirm}"))
+                .method(http::Method::POST)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(
+        approve_all("approve").await.unwrap().status(),
+        http::StatusCode::BAD_REQUEST
+    );
+
+    let res = approve_all("approve%20them%20all").await.unwrap();
+    assert!(res.status().is_success());
+    let res: paper::ApproveAllRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.approved, 2);
+    assert_eq!(res.failed, 0);
+
+    let select = state.papers.select(1, paper::Status::Approved.as_dim());
+    assert_eq!(select.iter().filter(Result::is_ok).count().await, 2);
+    // authors are notified the same as single approvals
+    {
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].envelope().to(), [email]);
+    }
+
+    // disabled without configured confirmation
+    let (_, route) = router();
+    assert_eq!(
+        route
+            .oneshot(
+                Request::builder()
+                    .uri("/secret/approve_all?confirm=")
+                    .method(http::Method::POST)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn request_id() {
+    let (_, route) = router();
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/paper/get/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    let id: uuid::Uuid = res.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res["request_id"], id.to_string());
+    assert!(res["timestamp"]
+        .as_str()
+        .unwrap()
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .is_ok());
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/version")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert_ne!(
+        res.headers()["x-request-id"].to_str().unwrap(),
+        id.to_string()
+    );
+}
+
+#[tokio::test]
+async fn import_legacy() {
+    let (state, route) = router();
+    let entries = serde_json::json!([
+        {
+            "author": "Yjn024",
+            "text": "Hello, world!",
+            "approved": true,
+            "created": "2023-01-01T00:00:00Z",
+        },
+        {
+            "author": "Yjn024",
+            "text": "Genshine Impact",
+            "approved": false,
+            "created": "2023-01-02T00:00:00Z",
+        },
+        { "author": "Yjn024" },
+        "not a paper",
+    ]);
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/import_legacy")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(entries.to_string())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: paper::ImportRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.imported, 2);
+    assert_eq!(
+        res.skipped
+            .iter()
+            .map(|skipped| skipped.index)
+            .collect::<Vec<_>>(),
+        [2, 3]
+    );
+
+    for (status, info) in [
+        (paper::Status::Approved, "Hello, world!"),
+        (paper::Status::Pending, "Genshine Impact"),
+    ] {
+        let select = state.papers.select(1, status.as_dim());
+        let mut iter = select.iter();
+        let lazy = iter.next().await.unwrap().unwrap();
+        let paper = lazy.get().await.unwrap();
+        assert_eq!(paper.name, "Yjn024");
+        assert_eq!(paper.info, info);
+        assert_eq!(paper.status, status);
+    }
+}
+
+#[tokio::test]
+async fn import_legacy_streaming() {
+    let (state, route) = router();
+    let body = r#"[
+        {"author": "Yjn024", "text": "[\"}, {", "approved": true, "created": "2023-01-01T00:00:00Z"},
+        {"author": "Yjn024", "text": "Genshine Impact", "approved": true, "created": "2023-01-02T00:00:00Z"},
+        {"author": "Yjn024", "text": "trunc"#;
+    // splits the body into small chunks to cross entry boundaries
+    let chunks = body
+        .as_bytes()
+        .chunks(7)
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+        .collect::<Vec<_>>();
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/import_legacy")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(axum::body::Body::from_stream(tokio_stream::iter(chunks)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: paper::ImportRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.imported, 2);
+    assert!(res.skipped.is_empty());
+    assert_eq!(res.error.as_deref(), Some("JSON array is truncated"));
+
+    let select = state.papers.select(1, paper::Status::Approved.as_dim());
+    let mut iter = select.iter();
+    let mut infos = vec![];
+    while let Some(lazy) = iter.next().await {
+        infos.push(lazy.unwrap().get().await.unwrap().info.clone());
+    }
+    infos.sort();
+    assert_eq!(infos, ["Genshine Impact", "[\"}, {"]);
+
+    let mut elements = crate::json::Elements::default();
+    let mut out = vec![];
+    assert!(elements.feed(b"{}", &mut out).is_err());
+}
+
+#[tokio::test]
+async fn spam_heuristics() {
+    let (_, route) = router_with(|config| config.max_links = 1);
+    let post = |info: &str| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::In {
+                        name: "Yjn024".to_owned(),
+                        info: info.to_owned(),
+                        email: None,
+                        color: "#ffc".to_owned(),
+                        image_url: None,
+                        submitted_at: None,
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+    };
+
+    for (info, code) in [
+        ("spam", "too_short"),
+        (
+            "https://spam.example HTTP://spam.example/again",
+            "too_many_links",
+        ),
+    ] {
+        let res = post(info).await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+        let res: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(res["code"], code);
+    }
+
+    assert!(post("see https://subit.example for details")
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+}
+
+#[tokio::test]
+async fn update_question() {
+    let (state, route) = router();
+    let question: question::Question = question::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+    }
+    .into();
+    let pid = question.pid;
+    state.questions.insert(question).await.unwrap();
+
+    let update = |body: String| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/resolve_questions/update")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap(),
+        )
+    };
+    let get = || async {
+        let select = pid.select(&state.questions);
+        let mut iter = select.iter();
+        while let Some(Ok(lazy)) = iter.next().await {
+            if lazy.id() == pid {
+                if let Ok(question) = lazy.get().await {
+                    return question.clone();
+                }
+            }
+        }
+        unreachable!("question should exist")
+    };
+
+    // omitted email is left unchanged
+    assert!(update(
+        serde_json::to_string(&question::UpdateReq {
+            pid,
+            name: Some("Anonymous".to_owned()),
+            info: None,
+            email: None,
+        })
+        .unwrap()
+    )
+    .await
+    .unwrap()
+    .status()
+    .is_success());
+    let question = get().await;
+    assert_eq!(question.name, "Anonymous");
+    assert_eq!(question.info, "Hello, world!");
+    assert!(question.email.is_some());
+
+    assert!(update(format!("{{\"pid\":{pid},\"email\":null}}"))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert!(get().await.email.is_none());
+
+    assert_eq!(
+        update(format!("{{\"pid\":{pid},\"info\":\" \"}}"))
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn unprocessed_ndjson() {
+    let (state, route) = router();
+    for info in ["Hello, world!", "Genshine Impact", "Honkai: Star Rail"] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers")
+                .header(http::header::ACCEPT, "application/x-ndjson")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        res.headers()[http::header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let papers = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<paper::MngOut>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(papers.len(), 3);
+}
+
+#[tokio::test]
+async fn paper_stats() {
+    let (state, route) = router();
+    for &status in paper::Status::all() {
+        for _ in 0..=status.as_dim() {
+            let mut paper: paper::Paper = paper::In {
+                name: "Yjn024".to_owned(),
+                info: "Genshine Impact".to_owned(),
+                email: None,
+                color: "#ffc".to_owned(),
+                image_url: None,
+                submitted_at: None,
+            }
+            .into_paper(&config())
+            .unwrap();
+            paper.status = status;
+            state.papers.insert(paper).await.unwrap();
+        }
+    }
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/stats")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: Vec<paper::StatusCount> =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.len(), paper::Status::all().len());
+    for (count, &status) in res.iter().zip(paper::Status::all()) {
+        assert_eq!(count.status, status);
+        assert_eq!(count.count as u64, status.as_dim() + 1);
+    }
+}
+
+#[tokio::test]
+async fn default_paper() {
+    let get = |route: Router| {
+        route.oneshot(
+            Request::builder()
+                .uri("/paper/get")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let (_, route) = router();
+    assert_eq!(
+        get(route).await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    let (state, route) = router_with(|config| {
+        config.default_paper = Some(paper::DefaultPaper {
+            name: "SubIT".to_owned(),
+            info: "Welcome!".to_owned(),
+            color: "#fff".to_owned(),
+        })
+    });
+    let res = get(route.clone()).await.unwrap();
+    assert!(res.status().is_success());
+    let res: paper::Out =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.pid, Pid(0));
+    assert_eq!(res.info, "Welcome!");
+    assert_eq!(state.papers.select_all().iter().count().await, 0);
+
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.status = paper::Status::Approved;
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+    let res = get(route).await.unwrap();
+    let res: paper::Out =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.pid, pid);
+}
+
+#[tokio::test]
+async fn decode_errors() {
+    use dmds::Data;
+    use futures_lite::AsyncWriteExt;
+    use std::sync::atomic::Ordering;
+
+    let mut healthy: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Hello, world!".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    healthy.pid = Pid(1);
+    healthy.status = paper::Status::Approved;
+    let mut encoded = vec![];
+    healthy.encode(&mut encoded).unwrap();
+
+    // one healthy record and one truncated record in the same chunk
+    let mut chunk = vec![];
+    for (pid, bytes) in [(1u64, encoded), (2, vec![0xff; 4])] {
+        chunk.extend(pid.to_be_bytes());
+        chunk.extend(paper::Status::Approved.as_dim().to_be_bytes());
+        chunk.extend((bytes.len() as u32).to_be_bytes());
+        chunk.extend(bytes);
+    }
+    let storage = MemStorage::new();
+    storage
+        .write_chunk(
+            paper::Paper::VERSION,
+            [0, paper::Status::Approved.as_dim() as usize],
+        )
+        .await
+        .unwrap()
+        .write_all(&chunk)
+        .await
+        .unwrap();
+
+    let config = config();
+    let layout = config.layout;
+    let state = Global::new(
+        Arc::new(config),
+        dmds::world! {
+            storage,
+            layout.papers_items_per_chunk() => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        },
+        dmds::world! {
+            MemStorage::new(),
+            layout.questions_items_per_chunk() => ..=u64::MAX,
+        },
+        dmds::world! {
+            MemStorage::new(),
+            crate::audit::ITEMS_PER_CHUNK => ..=u64::MAX,
+        },
+    );
+    let route = crate::routes(&state).with_state(state.clone());
+
+    // the random paper falls through to the healthy one
+    for _ in 0..8 {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/paper/get")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        let res: paper::Out =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(res.pid, Pid(1));
+    }
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/list")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let res: paper::ListRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.total, 1);
+    assert!(state.metrics.decode_errors.load(Ordering::Relaxed) > 0);
+}
+
+#[tokio::test]
+async fn post_batch() {
+    let (state, route) = router();
+    let paper = |info: &str| paper::In {
+        name: "Yjn024".to_owned(),
+        info: info.to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+    let post = |body: String| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post_batch")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap(),
+        )
+    };
+
+    // papers failed to post don't roll back others
+    let batch = [
+        paper("Genshine Impact"),
+        paper(""),
+        paper("Honkai: Star Rail"),
+    ];
+    let res = post(serde_json::to_string(&batch).unwrap()).await.unwrap();
+    assert!(res.status().is_success());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let res: Vec<paper::BatchItem> = serde_json::from_slice(&body).unwrap();
+    let [paper::BatchItem::Posted(first), paper::BatchItem::Failed { code, .. }, paper::BatchItem::Posted(third)] =
+        &res[..]
+    else {
+        panic!("unexpected results: {res:?}");
+    };
+    assert_eq!(code, "empty_info");
+
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut infos = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        let paper = lazy.get().await.unwrap();
+        assert!([first.pid, third.pid].contains(&paper.pid));
+        infos.push(paper.info.clone());
+    }
+    infos.sort();
+    assert_eq!(infos, ["Genshine Impact", "Honkai: Star Rail"]);
+
+    // batches are limited by count and size
+    let batch: Vec<_> = (0..=Config::default_max_batch_len())
+        .map(|i| paper(&format!("Paper {i}")))
+        .collect();
+    let res = post(serde_json::to_string(&batch).unwrap()).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let res: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(res["code"], "batch_too_large");
+
+    let res = post(format!(
+        "[{}]",
+        serde_json::to_string(&paper(&" ".repeat(config().batch_body_limit()))).unwrap()
+    ))
+    .await
+    .unwrap();
+    assert_eq!(res.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn display_timezone() {
+    let (state, route) =
+        router_with(|config| config.display_timezone = Some(chrono_tz::Asia::Shanghai));
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.time = "2024-01-01T00:00:00Z".parse().unwrap();
+    state.papers.insert(paper).await.unwrap();
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/list")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res["papers"][0]["time"], "2024-01-01T08:00:00+08:00");
+
+    let config = |tz: &str| {
+        toml::from_str::<Config>(&format!(
+            r#"
+            db_path = "./db"
+            address = "0.0.0.0:8080"
+            mng_secret = "secret"
+            mng_get_papers_secret = "get_papers"
+            mng_approve_papers_secret = "approve_papers"
+            mng_reject_papers_secret = "reject_papers"
+            mng_get_questions_secret = "get_questions"
+            mng_resolve_questions_secret = "resolve_questions"
+            display_timezone = "{tz}"
+            "#
+        ))
+    };
+    assert_eq!(
+        config("Asia/Shanghai").unwrap().display_timezone,
+        Some(chrono_tz::Asia::Shanghai)
+    );
+    assert!(config("Mars/Olympus_Mons").is_err());
+}
+
+#[tokio::test]
+async fn paper_rev() {
+    let (state, route) = router();
+    let mut pids = vec![];
+    for info in ["Hello, world!", "Genshine Impact"] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let send = |uri: &'static str, pid: Pid, rev: Option<u64>| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(
+        send("/secret/approve_papers", pids[0], Some(1))
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::CONFLICT
+    );
+    assert!(send("/secret/approve_papers", pids[0], Some(0))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert_eq!(
+        send("/secret/reject_papers", pids[1], Some(3))
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::CONFLICT
+    );
+    assert!(send("/secret/reject_papers", pids[1], Some(0))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/list")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let res: paper::ListRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.total, 2);
+    assert!(res.papers.iter().all(|paper| paper.rev == 1));
+}
+
+#[tokio::test]
+async fn public_config() {
+    let (state, route) = router_with(|config| {
+        config.board_title = Some("SubIT Board".to_owned());
+        config.instructions = Some("Be nice.".to_owned());
+    });
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/config/public")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let public: crate::public::PublicConfig = serde_json::from_slice(&body).unwrap();
+    assert_eq!(public.board_title.as_deref(), Some("SubIT Board"));
+    assert_eq!(public.max_info_len, state.config.max_info_len);
+
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(!body.contains("mng_"));
+    for (_, secret) in state.config.secrets() {
+        assert!(!body.contains(&format!("\"{secret}\"")));
+    }
+}
+
+#[tokio::test]
+async fn require_email() {
+    async fn submit(route: &Router, email: Option<&str>) -> (http::StatusCode, http::StatusCode) {
+        let email = email.map(|email| email.parse::<lettre::Address>().unwrap());
+        let post = |uri: &str, body: String| {
+            route.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(body)
+                    .unwrap(),
+            )
+        };
+        let paper = post(
+            "/paper/post",
+            serde_json::to_string(&paper::In {
+                name: "Yjn024".to_owned(),
+                info: "Genshine Impact".to_owned(),
+                email: email.clone(),
+                color: "#ffc".to_owned(),
+                image_url: None,
+                submitted_at: None,
+            })
+            .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status();
+        let question = post(
+            "/questions/new",
+            serde_json::to_string(&question::In {
+                name: "Yjn024".to_owned(),
+                info: format!("Hello from {email:?}"),
+                email,
+            })
+            .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status();
+        (paper, question)
+    }
+
+    let (_, route) = router();
+    assert_eq!(
+        submit(&route, None).await,
+        (http::StatusCode::OK, http::StatusCode::OK)
+    );
+
+    let (_, route) = router_with(|config| config.require_email = true);
+    assert_eq!(
+        submit(&route, None).await,
+        (http::StatusCode::BAD_REQUEST, http::StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(
+        submit(&route, Some("yjn024@example.com")).await,
+        (http::StatusCode::OK, http::StatusCode::OK)
+    );
+}
+
+#[tokio::test]
+async fn compact() {
+    let (state, route) = router();
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/reject_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let compact = || {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/compact")
+                .method(http::Method::POST)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let res = compact().await.unwrap();
+    assert!(res.status().is_success());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let res: compact::CompactRes = serde_json::from_slice(&body).unwrap();
+    // pending and rejected chunks of the paper
+    assert_eq!(res.chunks, 2);
+
+    let select = state.papers.select(1, paper::Status::Rejected.as_dim());
+    let mut iter = select.iter();
+    let mut found = false;
+    while let Some(Ok(lazy)) = iter.next().await {
+        found |= lazy.id() == pid && lazy.get().await.is_ok();
+    }
+    assert!(found, "compaction should keep live records");
+
+    let res = compact().await.unwrap();
+    assert_rate_limited(res).await;
+}
+
+#[test]
+fn paper_from_in() {
+    let input = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    };
+    let paper = input.clone().into_paper(&config()).unwrap();
+    assert_eq!(paper.status, paper::Status::Pending);
+    assert_ne!(paper.pid, Pid(0));
+    assert_eq!(paper.rev, 0);
+    assert!(paper.ip.is_none());
+    assert!((chrono::Utc::now() - paper.time).abs() < chrono::Duration::seconds(1));
+
+    let another = input.into_paper(&config()).unwrap();
+    assert_ne!(paper.pid, another.pid, "pids should be random");
+}
+
+#[test]
+fn question_from_in() {
+    let input = question::In {
+        name: "Yjn024".to_owned(),
+        info: "What is Genshine Impact?".to_owned(),
+        email: None,
+    };
+    let question = question::Question::from(input.clone());
+    assert!(!question.resolved);
+    assert_ne!(question.pid, Pid(0));
+    assert!(question.ip.is_none());
+    assert!((chrono::Utc::now() - question.time).abs() < chrono::Duration::seconds(1));
+
+    let another = question::Question::from(input);
+    assert_ne!(question.pid, another.pid, "pids should be random");
+}
+
+#[test]
+fn accept_language() {
+    assert_eq!(Lang::from_accept_language(""), Lang::En);
+    assert_eq!(Lang::from_accept_language("zh-CN"), Lang::Zh);
+    assert_eq!(Lang::from_accept_language("fr-FR, de"), Lang::En);
+    assert_eq!(
+        Lang::from_accept_language("en-US,en;q=0.9,zh;q=0.8"),
+        Lang::En
+    );
+    assert_eq!(
+        Lang::from_accept_language("fr;q=1, zh-Hans;q=0.7, en;q=0.5"),
+        Lang::Zh
+    );
+    assert_eq!(Lang::from_accept_language("zh, en"), Lang::Zh);
+    assert_eq!(Lang::from_accept_language("zh;q=0, en;q=0.1"), Lang::En);
+}
+
+#[tokio::test]
+async fn localized_errors() {
+    let (_, route) = router();
+    let post = |lang: Option<&'static str>| {
+        let mut req = Request::builder()
+            .uri("/paper/post")
+            .method(http::Method::POST)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+        if let Some(lang) = lang {
+            req = req.header(http::header::ACCEPT_LANGUAGE, lang);
+        }
+        let body = serde_json::to_string(&paper::In {
+            name: String::new(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        })
+        .unwrap();
+        let route = route.clone();
+        async move {
+            let res = route.oneshot(req.body(body).unwrap()).await.unwrap();
+            assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    let body = post(None).await;
+    assert_eq!(body["error"], "name should not be empty");
+    assert_eq!(body["code"], "empty_name");
+
+    let body = post(Some("zh-CN,zh;q=0.9,en;q=0.8")).await;
+    assert_eq!(body["error"], "名字不能为空");
+    assert_eq!(body["code"], "empty_name");
+}
+
+#[tokio::test]
+async fn route_timeouts() {
+    async fn slow() -> &'static str {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        "done"
+    }
+
+    let mut config = config();
+    config.request_timeout_ms = 20;
+    let send = |config: &Config| {
+        Router::new()
+            .route(
+                "/",
+                crate::with_timeout(config, "list", axum::routing::get(slow)),
+            )
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+    };
+
+    assert_eq!(
+        send(&config).await.unwrap().status(),
+        http::StatusCode::REQUEST_TIMEOUT
+    );
+
+    config.route_timeouts.insert("list".to_owned(), 5_000);
+    assert!(send(&config).await.unwrap().status().is_success());
+
+    config.route_timeouts.insert("export".to_owned(), 5_000);
+    assert_eq!(config.unknown_route_timeouts(), ["export"]);
+}
+
+#[tokio::test]
+async fn duplicate_questions() {
+    let (state, route) = router();
+    let question = question::In {
+        name: "Yjn024".to_owned(),
+        info: "What is Genshine Impact?".to_owned(),
+        email: None,
+    };
+    for _ in 0..2 {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/questions/new")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(serde_json::to_string(&question).unwrap())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+    }
+
+    let select = state.questions.select_all();
+    let mut iter = select.iter();
+    let mut pids = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        pids.push(lazy.id());
+    }
+    assert_eq!(pids.len(), 2);
+    assert_ne!(pids[0], pids[1]);
+}
+
+#[tokio::test]
+async fn search_by_email() {
+    let (state, route) = router();
+    for (i, email) in [
+        "Yjn024@Example.com",
+        "yjn024@example.COM",
+        "yjn024@example.com",
+        "other@example.com",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let email = Some(email.parse::<lettre::Address>().unwrap());
+        state
+            .papers
+            .insert(
+                paper::In {
+                    name: "Yjn024".to_owned(),
+                    info: "Genshine Impact".to_owned(),
+                    email: email.clone(),
+                    color: "#ffc".to_owned(),
+                    image_url: None,
+                    submitted_at: None,
+                }
+                .into_paper(&config())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        state
+            .questions
+            .insert(
+                question::In {
+                    name: "Yjn024".to_owned(),
+                    info: format!("Question {i}"),
+                    email,
+                }
+                .into(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let search = |uri: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            let mut res = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+            let results = if res["papers"].is_array() {
+                res["papers"].take()
+            } else {
+                res["questions"].take()
+            };
+            serde_json::from_value::<Vec<serde_json::Value>>(results).unwrap()
+        }
+    };
+
+    // local parts are case-sensitive, while domains are not
+    let papers = search("/secret/get_papers/search?email=yjn024@Example.Com").await;
+    assert_eq!(papers.len(), 2);
+    assert!(papers.iter().all(|paper| paper["status"] == 0));
+    let questions = search("/secret/get_questions/search?email=yjn024@example.com").await;
+    assert_eq!(questions.len(), 2);
+    assert!(questions.iter().all(|question| question["pid"].is_u64()));
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_questions/search?email=invalid")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn static_cache() {
+    let dir = std::env::temp_dir().join(format!("subboard-static-{}", fastrand::u64(..)));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "<!doctype html>").unwrap();
+    std::fs::write(dir.join("index-3f2a1c.js"), "console.log(1)").unwrap();
+
+    let cache_control = |secs: u64, uri: &'static str| {
+        let route = crate::static_files(&dir, secs);
+        async move {
+            let res = route
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            res.headers()
+                .get(http::header::CACHE_CONTROL)
+                .map(|value| value.to_str().unwrap().to_owned())
+        }
+    };
+
+    assert_eq!(cache_control(3600, "/").await.as_deref(), Some("no-cache"));
+    assert_eq!(
+        cache_control(3600, "/index.html").await.as_deref(),
+        Some("no-cache")
+    );
+    assert_eq!(
+        cache_control(3600, "/index-3f2a1c.js").await.as_deref(),
+        Some("public, max-age=3600")
+    );
+    assert_eq!(
+        cache_control(0, "/index-3f2a1c.js").await.as_deref(),
+        Some("no-cache")
+    );
+    assert_eq!(cache_control(3600, "/missing.js").await, None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn approval_email() {
+    let (state, _) = router_with(|config| {
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        })
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+    let route = crate::routes(&state).with_state(state.clone());
+
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    let mut pids = vec![];
+    for email in [Some(email.clone()), None] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    for pid in pids {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/secret/approve_papers")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+    }
+
+    let sent = mailer.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].envelope().to(), [email]);
+}
+
+#[tokio::test]
+async fn approve_twice() {
+    let (state, _) = router_with(|config| {
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        })
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+    let route = crate::routes(&state).with_state(state.clone());
+
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let approve = || {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+    let approved_at = || async {
+        let select = state.papers.select(1, paper::Status::Approved.as_dim());
+        let mut iter = select.iter();
+        let mut approved_at = None;
+        while let Some(Ok(lazy)) = iter.next().await {
+            let paper = lazy.get().await.unwrap();
+            if paper.pid == pid {
+                approved_at = paper.approved_at;
+            }
+        }
+        approved_at.unwrap()
+    };
+
+    assert!(approve().await.unwrap().status().is_success());
+    let first = approved_at().await;
+    // approved papers are not pending anymore
+    assert_eq!(
+        approve().await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+    assert_eq!(approved_at().await, first);
+    assert_eq!(mailer.sent.lock().unwrap().len(), 1);
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/audit")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let res: audit::AuditRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.entries.len(), 1);
+}
+
+#[tokio::test]
+async fn get_paper_exclude() {
+    async fn get(route: &Router, uri: String) -> (http::StatusCode, Option<u64>) {
+        let res = route
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let pid = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["pid"].as_u64();
+        (status, pid)
+    }
+
+    for strict in [false, true] {
+        let (state, route) = router_with(|config| config.strict_exclude = strict);
+        let mut pids = vec![];
+        for _ in 0..2 {
+            let mut paper: paper::Paper = paper::In {
+                name: "Yjn024".to_owned(),
+                info: "Genshine Impact".to_owned(),
+                email: None,
+                color: "#ffc".to_owned(),
+                image_url: None,
+                submitted_at: None,
+            }
+            .into_paper(&config())
+            .unwrap();
+            paper.status = paper::Status::Approved;
+            pids.push(paper.pid);
+            state.papers.insert(paper).await.unwrap();
+
+            for _ in 0..8 {
+                let (status, pid) = get(&route, format!("/paper/get?exclude={}", pids[0])).await;
+                if pids.len() == 1 && strict {
+                    assert_eq!(status, http::StatusCode::NOT_FOUND);
+                } else if pids.len() == 1 {
+                    assert_eq!(pid, Some(pids[0].0));
+                } else {
+                    assert_eq!(pid, Some(pids[1].0));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn load_config() {
+    let template =
+        include_str!("../config-template.toml").replace("static_path = \"./static\"", "");
+    crate::load_config(&template).unwrap();
+
+    let invalid = template
+        .replace("change-me-approve-papers", "short")
+        .replace("change-me-reject-papers", "change-me-get-papers")
+        .replace(
+            "retention_interval_secs = 3600",
+            "retention_interval_secs = 0",
+        )
+        .replace("papers_chunk_bits = 5", "papers_chunk_bits = 0")
+        + "static_path = \"./non-existent-static\"\n";
+    let errors = crate::load_config(&invalid).unwrap_err().0;
+    assert_eq!(errors.len(), 5, "{errors:#?}");
+    for field in [
+        "mng_approve_papers_secret",
+        "mng_reject_papers_secret",
+        "static_path",
+        "retention_interval_secs",
+        "bits",
+    ] {
+        assert!(
+            errors.iter().any(|err| err.contains(field)),
+            "{field} should be reported in {errors:#?}"
+        );
+    }
+
+    let invalid = template.replace(
+        "# display_timezone = \"Asia/Shanghai\"",
+        "display_timezone = \"Mars/Olympus\"",
+    );
+    let errors = crate::load_config(&invalid).unwrap_err().0;
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("display_timezone"), "{errors:#?}");
+
+    let valid = template.replace("# encryption_key = ", "encryption_key = ");
+    crate::load_config(&valid).unwrap();
+    let invalid = template.replace("# encryption_key = \"000102", "encryption_key = \"zz0102");
+    let errors = crate::load_config(&invalid).unwrap_err().0;
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("encryption_key"), "{errors:#?}");
+}
+
+#[test]
+fn parse_args() {
+    let parse = |args: &[&str]| crate::Args::parse(args.iter().map(|&arg| arg.to_owned()));
+    assert_eq!(parse(&[]).unwrap(), crate::Args::default());
+    assert!(parse(&["--check-config"]).unwrap().check_config);
+    assert!(parse(&["--check"]).is_err());
+}
+
+#[tokio::test]
+async fn paper_views() {
+    let (state, route) = router_with(|config| config.count_views = true);
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.status = paper::Status::Approved;
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let get = || {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/paper/get/{pid}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<paper::Out>(&body).unwrap().views
+        }
+    };
+
+    for _ in 0..3 {
+        assert_eq!(get().await, 0, "views should be flushed in batch");
+    }
+    assert_eq!(crate::views::flush(&state).await, 1);
+    assert_eq!(get().await, 3);
+    assert_eq!(crate::views::flush(&state).await, 1);
+    assert_eq!(get().await, 4);
+}
+
+#[test]
+fn paper_views_legacy() {
+    use dmds::Data;
+
+    let mut buf = vec![];
+    bincode::Options::serialize_into(
+        crate::bincode_options(),
+        &mut buf,
+        &(
+            "Yjn024",
+            "Hello, world!",
+            None::<lettre::Address>,
+            chrono::Utc::now(),
+            "#ffc",
+            None::<std::net::IpAddr>,
+            2u64,
+        ),
+    )
+    .unwrap();
+    let paper = paper::Paper::decode(4, &[1, paper::Status::Approved.as_dim()], &buf[..]).unwrap();
+    assert_eq!(paper.rev, 2);
+    assert_eq!(paper.views, 0);
+
+    let mut buf = vec![];
+    bincode::Options::serialize_into(
+        crate::bincode_options(),
+        &mut buf,
+        &(
+            "Yjn024",
+            "Hello, world!",
+            None::<lettre::Address>,
+            chrono::Utc::now(),
+            "#ffc",
+            None::<std::net::IpAddr>,
+            2u64,
+            3u64,
+        ),
+    )
+    .unwrap();
+    let paper = paper::Paper::decode(5, &[1, paper::Status::Pending.as_dim()], &buf[..]).unwrap();
+    assert_eq!(paper.views, 3);
+    assert!(!paper.priority);
+}
+
+#[tokio::test]
+async fn rotate_secret() {
+    let (_, route) = router();
+    let send = |method: http::Method, uri: String, body: Option<String>| {
+        let mut req = Request::builder().uri(uri).method(method);
+        if body.is_some() {
+            req = req.header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+        }
+        route
+            .clone()
+            .oneshot(req.body(body.map_or_else(Body::empty, Body::from)).unwrap())
+    };
+    let rotate = |root: String, name: &str| {
+        send(
+            http::Method::POST,
+            format!("/{root}/rotate_secret"),
+            Some(
+                serde_json::to_string(&crate::secret::RotateReq {
+                    name: name.to_owned(),
+                })
+                .unwrap(),
+            ),
+        )
+    };
+    let rotated = |res: axum::response::Response| async move {
+        assert!(res.status().is_success());
+        assert_eq!(res.headers()[http::header::CACHE_CONTROL], "no-store");
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice::<crate::secret::RotateRes>(&body)
+            .unwrap()
+            .secret
+    };
+
+    let get_papers = rotated(
+        rotate("secret".to_owned(), "mng_get_papers_secret")
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(get_papers.len() >= 32);
+    let status = |res: Result<axum::response::Response, _>| res.unwrap().status();
+    assert_eq!(
+        status(send(http::Method::GET, "/secret/get_papers".to_owned(), None).await),
+        http::StatusCode::NOT_FOUND
+    );
+    assert!(
+        status(send(http::Method::GET, format!("/secret/{get_papers}"), None).await).is_success()
+    );
+
+    let root = rotated(rotate("secret".to_owned(), "mng_secret").await.unwrap()).await;
+    assert_eq!(
+        status(send(http::Method::GET, format!("/secret/{get_papers}"), None).await),
+        http::StatusCode::NOT_FOUND
+    );
+    assert!(
+        status(send(http::Method::GET, format!("/{root}/{get_papers}"), None).await).is_success()
+    );
+    // rotations keep earlier ones
+    assert!(
+        status(send(http::Method::GET, format!("/{root}/get_questions"), None).await).is_success()
+    );
+
+    assert_eq!(
+        status(rotate("secret".to_owned(), "mng_secret").await),
+        http::StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(rotate(root, "approve_all_confirm").await),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn json_depth() {
+    let (_, route) = router();
+    let post = |body: String| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap(),
+        )
+    };
+
+    let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+    let res = post(nested).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(body["error"].as_str().unwrap().contains("nested"));
+
+    // brackets in strings are not nesting
+    let res = post(
+        serde_json::to_string(&paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("\"{}\\\"", "[{".repeat(crate::json::MAX_DEPTH)),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        })
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(res.status().is_success());
+}
+
+#[tokio::test]
+async fn restore_rejected() {
+    let (state, route) = router();
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let post = |uri: &'static str| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    // only rejected papers can be restored
+    assert_eq!(
+        post("/secret/reject_papers/restore")
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::NOT_FOUND
+    );
+    assert!(post("/secret/reject_papers")
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert!(post("/secret/reject_papers/restore")
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut restored = None;
+    while let Some(Ok(lazy)) = iter.next().await {
+        if lazy.id() == pid {
+            if let Ok(paper) = lazy.get().await {
+                restored = Some((paper.status, paper.rev));
+            }
+        }
+    }
+    assert_eq!(restored, Some((paper::Status::Pending, 2)));
+
+    // restored papers can be rejected again
+    assert!(post("/secret/reject_papers")
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    let select = state.papers.select(1, paper::Status::Rejected.as_dim());
+    let mut iter = select.iter();
+    let mut rejected = false;
+    while let Some(Ok(lazy)) = iter.next().await {
+        rejected |= lazy.id() == pid && lazy.get().await.is_ok();
+    }
+    assert!(rejected, "paper should be moved over its tombstone");
+}
+
+#[tokio::test]
+async fn signed_permalink() {
+    let (state, route) =
+        router_with(|config| config.permalink_key = Some("permalink key of the test".to_owned()));
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.status = paper::Status::Approved;
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let mint = |ttl_secs: Option<u64>| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri("/secret/get_papers/sign")
+                        .method(http::Method::POST)
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .body(
+                            serde_json::to_string(&crate::sign::SignReq { pid, ttl_secs }).unwrap(),
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<crate::sign::SignRes>(&body).unwrap()
+        }
+    };
+    let get = |url: String| {
+        route
+            .clone()
+            .oneshot(Request::builder().uri(url).body(Body::empty()).unwrap())
+    };
+
+    let signed = mint(None).await;
+    assert!(signed.expires.is_none());
+    let res = get(signed.url.clone()).await.unwrap();
+    assert!(res.status().is_success());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(
+        serde_json::from_slice::<paper::Out>(&body).unwrap().pid,
+        pid
+    );
+    // approved papers are not served by unsigned pids
+    assert_eq!(
+        get(format!("/paper/get/{pid}")).await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    // tampered signatures and pids
+    let mut tampered = signed.url.clone();
+    let last = tampered.pop().unwrap();
+    tampered.push(if last == '0' { '1' } else { '0' });
+    assert_eq!(
+        get(tampered).await.unwrap().status(),
+        http::StatusCode::FORBIDDEN
+    );
+    let (_, sig) = signed.url.split_once('?').unwrap();
+    assert_eq!(
+        get(format!("/paper/signed/{}?{sig}", pid.0.wrapping_add(1)))
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        get(format!("/paper/signed/{pid}?sig=zz"))
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    let signed = mint(Some(3600)).await;
+    assert!(signed.expires.is_some());
+    assert!(get(signed.url.clone()).await.unwrap().status().is_success());
+    // extending the expiry invalidates the signature
+    let exp = signed.expires.unwrap().timestamp();
+    let extended = signed
+        .url
+        .replace(&exp.to_string(), &(exp + 3600).to_string());
+    assert_eq!(
+        get(extended).await.unwrap().status(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    // out of range ttls
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/sign")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&crate::sign::SignReq {
+                        pid,
+                        ttl_secs: Some(u64::MAX),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+
+    let signed = mint(Some(0)).await;
+    assert_eq!(
+        get(signed.url).await.unwrap().status(),
+        http::StatusCode::FORBIDDEN
+    );
+
+    // disabled without a key
+    let (_, route) = router();
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri(format!("/paper/signed/{pid}?sig=00"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn reject_by_content() {
+    let (state, route) = router();
+    for info in ["Genshine Impact", "Genshine Impact", "Honkai Impact"] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/reject_papers/by_content")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::ByContentReq {
+                        name: "Yjn024".to_owned(),
+                        info: "Genshine Impact".to_owned(),
+                        from: 0,
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: paper::ByContentRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.count, 2);
+    assert_eq!(res.failed, 0);
+    assert!(!res.scan.truncated);
+
+    for (status, count) in [(paper::Status::Pending, 1), (paper::Status::Rejected, 2)] {
+        let select = state.papers.select(1, status.as_dim());
+        let mut iter = select.iter();
+        let mut infos = vec![];
+        while let Some(Ok(lazy)) = iter.next().await {
+            if let Ok(paper) = lazy.get().await {
+                infos.push(paper.info.clone());
+            }
+        }
+        assert_eq!(infos.len(), count);
+        if status == paper::Status::Pending {
+            assert_eq!(infos, ["Honkai Impact"]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn by_content_max_scan() {
+    let (state, route) = router_with(|config| config.max_scan = Some(1));
+    let items_per_chunk = config().layout.papers_items_per_chunk();
+    for pid in [1, items_per_chunk * 3 + 1] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.pid = Pid(pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let approve = |from: u64| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri("/secret/approve_papers/by_content")
+                        .method(http::Method::POST)
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .body(
+                            serde_json::to_string(&paper::ByContentReq {
+                                name: "Yjn024".to_owned(),
+                                info: "Genshine Impact".to_owned(),
+                                from,
+                            })
+                            .unwrap(),
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let status = res.status();
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            (
+                status,
+                serde_json::from_slice::<paper::ByContentRes>(&body).ok(),
+            )
+        }
+    };
+
+    // scans stop at the cap, and continue from the next pid
+    let (status, res) = approve(0).await;
+    assert!(status.is_success());
+    let res = res.unwrap();
+    assert_eq!((res.count, res.failed), (1, 0));
+    assert!(res.scan.truncated);
+    let (status, res) = approve(res.scan.next.unwrap()).await;
+    assert!(status.is_success());
+    let res = res.unwrap();
+    assert_eq!((res.count, res.failed), (1, 0));
+    assert!(res.scan.truncated);
+    assert_eq!(
+        approve(res.scan.next.unwrap()).await.0,
+        http::StatusCode::NOT_FOUND
+    );
+
+    let select = paper::Status::Approved.select(&state.papers);
+    assert_eq!(select.iter().count().await, 2);
+    // truncated scans matching nothing are not missing papers
+    let (status, res) = approve(0).await;
+    assert!(status.is_success());
+    assert_eq!(res.unwrap().count, 0);
+}
+
+#[tokio::test]
+async fn approved_ticker() {
+    use futures_lite::StreamExt as _;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (state, route) = router();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(std::future::IntoFuture::into_future(axum::serve(
+        listener,
+        route.clone(),
+    )));
+
+    let new_paper = |info: &str| -> paper::Paper {
+        paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap()
+    };
+    let replayed = new_paper("Honkai Impact");
+    state.ticker.publish(replayed.to_out());
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws/approved?replay=5"))
+        .await
+        .unwrap();
+    let mut next_paper = async || loop {
+        match ws.next().await.unwrap().unwrap() {
+            Message::Text(text) => return serde_json::from_str::<paper::Out>(&text).unwrap(),
+            Message::Close(frame) => panic!("closed: {frame:?}"),
+            _ => {}
+        }
+    };
+    assert_eq!(next_paper().await.pid, replayed.pid);
+
+    let paper = new_paper("Genshine Impact");
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let approved = next_paper().await;
+    assert_eq!(approved.pid, pid);
+    assert_eq!(approved.info, "Genshine Impact");
+
+    // lagging clients are closed
+    for _ in 0..100 {
+        state.ticker.publish(approved.clone());
+    }
+    let mut closed = false;
+    while let Some(Ok(msg)) = ws.next().await {
+        if let Message::Close(frame) = msg {
+            closed = frame.is_some_and(|frame| frame.code == 1008.into());
+            break;
+        }
+    }
+    assert!(closed, "lagging client should be closed");
+}
+
+#[tokio::test]
+async fn max_scan() {
+    let (state, route) = router_with(|config| config.max_scan = Some(1));
+    let items_per_chunk = state.config.layout.papers_items_per_chunk();
+    for chunk in [0, 1, 5] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper in chunk {chunk}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.pid = Pid(chunk * items_per_chunk + 1);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let mut from = 0;
+    let mut requests = 0;
+    let mut infos = vec![];
+    loop {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/secret/get_papers/list?from={from}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        let res: paper::ListRes =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        requests += 1;
+        infos.extend(res.papers.into_iter().map(|paper| paper.info));
+        if !res.scan.truncated {
+            assert_eq!(res.scan.next, None);
+            break;
+        }
+        from = res.scan.next.unwrap();
+        assert_eq!(from % items_per_chunk, 0);
+    }
+    assert_eq!(requests, 4);
+    assert_eq!(
+        infos,
+        ["Paper in chunk 0", "Paper in chunk 1", "Paper in chunk 5"]
+    );
+}
+
+#[tokio::test]
+async fn reject_range() {
+    let (state, _) = router_with(|config| {
+        config.reject_range_confirm = Some("reject them".to_owned());
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        });
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state
+        .with_mailer(mailer.clone())
+        .with_templates(mail::Templates {
+            rejection: Some(mail::Template::parse("Sorry\nSorry, {name}.\n").unwrap()),
+            ..Default::default()
+        });
+    let route = crate::routes(&state).with_state(state.clone());
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    let now = chrono::Utc::now();
+    for (i, status) in [
+        paper::Status::Pending,
+        paper::Status::Pending,
+        paper::Status::Approved,
+        paper::Status::Pending,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {i}"),
+            email: (i == 0).then(|| email.clone()),
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        paper.time = now - chrono::Duration::hours(i as i64);
+        paper.received_at = paper.time;
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let reject = |confirm: &'static str, since: i64, until: i64| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(format!("/secret/reject_range?confirm={confirm}"))
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::RangeReq {
+                        since: now - chrono::Duration::minutes(since),
+                        until: now - chrono::Duration::minutes(until),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+    };
+    let rejected = |res: axum::response::Response| async move {
+        assert!(res.status().is_success());
+        let res: paper::RejectRangeRes =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(res.failed, 0);
+        res.rejected
+    };
+
+    assert_eq!(
+        reject("reject", 150, 30).await.unwrap().status(),
+        http::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        reject("reject%20them", 30, 150).await.unwrap().status(),
+        http::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        rejected(reject("reject%20them", 20, 10).await.unwrap()).await,
+        0
+    );
+    // approved papers in the range are kept
+    assert_eq!(
+        rejected(reject("reject%20them", 150, 30).await.unwrap()).await,
+        1
+    );
+    assert_eq!(
+        rejected(reject("reject%20them", 200, 0).await.unwrap()).await,
+        2
+    );
+
+    let select = state.papers.select(1, paper::Status::Rejected.as_dim());
+    let mut iter = select.iter();
+    let mut infos = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if let Ok(paper) = lazy.get().await {
+            infos.push(paper.info.clone());
+        }
+    }
+    infos.sort();
+    assert_eq!(infos, ["Paper 0", "Paper 1", "Paper 3"]);
+
+    // authors are notified the same as single rejections
+    let sent = mailer.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].envelope().to(), [email]);
+}
+
+#[tokio::test]
+async fn project_fields() {
+    let (state, route) = router();
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    let get = |uri: &'static str, accept: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header(http::header::ACCEPT, accept)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::Deserializer::from_slice(&body)
+                .into_iter::<serde_json::Value>()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        }
+    };
+    let keys = |paper: &serde_json::Value| {
+        let mut keys = paper
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    };
+
+    let res = get(
+        "/secret/get_papers/list?fields=pid,name,%20time,unknown",
+        "application/json",
+    )
+    .await;
+    assert_eq!(res[0]["total"], 1);
+    assert_eq!(res[0]["papers"][0]["pid"], pid.0);
+    assert_eq!(keys(&res[0]["papers"][0]), ["name", "pid", "time"]);
+
+    let res = get("/secret/get_papers?fields=pid,info", "application/json").await;
+    assert_eq!(keys(&res[0][0]), ["info", "pid"]);
+    let res = get("/secret/get_papers?fields=pid", "application/x-ndjson").await;
+    assert_eq!(keys(&res[0]), ["pid"]);
+
+    // all fields are responded if none is queried
+    let res = get("/secret/get_papers/list?fields=", "application/json").await;
+    assert!(keys(&res[0]["papers"][0]).contains(&"info".to_owned()));
+}
+
+#[test]
+fn open_log_file() {
+    let dir = std::env::temp_dir().join(format!("subboard-log-{}", fastrand::u64(..)));
+    let path = dir.join("nested").join("backend.log");
+    assert!(crate::open_log_file(&path).is_ok());
+    assert!(path.is_file());
+
+    // directories can't be opened as log files
+    assert!(crate::open_log_file(&dir).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn whoami() {
+    let (_, route) = router();
+    let whoami = |token: Option<&'static str>| {
+        let mut req = Request::builder().uri("/admin/whoami");
+        if let Some(token) = token {
+            req = req.header(http::header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        route.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    for (token, capability) in [
+        ("secret", "root"),
+        ("get_papers", "get_papers"),
+        ("approve_papers", "approve_papers"),
+        ("resolve_questions", "resolve_questions"),
+    ] {
+        let res = whoami(Some(token)).await.unwrap();
+        assert!(res.status().is_success());
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let res: crate::secret::WhoamiRes = serde_json::from_slice(&body).unwrap();
+        assert_eq!(res.capability, capability);
+    }
+
+    for token in [Some("secre"), Some("secrets"), Some(""), None] {
+        assert_eq!(
+            whoami(token).await.unwrap().status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+    }
+}
+
+#[tokio::test]
+async fn submission_window() {
+    let now = chrono::Utc::now();
+    let hour = chrono::Duration::hours(1);
+    let mut config = config();
+    assert!(config.check_submissions_open(now).is_ok());
+    config.submissions_open_from = Some(now - hour);
+    config.submissions_open_until = Some(now + hour);
+    assert!(config.check_submissions_open(now).is_ok());
+    assert_eq!(
+        config
+            .check_submissions_open(now - hour * 2)
+            .unwrap_err()
+            .reopen,
+        Some(now - hour)
+    );
+    assert_eq!(
+        config
+            .check_submissions_open(now + hour * 2)
+            .unwrap_err()
+            .reopen,
+        None
+    );
+
+    let (_, route) = router_with(|config| config.submissions_open_from = Some(now + hour));
+    let post = |uri: &'static str, body: String| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap(),
+        )
+    };
+    let paper = serde_json::to_string(&paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    })
+    .unwrap();
+    let question = serde_json::to_string(&question::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+    })
+    .unwrap();
+    for (uri, body) in [("/paper/post", paper), ("/questions/new", question)] {
+        let res = post(uri, body).await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "closed");
+        assert!(body["error"].as_str().unwrap().contains("reopening at"));
+    }
+
+    // reads are unaffected
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/paper/get")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn paper_histogram() {
+    let (state, route) = router();
+    for (i, (day, status)) in [
+        ("2024-01-01", paper::Status::Pending),
+        ("2024-01-01", paper::Status::Approved),
+        ("2024-01-02", paper::Status::Approved),
+        ("2024-01-09", paper::Status::Rejected),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {i}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        paper.time = format!("{day}T12:00:00Z").parse().unwrap();
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let histogram = |query: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/secret/get_papers/histogram?{query}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            if !res.status().is_success() {
+                return Err(res.status());
+            }
+            let res: paper::HistogramRes =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            assert!(!res.scan.truncated);
+            Ok(res
+                .buckets
+                .into_iter()
+                .map(|(date, count)| (date.to_string(), count))
+                .collect::<Vec<_>>())
+        }
+    };
+    let buckets = |buckets: &[(&str, usize)]| {
+        buckets
+            .iter()
+            .map(|&(date, count)| (date.to_owned(), count))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        histogram("").await.unwrap(),
+        buckets(&[("2024-01-01", 2), ("2024-01-02", 1), ("2024-01-09", 1)])
+    );
+    assert_eq!(
+        histogram("status=1").await.unwrap(),
+        buckets(&[("2024-01-01", 1), ("2024-01-02", 1)])
+    );
+    assert_eq!(
+        histogram("since=2024-01-02&until=2024-01-31")
+            .await
+            .unwrap(),
+        buckets(&[("2024-01-02", 1), ("2024-01-09", 1)])
+    );
+    // 2024-01-01 is a Monday
+    assert_eq!(
+        histogram("group_by=week").await.unwrap(),
+        buckets(&[("2024-01-01", 3), ("2024-01-08", 1)])
+    );
+    assert_eq!(
+        histogram("group_by=month").await.unwrap(),
+        buckets(&[("2024-01-01", 4)])
+    );
+    assert_eq!(
+        histogram("since=2024-01-02&until=2024-01-01").await,
+        Err(http::StatusCode::BAD_REQUEST)
+    );
+}
+
+#[tokio::test]
+async fn dedup_window() {
+    let post = |route: Router, info: &'static str| async move {
+        route
+            .oneshot(
+                Request::builder()
+                    .uri("/paper/post")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(
+                        serde_json::to_string(&paper::In {
+                            name: "Yjn024".to_owned(),
+                            info: info.to_owned(),
+                            email: None,
+                            color: "#ffc".to_owned(),
+                            image_url: None,
+                            submitted_at: None,
+                        })
+                        .unwrap(),
+                    )
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    };
+    let insert = |state: Global<MemStorage>, status, age_secs| async move {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        paper.time = chrono::Utc::now() - chrono::Duration::seconds(age_secs);
+        paper.received_at = paper.time;
+        state.papers.insert(paper).await.unwrap();
+    };
+
+    let (state, route) = router_with(|config| config.dedup_window_secs = 60);
+    insert(state.clone(), paper::Status::Approved, 30).await;
+    // surrounding whitespaces are not content
+    assert_eq!(
+        post(route.clone(), " Genshine Impact\n").await,
+        http::StatusCode::CONFLICT
+    );
+    assert!(post(route.clone(), "Honkai Impact").await.is_success());
+    // supplied times don't move duplicates out of the window
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::In {
+                        name: "Yjn024".to_owned(),
+                        info: "Honkai Impact".to_owned(),
+                        email: None,
+                        color: "#ffc".to_owned(),
+                        image_url: None,
+                        submitted_at: Some(chrono::Utc::now() - chrono::Duration::days(2)),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::CONFLICT);
+
+    // papers out of the window or rejected are not duplicated
+    let (state, route) = router_with(|config| config.dedup_window_secs = 60);
+    insert(state.clone(), paper::Status::Approved, 90).await;
+    insert(state.clone(), paper::Status::Rejected, 30).await;
+    assert!(post(route.clone(), "Genshine Impact").await.is_success());
+    assert_eq!(
+        post(route.clone(), "Genshine Impact").await,
+        http::StatusCode::CONFLICT
+    );
+
+    // posts after the first check are found regardless of the scan cap
+    let (state, route) = router_with(|config| {
+        config.dedup_window_secs = 60;
+        config.max_scan = Some(1);
+    });
+    insert(state.clone(), paper::Status::Approved, 90).await;
+    insert(state.clone(), paper::Status::Approved, 90).await;
+    assert!(post(route.clone(), "Honkai Impact").await.is_success());
+    assert!(post(route.clone(), "Genshine Impact").await.is_success());
+    assert_eq!(
+        post(route.clone(), "Genshine Impact").await,
+        http::StatusCode::CONFLICT
+    );
+    // rejected posts are not duplicated
+    for mut lazy in state
+        .papers
+        .select_all()
+        .iter()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .map(Result::unwrap)
+    {
+        let paper = lazy.get_mut().await.unwrap();
+        if paper.received_at > chrono::Utc::now() - chrono::Duration::seconds(60) {
+            paper.status = paper::Status::Rejected;
+        }
+        lazy.close().await.unwrap();
+    }
+    assert!(post(route.clone(), "Genshine Impact").await.is_success());
+
+    let (state, route) = router_with(|config| config.dedup_window_secs = 0);
+    insert(state.clone(), paper::Status::Pending, 0).await;
+    assert!(post(route.clone(), "Genshine Impact").await.is_success());
+    assert!(post(route.clone(), "Genshine Impact").await.is_success());
+
+    let (state, route) = router_with(|config| config.dedup_window_secs = u64::MAX);
+    insert(state.clone(), paper::Status::Pending, 10 * 365 * 86_400).await;
+    assert_eq!(
+        post(route.clone(), "Genshine Impact").await,
+        http::StatusCode::CONFLICT
+    );
+}
+
+#[tokio::test]
+async fn purge_all() {
+    let configure = |config: &mut Config| config.purge_all_confirm = Some("purge".to_owned());
+    let purge_all = |route: Router, confirm: &'static str| {
+        route.oneshot(
+            Request::builder()
+                .uri(format!("/secret/purge_all?confirm={confirm}"))
+                .method(http::Method::POST)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // disabled without the environment variable
+    let (_, route) = router_with(configure);
+    assert_eq!(
+        purge_all(route, "purge").await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+
+    std::env::set_var(mng::ALLOW_PURGE_ENV, "1");
+    let (state, route) = router_with(configure);
+    for status in [paper::Status::Pending, paper::Status::Approved] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+    state
+        .questions
+        .insert(
+            question::In {
+                name: "Yjn024".to_owned(),
+                info: "What is Genshine Impact?".to_owned(),
+                email: None,
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        purge_all(route.clone(), "purge%20all")
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::BAD_REQUEST
+    );
+    let res = purge_all(route.clone(), "purge").await.unwrap();
+    assert!(res.status().is_success());
+    let res: mng::PurgeAllRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!((res.papers, res.questions), (2, 1));
+
+    let select = state.papers.select_all();
+    assert_eq!(select.iter().filter(Result::is_ok).count().await, 0);
+    let select = state.questions.select_all();
+    assert_eq!(select.iter().filter(Result::is_ok).count().await, 0);
+
+    // disabled without configured confirmation even if allowed
+    let (_, route) = router();
+    assert_eq!(
+        purge_all(route, "").await.unwrap().status(),
+        http::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn list_papers_by_cursor() {
+    let (state, route) = router_with(|config| config.max_scan = Some(2));
+    let items_per_chunk = state.config.layout.papers_items_per_chunk();
+    // pids spread over chunks, with one rejected paper
+    for (pid, status) in [
+        (1, paper::Status::Pending),
+        (2, paper::Status::Rejected),
+        (3, paper::Status::Pending),
+        (items_per_chunk + 1, paper::Status::Pending),
+        (3 * items_per_chunk, paper::Status::Pending),
+    ] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {pid}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.pid = Pid(pid);
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let mut cursor = String::new();
+    let mut pages = vec![];
+    loop {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/secret/get_papers/list?status=0&limit=1&after={cursor}"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        let res: paper::ListRes =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        pages.push(
+            res.papers
+                .into_iter()
+                .map(|paper| paper.pid.0)
+                .collect::<Vec<_>>(),
+        );
+        let Some(next) = res.next_cursor else {
+            break;
+        };
+        cursor = serde_json::to_value(next)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_owned();
+    }
+    // the scan limit is reached with the last paper, so it is not
+    // known to be the last, which leaves an empty page
+    assert_eq!(
+        pages,
+        [
+            vec![1],
+            vec![3],
+            vec![items_per_chunk + 1],
+            vec![3 * items_per_chunk],
+            vec![]
+        ]
+    );
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/list?after=not-a-cursor")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn pending_alert() {
+    let (state, _) = router_with(|config| {
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        });
+        config.admin_alert_email = Some("admin@example.com".parse().unwrap());
+        config.alert_pending_threshold = 1;
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+    let insert = || async {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        state.papers.insert(paper).await.unwrap();
+    };
+
+    let mut alert = alert::PendingAlert::default();
+    insert().await;
+    assert!(!alert::check(&state, &mut alert).await);
+    insert().await;
+    assert!(alert::check(&state, &mut alert).await);
+    // not alerted again while the queue stays over the threshold
+    insert().await;
+    assert!(!alert::check(&state, &mut alert).await);
+
+    let sent = mailer.sent.lock().unwrap().clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(
+        sent[0].envelope().to(),
+        ["admin@example.com".parse::<lettre::Address>().unwrap()]
+    );
+
+    // alerted again once the queue drops and re-crosses the threshold
+    let cooldown = std::time::Duration::from_secs(3600);
+    assert!(!alert.hit(1, 1, cooldown));
+    assert!(alert.hit(2, 1, cooldown));
+    assert!(!alert.hit(2, 1, cooldown));
+    // or after the cooldown
+    assert!(alert.hit(2, 1, std::time::Duration::ZERO));
+}
+
+#[test]
+fn paper_out_ref() {
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.views = 42;
+    assert_eq!(
+        serde_json::to_value(paper.to_out_ref()).unwrap(),
+        serde_json::to_value(paper.to_out()).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn get_weighted_paper() {
+    let (state, route) = router_with(|config| config.weighted_half_life_secs = 3600);
+    let mut pids = vec![];
+    // weights are 1 and 1/4 of two half-lives
+    for age_hours in [0, 2] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper of {age_hours} hours ago"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = paper::Status::Approved;
+        paper.time = chrono::Utc::now() - chrono::Duration::hours(age_hours);
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let get = |query: String| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/paper/get?{query}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            if !res.status().is_success() {
+                return Err(res.status());
+            }
+            let paper: paper::Out =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            Ok(paper.pid)
+        }
+    };
+
+    fastrand::seed(7);
+    let mut recent = 0;
+    for _ in 0..1000 {
+        if get("weighted=true".to_owned()).await.unwrap() == pids[0] {
+            recent += 1;
+        }
+    }
+    // expected to be 800
+    assert!((750..=850).contains(&recent), "{recent} of 1000 are recent");
+
+    let mut recent = 0;
+    for _ in 0..1000 {
+        if get(String::new()).await.unwrap() == pids[0] {
+            recent += 1;
+        }
+    }
+    assert!((450..=550).contains(&recent), "{recent} of 1000 are recent");
+
+    // the excluded paper is never responded
+    for _ in 0..20 {
+        assert_eq!(
+            get(format!("weighted=true&exclude={}", pids[0])).await,
+            Ok(pids[1])
+        );
+    }
+}
+
+#[tokio::test]
+async fn paper_image_url() {
+    use dmds::Data;
+
+    let (state, route) = router_with(|config| {
+        config.allowed_image_hosts = vec!["Example.com".to_owned()];
+    });
+    let post = |image_url: &str| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::In {
+                        name: "Yjn024".to_owned(),
+                        info: format!("Look at {image_url}"),
+                        email: None,
+                        color: "#ffc".to_owned(),
+                        image_url: Some(image_url.to_owned()),
+                        submitted_at: None,
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+    };
+
+    for (url, code) in [
+        ("not a url", "invalid_image_url"),
+        ("ftp://example.com/1.png", "invalid_image_url"),
+        ("/1.png", "invalid_image_url"),
+        ("https://evil.com/1.png", "disallowed_image_host"),
+        ("https://notexample.com/1.png", "disallowed_image_host"),
+        ("https://example.com@evil.com/1.png", "invalid_image_url"),
+    ] {
+        let res = post(url).await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST, "{url}");
+        let body: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], code, "{url}");
+    }
+
+    for url in ["https://example.com/1.png", "http://img.EXAMPLE.com/2.png"] {
+        assert!(post(url).await.unwrap().status().is_success(), "{url}");
+    }
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    let mut urls = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        urls.extend(lazy.get().await.unwrap().image_url.clone());
+    }
+    urls.sort();
+    assert_eq!(
+        urls,
+        ["http://img.EXAMPLE.com/2.png", "https://example.com/1.png"]
+    );
+
+    // papers of version 6 have no image
+    let mut buf = vec![];
+    bincode::Options::serialize_into(
+        crate::bincode_options(),
+        &mut buf,
+        &(
+            "Yjn024",
+            "Hello, world!",
+            None::<lettre::Address>,
+            chrono::Utc::now(),
+            "#ffc",
+            None::<std::net::IpAddr>,
+            2u64,
+            3u64,
+            true,
+        ),
+    )
+    .unwrap();
+    let paper = paper::Paper::decode(6, &[1, paper::Status::Pending.as_dim()], &buf[..]).unwrap();
+    assert!(paper.priority);
+    assert_eq!(paper.image_url, None);
+}
+
+#[tokio::test]
+async fn approve_failed_reads() {
+    let config = Arc::new(config());
+    let items_per_chunk = config.layout.papers_items_per_chunk();
+    let storage = Arc::new(FaultyHandle::<MemStorage>::default());
+    let world = || {
+        dmds::world! {
+            storage.clone(),
+            items_per_chunk => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        }
+    };
+
+    // stores papers, which are not buffered by the world of the state
+    let stored = world();
+    for (pid, status) in [
+        (1, paper::Status::Approved),
+        (2, paper::Status::Pending),
+        (items_per_chunk + 1, paper::Status::Pending),
+    ] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Paper {pid}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config)
+        .unwrap();
+        paper.pid = Pid(pid);
+        paper.status = status;
+        stored.insert(paper).await.unwrap();
+    }
+    for pos in [
+        [0, paper::Status::Approved.as_dim() as usize],
+        [0, paper::Status::Pending.as_dim() as usize],
+        [1, paper::Status::Pending.as_dim() as usize],
+    ] {
+        let chunk = stored.chunk_buf_of_pos(pos).unwrap();
+        storage.inner.flush_chunk(&chunk).await.unwrap();
+    }
+    let state = Global::new(
+        config.clone(),
+        world(),
+        dmds::world! {
+            Arc::new(FaultyHandle::<MemStorage>::default()),
+            config.layout.questions_items_per_chunk() => ..=u64::MAX,
+        },
+        dmds::world! {
+            Arc::new(FaultyHandle::<MemStorage>::default()),
+            crate::audit::ITEMS_PER_CHUNK => ..=u64::MAX,
+        },
+    );
+    let approve = |pid: u64| {
+        paper::approve(
+            axum::extract::State(state.clone()),
+            crate::secret::Actor::PaperApprover,
+            crate::json::Json(paper::ApprRejReq {
+                pid: Pid(pid),
+                rev: None,
+            }),
+        )
+    };
+    let approved = || async {
+        let select = paper::Status::Approved.select(&state.papers);
+        let mut iter = select.iter();
+        let mut pids = vec![];
+        while let Some(Ok(lazy)) = iter.next().await {
+            if let Ok(paper) = lazy.get().await {
+                pids.push(paper.pid.0);
+            }
+        }
+        pids.sort();
+        pids
+    };
+
+    // a failed read is retried, and the stored approved paper is kept
+    storage
+        .reads
+        .fail_chunk([0, paper::Status::Approved.as_dim() as usize], 1);
+    approve(2).await.unwrap();
+    assert_eq!(approved().await, [1, 2]);
+
+    // the paper is left pending if reads keep failing
+    storage
+        .reads
+        .fail_chunk([1, paper::Status::Approved.as_dim() as usize], 2);
+    let err = approve(items_per_chunk + 1).await.unwrap_err();
+    assert_eq!(err.code(), "unsaved");
+    assert_eq!(
+        axum::response::IntoResponse::into_response(err).status(),
+        http::StatusCode::SERVICE_UNAVAILABLE
+    );
+    {
+        let select = Pid(items_per_chunk + 1).select(&state.papers);
+        let mut iter = select.iter();
+        let lazy = iter.next().await.unwrap().unwrap();
+        let paper = lazy.get().await.unwrap();
+        assert_eq!(paper.status, paper::Status::Pending);
+        assert_eq!(paper.rev, 0);
+    }
+
+    approve(items_per_chunk + 1).await.unwrap();
+    assert_eq!(approved().await, [1, 2, items_per_chunk + 1]);
+}
+
+#[tokio::test]
+async fn audit_log() {
+    let (state, route) = router();
+    let mut pids = vec![];
+    for info in ["Genshine Impact", "Honkai Impact"] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let post = |uri: &'static str, pid: Pid| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+    let query = |query: String| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/secret/audit?{query}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let res: audit::AuditRes =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            res.entries
+                .into_iter()
+                .map(|entry| (entry.action, entry.pid, entry.actor))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let start = chrono::Utc::now();
+    assert!(post("/secret/approve_papers", pids[0])
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert!(post("/secret/reject_papers", pids[1])
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    let middle = chrono::Utc::now();
+    assert!(post("/secret/reject_papers/restore", pids[1])
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    // failed actions are not recorded
+    assert!(!post("/secret/reject_papers", pids[0])
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+
+    assert_eq!(
+        query(String::new()).await,
+        [
+            (audit::Action::Approve, pids[0], "approve_papers".to_owned()),
+            (audit::Action::Reject, pids[1], "reject_papers".to_owned()),
+            (audit::Action::Restore, pids[1], "reject_papers".to_owned()),
+        ]
+    );
+    assert_eq!(
+        query(format!("pid={}", pids[1])).await,
+        [
+            (audit::Action::Reject, pids[1], "reject_papers".to_owned()),
+            (audit::Action::Restore, pids[1], "reject_papers".to_owned()),
+        ]
+    );
+    let time = |time: chrono::DateTime<chrono::Utc>| {
+        time.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+            .replace('+', "%2B")
+    };
+    assert_eq!(
+        query(format!("since={}&until={}", time(start), time(middle))).await,
+        [
+            (audit::Action::Approve, pids[0], "approve_papers".to_owned()),
+            (audit::Action::Reject, pids[1], "reject_papers".to_owned()),
+        ]
+    );
+    assert_eq!(query(format!("since={}", time(middle))).await.len(), 1);
+}
+
+#[tokio::test]
+async fn pid_as_string() {
+    // beyond the safe integers of JavaScript
+    let large = Pid((1 << 53) + 1);
+
+    assert_eq!(serde_json::to_string(&large).unwrap(), "9007199254740993");
+    let json = PidFormat::String.sync_scope(|| serde_json::to_string(&large).unwrap());
+    assert_eq!(json, "\"9007199254740993\"");
+    assert_eq!(serde_json::from_str::<Pid>(&json).unwrap(), large);
+    assert_eq!(
+        serde_json::from_str::<Pid>("9007199254740993").unwrap(),
+        large
+    );
+    assert!(serde_json::from_str::<Pid>("\"0x10\"").is_err());
+    assert!(serde_json::from_str::<Pid>("-1").is_err());
+    // binary formats are unaffected
+    let bytes = PidFormat::String
+        .sync_scope(|| bincode::Options::serialize(crate::bincode_options(), &large).unwrap());
+    assert_eq!(bytes, large.0.to_le_bytes());
+
+    let (state, route) = router_with(|config| config.pid_format = PidFormat::String);
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    paper.pid = large;
+    state.papers.insert(paper).await.unwrap();
+
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res[0]["pid"], "9007199254740993");
+
+    // approving by the pid as a string targets the exact paper
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(format!(r#"{{"pid":{}}}"#, res[0]["pid"]))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri(format!("/paper/get/{large}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res["pid"], "9007199254740993");
+}
+
+#[tokio::test]
+async fn get_many_papers() {
+    let (state, route) = router();
+    let items_per_chunk = state.config.layout.papers_items_per_chunk();
+    // two papers sharing a chunk, and one in another chunk
+    let pids = [1, 2, items_per_chunk + 1].map(Pid);
+    for (pid, status) in pids.into_iter().zip([
+        paper::Status::Pending,
+        paper::Status::Approved,
+        paper::Status::Rejected,
+    ]) {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Genshine Impact {pid}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.pid = pid;
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    let get_many = |pids: Vec<Pid>| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/get_papers/many")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::GetManyReq { pids }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    let missing = [Pid(3), Pid(items_per_chunk * 2)];
+    let res = get_many(vec![
+        pids[2], missing[0], pids[0], pids[2], missing[1], pids[1],
+    ])
+    .await
+    .unwrap();
+    assert!(res.status().is_success());
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let papers = res["papers"].as_object().unwrap();
+    assert_eq!(
+        papers.keys().map(String::as_str).collect::<Vec<_>>(),
+        pids.map(|pid| pid.to_string())
+    );
+    for (pid, paper) in papers {
+        assert_eq!(paper["pid"].to_string(), *pid);
+        assert_eq!(paper["info"], format!("Genshine Impact {pid}"));
+    }
+    assert_eq!(
+        papers[&pids[2].to_string()]["status"],
+        serde_json::to_value(paper::Status::Rejected).unwrap()
+    );
+
+    // duplicates count once against the cap
+    assert!(get_many(vec![Pid(1); paper::MAX_GET_MANY + 1])
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    assert_eq!(
+        get_many((0..=paper::MAX_GET_MANY as u64).map(Pid).collect())
+            .await
+            .unwrap()
+            .status(),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn management_nonce() {
+    let (state, route) = router_with(|config| {
+        config.nonce_ttl_secs = Some(600);
+        config.nonce_capacity = 2;
+    });
+    let mut pids = vec![];
+    for info in ["Genshine Impact", "Honkai Impact", "Zenless Zone Zero"] {
+        let paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let approve = |pid: Pid, nonce: Option<&str>| {
+        let mut req = Request::builder()
+            .uri("/secret/approve_papers")
+            .method(http::Method::POST)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+        if let Some(nonce) = nonce {
+            req = req.header("x-nonce", nonce);
+        }
+        route.clone().oneshot(
+            req.body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(
+        approve(pids[0], None).await.unwrap().status(),
+        http::StatusCode::BAD_REQUEST
+    );
+    assert!(approve(pids[0], Some("n1"))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    // replaying the approval is rejected, even for another paper
+    assert_eq!(
+        approve(pids[0], Some("n1")).await.unwrap().status(),
+        http::StatusCode::CONFLICT
+    );
+    assert_eq!(
+        approve(pids[1], Some("n1")).await.unwrap().status(),
+        http::StatusCode::CONFLICT
+    );
+    assert!(approve(pids[1], Some("n2"))
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    // the store is full until older nonces expire
+    assert_eq!(
+        approve(pids[2], Some("n3")).await.unwrap().status(),
+        http::StatusCode::SERVICE_UNAVAILABLE
+    );
+
+    // reads need no nonce
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/get_papers")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+}
+
+#[test]
+fn nonce_expiry() {
+    let nonces = crate::nonce::Nonces::default();
+    let ttl = std::time::Duration::from_millis(20);
+    nonces.hit("n1", ttl, 1).unwrap();
+    assert!(nonces.hit("n1", ttl, 1).is_err());
+    assert!(nonces.hit("n2", ttl, 1).is_err());
+    std::thread::sleep(ttl);
+    nonces.hit("n2", ttl, 1).unwrap();
+    nonces
+        .hit("n1", std::time::Duration::from_secs(60), 2)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn diag() {
+    let (state, route) = router_with(|config| config.count_views = true);
+    for status in [paper::Status::Pending, paper::Status::Approved] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        state.papers.insert(paper).await.unwrap();
+    }
+    state
+        .questions
+        .insert(
+            question::In {
+                name: "Yjn024".to_owned(),
+                info: "What is Genshine Impact?".to_owned(),
+                email: None,
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+    audit::record(
+        &state.audit,
+        audit::Action::Approve,
+        Pid(1),
+        crate::secret::Actor::Root,
+    )
+    .await;
+    state
+        .read_only
+        .store(true, std::sync::atomic::Ordering::Release);
+
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/diag")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: mng::DiagRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(
+        res.papers
+            .iter()
+            .map(|count| (count.status, count.count))
+            .collect::<Vec<_>>(),
+        [
+            (paper::Status::Pending, 1),
+            (paper::Status::Approved, 1),
+            (paper::Status::Rejected, 0)
+        ]
+    );
+    assert_eq!((res.questions, res.audit_entries), (1, 1));
+    assert_eq!(res.flush_intervals.views, Some(60));
+    assert!(res.read_only);
+}
+
+#[tokio::test]
+async fn paper_submitted_at() {
+    let (state, route) = router_with(|config| config.max_submitted_age_secs = 3600);
+    let post = |uri: &'static str, info: &str, submitted_at: chrono::DateTime<chrono::Utc>| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::In {
+                        name: "Yjn024".to_owned(),
+                        info: info.to_owned(),
+                        email: None,
+                        color: "#ffc".to_owned(),
+                        image_url: None,
+                        submitted_at: Some(submitted_at),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+    };
+    let code = |res: http::Response<Body>| async {
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+        let res: serde_json::Value =
+            serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        res["code"].as_str().unwrap().to_owned()
+    };
+
+    let now = chrono::Utc::now();
+    let written = now - chrono::Duration::minutes(30);
+    assert!(post("/paper/post", "Genshine Impact", written)
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    for (uri, time) in [
+        ("/paper/post", now + chrono::Duration::minutes(5)),
+        ("/paper/post", now - chrono::Duration::hours(2)),
+        ("/paper/validate", now - chrono::Duration::hours(2)),
+    ] {
+        assert_eq!(
+            code(post(uri, "Honkai Impact", time).await.unwrap()).await,
+            "invalid_submitted_at"
+        );
+    }
+
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut times = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        times.push(lazy.get().await.unwrap().time);
+    }
+    assert_eq!(times, [written]);
+}
+
+#[tokio::test]
+async fn author_approve_interval() {
+    let (state, route) = router_with(|config| config.author_approve_interval_secs = 3600);
+    let mut pids = vec![];
+    for (name, info) in [
+        ("Yjn024", "Genshine Impact"),
+        ("Yjn024", "Honkai Impact"),
+        ("JieXu", "Zenless Zone Zero"),
+    ] {
+        let paper = paper::In {
+            name: name.to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let approve = |pid: Pid| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+
+    assert!(approve(pids[0]).await.unwrap().status().is_success());
+    let res = approve(pids[1]).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::CONFLICT);
+    let retry_after: u64 = res.headers()[http::header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((3500..=3600).contains(&retry_after), "{retry_after}");
+    let res: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res["code"], "author_cooldown");
+
+    // other authors are unaffected
+    assert!(approve(pids[2]).await.unwrap().status().is_success());
+
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut pending = false;
+    while let Some(Ok(lazy)) = iter.next().await {
+        if let Ok(paper) = lazy.get().await {
+            pending |= paper.pid == pids[1] && paper.status == paper::Status::Pending;
+        }
+    }
+    assert!(pending, "blocked paper should stay pending");
+
+    // bulk approvals are checked per paper, including each other
+    let (state, route) = router_with(|config| {
+        config.author_approve_interval_secs = 3600;
+        config.approve_all_confirm = Some("all".to_owned());
+    });
+    for (name, info) in [
+        ("Yjn024", "Genshine Impact"),
+        ("Yjn024", "Honkai Impact"),
+        ("JieXu", "Zenless Zone Zero"),
+    ] {
+        let paper = paper::In {
+            name: name.to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        state.papers.insert(paper).await.unwrap();
+    }
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_all?confirm=all")
+                .method(http::Method::POST)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res: paper::ApproveAllRes =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(res.approved, 2);
+    assert_eq!(res.failed, 1);
+    let select = state.papers.select(1, paper::Status::Pending.as_dim());
+    let mut iter = select.iter();
+    let mut pending = vec![];
+    while let Some(Ok(lazy)) = iter.next().await {
+        if let Ok(paper) = lazy.get().await {
+            if paper.status == paper::Status::Pending {
+                pending.push(paper.name.clone());
+            }
+        }
+    }
+    assert_eq!(pending, ["Yjn024"]);
+}
+
+#[tokio::test]
+async fn server_protocols() {
+    use hyper::client::conn::{http1, http2};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+
+    async fn spawn(config: Config) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().route(
+            "/",
+            axum::routing::get(
+                |axum::extract::ConnectInfo(remote): axum::extract::ConnectInfo<
+                    std::net::SocketAddr,
+                >| async move { remote.ip().to_string() },
+            ),
+        );
+        tokio::spawn(async move {
+            crate::server::serve(listener, router, &config, std::future::pending()).await
+        });
+        addr
+    }
+
+    fn req() -> Request<http_body_util::Empty<bytes::Bytes>> {
+        Request::get("/")
+            .body(http_body_util::Empty::new())
+            .unwrap()
+    }
+
+    let addr = spawn(config()).await;
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut sender, conn) = http1::handshake(TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(conn);
+    let res = sender.send_request(req()).await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        &res.into_body().collect().await.unwrap().to_bytes()[..],
+        b"127.0.0.1"
+    );
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+    let res = sender.send_request(req()).await.unwrap();
+    assert_eq!(res.version(), http::Version::HTTP_2);
+    assert!(res.status().is_success());
+
+    let addr = spawn(Config {
+        http2: false,
+        max_connections: Some(1),
+        ..config()
+    })
+    .await;
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(conn);
+    assert!(sender.send_request(req()).await.is_err());
+
+    // the only slot is held by the first connection until it closes
+    let first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut first_sender, first_conn) = http1::handshake(TokioIo::new(first)).await.unwrap();
+    let first_conn = tokio::spawn(first_conn);
+    assert!(first_sender
+        .send_request(req())
+        .await
+        .unwrap()
+        .status()
+        .is_success());
+    let second = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (mut second_sender, second_conn) = http1::handshake(TokioIo::new(second)).await.unwrap();
+    tokio::spawn(second_conn);
+    let second_res = tokio::spawn(async move { second_sender.send_request(req()).await });
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(!second_res.is_finished());
+    drop(first_sender);
+    let _ = first_conn.await;
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), second_res)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(res.status().is_success());
+}
+
+#[tokio::test]
+async fn preview_approval_email() {
+    let (state, route) = router_with(|config| config.board_title = Some("Subboard".to_owned()));
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: Some(email.clone()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    let pid = paper.pid;
+    state.papers.insert(paper).await.unwrap();
+
+    async fn preview(route: &Router, query: &str) -> (http::StatusCode, serde_json::Value) {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/secret/approve_papers/preview_email?{query}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    let (status, body) = preview(&route, &format!("pid={pid}")).await;
+    assert!(status.is_success());
+    let res: paper::PreviewEmailRes = serde_json::from_value(body).unwrap();
+    assert_eq!(res.to, Some(email));
+    assert!(res.body.contains("Yjn024"));
+    assert!(res.subject.contains("Subboard"));
+
+    let (status, body) = preview(&route, "name=Someone").await;
+    assert!(status.is_success());
+    let res: paper::PreviewEmailRes = serde_json::from_value(body).unwrap();
+    assert_eq!(res.to, None);
+    assert!(res.body.contains("Someone"));
+
+    let (status, _) = preview(&route, &format!("pid={}", pid.0 + 1)).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+    // nothing is sent or approved by previews
+    assert!(mailer.sent.lock().unwrap().is_empty());
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        assert_eq!(lazy.get().await.unwrap().status, paper::Status::Pending);
+    }
+}
+
+#[test]
+fn email_templates() {
+    let paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+
+    let template = mail::Template::parse("{name} on {board}\n{{{info}}} #{pid}\n").unwrap();
+    assert_eq!(
+        template.render(&paper, &config()),
+        (
+            "Yjn024 on the board".to_owned(),
+            format!("{{Genshine Impact}} #{}\n", paper.pid)
+        )
+    );
+    let (subject, body) = mail::Templates::default()
+        .approval
+        .render(&paper, &config());
+    assert!(subject.contains("approved"));
+    assert!(body.contains("Yjn024"));
+
+    for src in ["{nmae}\nbody", "subject\n{name", "subject\n}", "\nbody"] {
+        assert!(mail::Template::parse(src).is_err(), "{src:?}");
+    }
+
+    let dir = std::env::temp_dir().join(format!("subboard-templates-{}", fastrand::u64(..)));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("approval.txt"), "Approved {pid}\nHi {name}\n").unwrap();
+    std::fs::write(dir.join("rejection.txt"), "Rejected\nHi {author}\n").unwrap();
+    let mut config = config();
+    config.approval_template_path = Some(dir.join("approval.txt"));
+    let templates = mail::Templates::load(&config).unwrap();
+    assert!(templates.rejection.is_none());
+    assert_eq!(templates.approval.render(&paper, &config).1, "Hi Yjn024\n");
+    config.rejection_template_path = Some(dir.join("rejection.txt"));
+    let err = mail::Templates::load(&config).unwrap_err();
+    assert!(err.contains("rejection_template_path"), "{err}");
+    assert!(err.contains("{author}"), "{err}");
+    config.rejection_template_path = Some(dir.join("missing.txt"));
+    assert!(mail::Templates::load(&config).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rejection_email() {
+    let (state, _) = router_with(|config| {
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        })
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    let mut pids = vec![];
+    for _ in 0..2 {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: Some(email.clone()),
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    async fn reject(route: &Router, pid: Pid) {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/secret/reject_papers")
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+    }
+
+    // no rejection email is sent without a template
+    reject(&crate::routes(&state).with_state(state.clone()), pids[0]).await;
+    assert!(mailer.sent.lock().unwrap().is_empty());
+
+    let state = state.with_templates(mail::Templates {
+        rejection: Some(mail::Template::parse("Sorry\nSorry, {name}.\n").unwrap()),
+        ..Default::default()
+    });
+    reject(&crate::routes(&state).with_state(state.clone()), pids[1]).await;
+    let sent = mailer.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].envelope().to(), [email]);
+    assert!(String::from_utf8_lossy(&sent[0].formatted()).contains("Sorry, Yjn024."));
+}
+
+#[tokio::test]
+async fn server_shutdown_drain() {
+    use hyper::client::conn::http1;
+    use hyper_util::rt::TokioIo;
+
+    let router = Router::new()
+        .route("/", axum::routing::get(|| async { "ok" }))
+        .route(
+            "/stream",
+            axum::routing::get(|| async {
+                Body::from_stream(futures_lite::stream::pending::<
+                    Result<bytes::Bytes, std::io::Error>,
+                >())
+            }),
+        );
+    let req = |uri| {
+        Request::get(uri)
+            .body(http_body_util::Empty::<bytes::Bytes>::new())
+            .unwrap()
+    };
+
+    for (stream, forced) in [(false, 0), (true, 1)] {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let config = Config {
+            shutdown_drain_secs: 1,
+            ..config()
+        };
+        let router = router.clone();
+        let server = tokio::spawn(async move {
+            crate::server::serve(listener, router, &config, async {
+                let _ = rx.await;
+            })
+            .await
+        });
+
+        // an idle keep-alive connection is closed right away
+        let conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) = http1::handshake(TokioIo::new(conn)).await.unwrap();
+        tokio::spawn(conn);
+        assert!(sender
+            .send_request(req("/"))
+            .await
+            .unwrap()
+            .status()
+            .is_success());
+        let mut res = None;
+        if stream {
+            res = Some(sender.send_request(req("/stream")).await.unwrap());
+        }
+
+        let start = std::time::Instant::now();
+        tx.send(()).unwrap();
+        assert_eq!(server.await.unwrap(), forced);
+        let elapsed = start.elapsed();
+        if stream {
+            assert!(elapsed >= std::time::Duration::from_secs(1), "{elapsed:?}");
+            // the stream is cut rather than completed
+            assert!(res.unwrap().into_body().collect().await.is_err());
+        } else {
+            assert!(elapsed < std::time::Duration::from_secs(1), "{elapsed:?}");
+        }
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn list_papers_boundaries() {
+    let (state, route) = router();
+    let pids = seed_papers(&state, 120, 0.5).await;
+    assert_eq!(pids, seed_papers(&router().0, 120, 0.5).await);
+
+    async fn list(route: &Router, query: &str) -> paper::ListRes {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/secret/get_papers/list?{query}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap()
+    }
+
+    // pages by offset, ending with a partial and an empty page
+    let mut listed = vec![];
+    for (offset, len) in [(0, 50), (50, 50), (100, 20), (120, 0)] {
+        let res = list(&route, &format!("limit=50&offset={offset}")).await;
+        assert_eq!(res.total, 120);
+        assert_eq!(res.papers.len(), len, "offset {offset}");
+        listed.extend(res.papers);
+    }
+    let mut all: Vec<_> = listed.iter().map(|paper| paper.pid).collect();
+    all.sort_unstable();
+    let mut expected = pids.clone();
+    expected.sort_unstable();
+    assert_eq!(all, expected);
+
+    // pages by cursor, with approved papers filling the last page exactly
+    let status = serde_json::to_value(paper::Status::Approved).unwrap();
+    let mut cursor = String::new();
+    let mut pages = vec![];
+    loop {
+        let res = list(&route, &format!("status={status}&limit=20&after={cursor}")).await;
+        pages.push(res.papers.iter().map(|paper| paper.pid).collect::<Vec<_>>());
+        let Some(next) = res.next_cursor else {
+            break;
+        };
+        cursor = serde_json::to_value(next)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_owned();
+    }
+    assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [20, 20, 20]);
+    let approved: Vec<_> = pages.concat();
+    assert!(approved.is_sorted());
+    assert!(approved.iter().all(|pid| pids.contains(pid)));
+}
+
+#[tokio::test]
+async fn status_scan_skips_other_statuses() {
+    let (state, _) = router();
+    let pids = seed_papers(&state, 1000, 0.99).await;
+
+    let mut approved = 0;
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        approved += usize::from(lazy.get().await.unwrap().status == paper::Status::Approved);
+    }
+    assert_eq!(approved, 990);
+
+    // only the pending papers are visited, without reading approved chunks
+    let mut visited = vec![];
+    let select = paper::Status::Pending.select(&state.papers);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        let paper = lazy.get().await.unwrap();
+        assert_eq!(paper.status, paper::Status::Pending);
+        visited.push(paper.pid);
+    }
+    assert_eq!(visited.len(), 10);
+    assert!(visited.iter().all(|pid| pids.contains(pid)));
+}
+
+#[tokio::test]
+async fn paper_email_forms() {
+    let (state, route) = router();
+    let post = |email: serde_json::Value| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri("/paper/post")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::json!({
+                        "name": "Yjn024",
+                        "info": format!("Paper with email {email}"),
+                        "email": email,
+                        "color": "#ffc",
+                    })
+                    .to_string(),
+                )
+                .unwrap(),
+        )
+    };
+
+    for email in [
+        serde_json::json!("yjn024@example.com"),
+        serde_json::json!("Jane Doe <yjn024@example.com>"),
+        serde_json::json!("\"Doe, Jane\" <yjn024@example.com>"),
+        serde_json::json!(" yjn024@example.com "),
+        // blank emails are absent ones
+        serde_json::json!(""),
+        serde_json::Value::Null,
+    ] {
+        let res = post(email.clone()).await.unwrap();
+        assert!(res.status().is_success(), "{email}");
+    }
+
+    let res = post(serde_json::json!("Jane Doe <not an email>"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("Name <name@example.com>"), "{error}");
+
+    let mut emails = vec![];
+    let select = state.papers.select_all();
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        emails.push(lazy.get().await.unwrap().email.clone());
+    }
+    emails.sort();
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    assert_eq!(
+        emails,
+        [
+            None,
+            None,
+            Some(email.clone()),
+            Some(email.clone()),
+            Some(email.clone()),
+            Some(email)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn recent_papers() {
+    let (state, route) = router_with(|config| config.max_recent = 10);
+    let pids = seed_papers(&state, 40, 0.5).await;
+
+    async fn recent(route: &Router, query: &str) -> Vec<paper::Out> {
+        let res = route
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/paper/recent?{query}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap()
+    }
+
+    let papers = recent(&route, "limit=5").await;
+    assert_eq!(papers.len(), 5);
+    assert!(papers
+        .windows(2)
+        .all(|pair| pair[0].approved_at >= pair[1].approved_at));
+    assert!(papers.iter().all(|paper| paper.approved_at.is_some()));
+    // capped by the configured maximum
+    assert_eq!(recent(&route, "limit=100").await.len(), 10);
+
+    // approving an old pending paper makes it the latest
+    let mut pending = None;
+    let select = paper::Status::Pending.select(&state.papers);
+    let mut iter = select.iter();
+    while let Some(Ok(lazy)) = iter.next().await {
+        let paper = lazy.get().await.unwrap();
+        if pending.is_none_or(|(_, time)| paper.time < time) {
+            pending = Some((paper.pid, paper.time));
+        }
+    }
+    let (pid, _) = pending.unwrap();
+    assert!(pids.contains(&pid));
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let papers = recent(&route, "").await;
+    assert_eq!(papers.len(), 10);
+    assert_eq!(papers[0].pid, pid);
+    assert!(papers[0].approved_at.unwrap() > papers[1].approved_at.unwrap());
+}
+
+#[tokio::test]
+async fn paper_get_cache() {
+    let (state, route) = router_with(|config| config.paper_get_cache_secs = 60);
+    let new_paper = |info: &str, status| {
+        let mut paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: info.to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        paper.status = status;
+        paper
+    };
+    async fn get_pids(route: &Router) -> std::collections::HashSet<u64> {
+        let mut pids = std::collections::HashSet::new();
+        for _ in 0..20 {
+            let res = route
+                .clone()
+                .oneshot(Request::get("/paper/get").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let body: serde_json::Value =
+                serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes())
+                    .unwrap();
+            pids.insert(body["pid"].as_u64().unwrap());
+        }
+        pids
+    }
+
+    let first = new_paper("First approved paper", paper::Status::Approved);
+    let first_pid = first.pid;
+    state.papers.insert(first).await.unwrap();
+    assert_eq!(get_pids(&route).await, [first_pid.0].into());
+
+    // papers inserted behind the cache are unseen within the TTL
+    state
+        .papers
+        .insert(new_paper("Second approved paper", paper::Status::Approved))
+        .await
+        .unwrap();
+    assert_eq!(get_pids(&route).await, [first_pid.0].into());
+
+    // approvals drop the cache
+    let pending = new_paper("Pending paper", paper::Status::Pending);
+    let pending_pid = pending.pid;
+    state.papers.insert(pending).await.unwrap();
+    let res = route
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/secret/approve_papers")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&paper::ApprRejReq {
+                        pid: pending_pid,
+                        rev: None,
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let mut pids = std::collections::HashSet::new();
+    for _ in 0..10 {
+        pids.extend(get_pids(&route).await);
+    }
+    assert_eq!(pids.len(), 3);
+    assert!(pids.contains(&pending_pid.0));
+}
+
+#[tokio::test]
+async fn db_errors() {
+    use axum::response::IntoResponse;
+
+    for res in [
+        paper::Error::from(dmds::Error::ValueMoved).into_response(),
+        question::Error::from(dmds::Error::Io(std::io::Error::other("broken"))).into_response(),
+    ] {
+        assert_eq!(res.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "db");
+        // causes are logged only
+        assert!(!body.to_string().contains("broken"));
+    }
+}
+
+#[tokio::test]
+async fn author_edits() {
+    const KEY: &str = "edit key of the test";
+    let (state, route) = router_with(|config| config.edit_key = Some(KEY.to_owned()));
+    let send = |uri: &'static str, body: String| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(body)
+                .unwrap(),
+        )
+    };
+    let edit = |pid: Pid, token: &str, info: &str| {
+        send(
+            "/paper/edit",
+            serde_json::to_string(&crate::sign::EditReq {
+                pid,
+                token: token.to_owned(),
+                info: info.to_owned(),
+            })
+            .unwrap(),
+        )
+    };
+    let info_of = |pid: Pid| {
+        let state = state.clone();
+        async move {
+            let select = pid.select(&state.papers);
+            let mut iter = select.iter();
+            while let Some(Ok(lazy)) = iter.next().await {
+                if lazy.id() == pid.0 {
+                    // skips stale copies moved out on approval
+                    if let Ok(paper) = lazy.get().await {
+                        return paper.info.clone();
+                    }
+                }
+            }
+            panic!("paper {pid} not found")
+        }
+    };
+
+    let res = send(
+        "/paper/post",
+        serde_json::to_string(&paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impcat".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        })
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(res.status().is_success());
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let posted: paper::PostRes = serde_json::from_slice(&body).unwrap();
+    let pid = posted.pid;
+    let token = posted.edit_token.unwrap();
+    assert!(posted.edit_expires.unwrap() > chrono::Utc::now());
+
+    // in the window
+    let res = edit(pid, &token, "Genshine Impact").await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(info_of(pid).await, "Genshine Impact");
+
+    // edits are validated the same as posting
+    let res = edit(pid, &token, " ").await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+
+    // tampered and expired tokens
+    let (exp, sig) = token.split_once('.').unwrap();
+    let extended = format!("{}.{sig}", exp.parse::<i64>().unwrap() + 3600);
+    let res = edit(pid, &extended, "Honkai Impact").await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+    // tokens are bound to their papers
+    let res = edit(Pid(pid.0 ^ 1), &token, "Honkai Impact").await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+    let expired = crate::sign::edit_token(KEY, pid, chrono::Utc::now());
+    let res = edit(pid, &expired, "Honkai Impact").await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+    assert_eq!(info_of(pid).await, "Genshine Impact");
+
+    // after approval
+    let res = send(
+        "/secret/approve_papers",
+        serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(res.status().is_success());
+    let res = edit(pid, &token, "Honkai Impact").await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::CONFLICT);
+    assert_eq!(info_of(pid).await, "Genshine Impact");
+
+    // disabled without a key
+    let (_, route) = router();
+    let res = route
+        .oneshot(
+            Request::builder()
+                .uri("/paper/edit")
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(
+                    serde_json::to_string(&crate::sign::EditReq {
+                        pid,
+                        token,
+                        info: "Honkai Impact".to_owned(),
+                    })
+                    .unwrap(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn resend_email() {
+    let (state, _) = router_with(|config| {
+        config.smtp = Some(mail::Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: None,
+            username: "board".to_owned(),
+            password: "password".to_owned(),
+            from: "Board <board@example.com>".parse().unwrap(),
+        })
+    });
+    let mailer = Arc::new(StubMailer::default());
+    let state = state.with_mailer(mailer.clone());
+    let route = crate::routes(&state).with_state(state.clone());
+    let send = |uri: &'static str, pid: Pid| {
+        route.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap())
+                .unwrap(),
+        )
+    };
+    let resend = |pid: Pid| send("/secret/approve_papers/resend_email", pid);
+
+    let email: lettre::Address = "yjn024@example.com".parse().unwrap();
+    let mut pids = vec![];
+    for email in [Some(email.clone()), None] {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+
+    // pending papers have nothing to re-send
+    let res = resend(pids[0]).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+    for &pid in &pids {
+        let res = send("/secret/approve_papers", pid).await.unwrap();
+        assert!(res.status().is_success());
+    }
+    assert_eq!(mailer.sent.lock().unwrap().len(), 1);
+
+    let res = resend(pids[0]).await.unwrap();
+    assert!(res.status().is_success());
+    {
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].envelope().to(), [email]);
+        assert!(String::from_utf8_lossy(&sent[1].formatted()).contains("Yjn024"));
+    }
+
+    // re-sends of the same paper are rate-limited
+    assert_rate_limited(resend(pids[0]).await.unwrap()).await;
+    assert_eq!(mailer.sent.lock().unwrap().len(), 2);
+
+    // papers without emails or not found
+    let res = resend(pids[1]).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    let res = resend(Pid(pids[0].0 ^ 1)).await.unwrap();
+    assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn bearer_capabilities() {
+    let (state, route) = router();
+    let mut pids = vec![];
+    for _ in 0..2 {
+        let paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: "Genshine Impact".to_owned(),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config())
+        .unwrap();
+        pids.push(paper.pid);
+        state.papers.insert(paper).await.unwrap();
+    }
+    let send = |uri: &str, token: &str, pid: Option<Pid>| {
+        let req = Request::builder()
+            .uri(uri)
+            .header(http::header::AUTHORIZATION, format!("Bearer {token}"));
+        let req = match pid {
+            Some(pid) => req
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_string(&paper::ApprRejReq { pid, rev: None }).unwrap(),
+                )),
+            None => req.body(Body::empty()),
+        };
+        route.clone().oneshot(req.unwrap())
+    };
+
+    // the management secret implies all capabilities
+    let res = send("/admin/get_papers/list", "secret", None)
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res = send("/admin/approve_papers", "secret", Some(pids[0]))
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    let res = send("/admin/reject_papers", "secret", Some(pids[1]))
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    // capability secrets only do their own things
+    let res = send("/admin/get_papers/list?limit=1", "get_papers", None)
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    for (uri, token) in [
+        ("/admin/approve_papers", "reject_papers"),
+        ("/admin/reject_papers", "approve_papers"),
+        ("/admin/reject_papers/restore", "approve_papers"),
+        ("/admin/get_papers/list", "approve_papers"),
+        ("/admin/root/purge_all", "get_papers"),
+        ("/admin/approve_papers", "secrets"),
+    ] {
+        let res = send(uri, token, Some(pids[0])).await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED, "{uri}");
+    }
+
+    // root actions are audited as root
+    let res = send(
+        &format!("/admin/root/audit?pid={}", pids[0]),
+        "secret",
+        None,
+    )
+    .await
+    .unwrap();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let res: audit::AuditRes = serde_json::from_slice(&body).unwrap();
+    assert_eq!(res.entries.len(), 1);
+    assert_eq!(res.entries[0].actor, "root");
+}
+
+#[tokio::test]
+async fn list_previews() {
+    let (state, route) = router();
+    let info = "小纸条".repeat(100);
+    let paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: info.clone(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config())
+    .unwrap();
+    state.papers.insert(paper).await.unwrap();
+
+    let get = |uri: &'static str| {
+        let route = route.clone();
+        async move {
+            let res = route
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    let preview_len = Config::default_list_preview_len();
+    for res in [
+        get("/secret/get_papers/list").await["papers"][0].take(),
+        get("/secret/get_papers").await[0].take(),
+    ] {
+        let preview = res["info"].as_str().unwrap();
+        assert_eq!(preview.chars().count(), preview_len + 1);
+        assert!(preview.ends_with('…'));
+        assert!(info.starts_with(preview.trim_end_matches('…')));
+        assert_eq!(res["truncated"], true);
+    }
+
+    for res in [
+        get("/secret/get_papers/list?full=true").await["papers"][0].take(),
+        get("/secret/get_papers?full=true").await[0].take(),
+    ] {
+        assert_eq!(res["info"], info.as_str());
+        assert_eq!(res["truncated"], false);
+    }
+}
+
+#[tokio::test]
+async fn injected_faults() {
+    // posts read only chunks they are inserted to without deduplication
+    let config = Arc::new(Config {
+        dedup_window_secs: 0,
+        email_cooldown_secs: 60,
+        ..config()
+    });
+    let items_per_chunk = config.layout.papers_items_per_chunk();
+    let storage = Arc::new(FaultyHandle::<MemStorage>::default());
+    let world = || {
+        dmds::world! {
+            storage.clone(),
+            items_per_chunk => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        }
+    };
+
+    // stores papers, which are not buffered by the world of the state
+    let stored = world();
+    for (pid, status) in [(1, paper::Status::Approved), (2, paper::Status::Pending)] {
+        let mut paper: paper::Paper = paper::In {
+            name: "Yjn024".to_owned(),
+            info: format!("Genshine Impact {pid}"),
+            email: None,
+            color: "#ffc".to_owned(),
+            image_url: None,
+            submitted_at: None,
+        }
+        .into_paper(&config)
+        .unwrap();
+        paper.pid = Pid(pid);
+        paper.status = status;
+        stored.insert(paper).await.unwrap();
+        let chunk = stored
+            .chunk_buf_of_pos([0, status.as_dim() as usize])
+            .unwrap();
+        storage.inner.flush_chunk(&chunk).await.unwrap();
+    }
+    let route_of = |config: &Arc<Config>, papers| {
+        let state = Global::new(
+            config.clone(),
+            papers,
+            dmds::world! {
+                Arc::new(FaultyHandle::<MemStorage>::default()),
+                config.layout.questions_items_per_chunk() => ..=u64::MAX,
+            },
+            dmds::world! {
+                Arc::new(FaultyHandle::<MemStorage>::default()),
+                crate::audit::ITEMS_PER_CHUNK => ..=u64::MAX,
+            },
+        );
+        crate::routes(&state).with_state(state)
+    };
+    let send = |route: &Router, uri: &str, body: Option<String>| {
+        let req = Request::builder().uri(uri);
+        let req = match body {
+            Some(body) => req
+                .method(http::Method::POST)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(body)),
+            None => req.body(Body::empty()),
+        };
+        let route = route.clone();
+        async move {
+            let res = route.oneshot(req.unwrap()).await.unwrap();
+            let status = res.status();
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).ok(),
+            )
+        }
+    };
+    let code = |(status, body): (http::StatusCode, Option<serde_json::Value>)| {
+        (status, body.unwrap()["code"].as_str().unwrap().to_owned())
+    };
+    let route = route_of(&config, world());
+
+    // failed reads are reported, rather than as missing papers
+    storage.reads.fail_nth(1);
+    assert_eq!(
+        code(send(&route, "/paper/get", None).await),
+        (http::StatusCode::INTERNAL_SERVER_ERROR, "db".to_owned())
+    );
+    let (status, body) = send(&route, "/paper/get", None).await;
+    assert!(status.is_success());
+    assert_eq!(body.unwrap()["pid"], 1);
+
+    storage.reads.fail_nth(1);
+    assert_eq!(
+        code(send(&route, "/paper/get/1", None).await),
+        (http::StatusCode::INTERNAL_SERVER_ERROR, "db".to_owned())
+    );
+    assert!(send(&route, "/paper/get/1", None).await.0.is_success());
+
+    let approve = serde_json::to_string(&paper::ApprRejReq {
+        pid: Pid(2),
+        rev: None,
+    })
+    .unwrap();
+    storage.reads.fail_nth(1);
+    assert_eq!(
+        code(send(&route, "/secret/approve_papers", Some(approve.clone())).await),
+        (http::StatusCode::INTERNAL_SERVER_ERROR, "db".to_owned())
+    );
+    assert!(send(&route, "/secret/approve_papers", Some(approve))
+        .await
+        .0
+        .is_success());
+
+    // compaction stops at the first failed write
+    let writes = storage.writes.count();
+    storage.writes.fail_nth(2);
+    assert_eq!(
+        send(&route, "/secret/compact", Some(String::new())).await.0,
+        http::StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(storage.writes.count(), writes + 2);
+
+    // compaction rewrites chunks not buffered by the world
+    let unbuffered = Arc::new(FaultyHandle::<MemStorage>::default());
+    let papers = || {
+        dmds::world! {
+            unbuffered.clone(),
+            items_per_chunk => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        }
+    };
+    let stored = papers();
+    let mut paper: paper::Paper = paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact 3".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    }
+    .into_paper(&config)
+    .unwrap();
+    paper.pid = Pid(3);
+    stored.insert(paper).await.unwrap();
+    let chunk = stored
+        .chunk_buf_of_pos([0, paper::Status::Pending.as_dim() as usize])
+        .unwrap();
+    unbuffered.inner.flush_chunk(&chunk).await.unwrap();
+    let route = route_of(&config, papers());
+    let (status, body) = send(&route, "/secret/compact", Some(String::new())).await;
+    assert!(status.is_success());
+    let res: compact::CompactRes = serde_json::from_value(body.unwrap()).unwrap();
+    assert_eq!(res.chunks, 1);
+    assert_eq!(unbuffered.writes.count(), 1);
+
+    // posting never loads chunks failed to read as empty ones,
+    // where nothing is buffered so new papers are always read first,
+    // and failed posts don't count for email cooldowns
+    let fresh = Arc::new(FaultyHandle::<MemStorage>::default());
+    let route = route_of(
+        &config,
+        dmds::world! {
+            fresh.clone(),
+            items_per_chunk => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        },
+    );
+    let post = serde_json::to_string(&paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact".to_owned(),
+        email: Some("yjn024@example.com".parse().unwrap()),
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    })
+    .unwrap();
+    fresh.reads.fail_nth(1);
+    assert_eq!(
+        code(send(&route, "/paper/post", Some(post.clone())).await),
+        (http::StatusCode::SERVICE_UNAVAILABLE, "unsaved".to_owned())
+    );
+    assert!(send(&route, "/paper/post", Some(post)).await.0.is_success());
+
+    // imports never load chunks failed to read as empty ones either
+    let imported = Arc::new(FaultyHandle::<MemStorage>::default());
+    let route = route_of(
+        &config,
+        dmds::world! {
+            imported.clone(),
+            items_per_chunk => ..=u64::MAX,
+            1 => ..=paper::Status::max_dim(),
+        },
+    );
+    let entries = serde_json::json!([{
+        "author": "Yjn024",
+        "text": "Hello, world!",
+        "approved": true,
+        "created": "2023-01-01T00:00:00Z",
+    }])
+    .to_string();
+    imported.reads.fail_nth(1);
+    let (status, body) = send(&route, "/secret/import_legacy", Some(entries.clone())).await;
+    assert!(status.is_success());
+    let res: paper::ImportRes = serde_json::from_value(body.unwrap()).unwrap();
+    assert_eq!(res.imported, 0);
+    assert_eq!(res.skipped.len(), 1);
+    assert_eq!(res.skipped[0].index, 0);
+    let (_, body) = send(&route, "/secret/import_legacy", Some(entries)).await;
+    let res: paper::ImportRes = serde_json::from_value(body.unwrap()).unwrap();
+    assert_eq!(res.imported, 1);
+
+    // failed reads while deduplicating are reported, rather than as no duplicate
+    let dedup = Arc::new(Config {
+        dedup_window_secs: 60,
+        ..(*config).clone()
+    });
+    let route = route_of(&dedup, world());
+    let post = serde_json::to_string(&paper::In {
+        name: "Yjn024".to_owned(),
+        info: "Genshine Impact 1".to_owned(),
+        email: None,
+        color: "#ffc".to_owned(),
+        image_url: None,
+        submitted_at: None,
+    })
+    .unwrap();
+    storage.reads.fail_nth(1);
+    assert_eq!(
+        code(send(&route, "/paper/post", Some(post.clone())).await),
+        (http::StatusCode::INTERNAL_SERVER_ERROR, "db".to_owned())
+    );
+    assert_eq!(
+        code(send(&route, "/paper/post", Some(post)).await),
+        (http::StatusCode::CONFLICT, "duplicate".to_owned())
+    );
+}
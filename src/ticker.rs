@@ -0,0 +1,148 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+};
+use dmds::IoHandle;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::{paper, pid::PidFormat, Global};
+
+/// Capacity of the approved papers channel, and the count of
+/// recently approved papers kept for replaying.
+const CAPACITY: usize = 32;
+
+/// Interval of pings sent to clients.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Broadcaster of newly approved papers.
+///
+/// Papers approved in bulk by `approve_all` are not broadcast,
+/// as they would make every client lag behind.
+#[derive(Debug)]
+pub struct Ticker {
+    tx: broadcast::Sender<paper::Out>,
+    /// Recently approved papers, the oldest first.
+    recent: Mutex<VecDeque<paper::Out>>,
+}
+
+impl Default for Ticker {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+            recent: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+        }
+    }
+}
+
+impl Ticker {
+    /// Publishes a newly approved paper.
+    pub fn publish(&self, paper: paper::Out) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(paper.clone());
+        // Sending fails only if there is no subscriber.
+        let _ = self.tx.send(paper);
+    }
+
+    /// Subscribes to approved papers, along with at most `replay`
+    /// recently approved papers, the oldest first.
+    ///
+    /// Recent papers are taken under the same lock as publishing,
+    /// so no paper is missed or duplicated in between.
+    fn subscribe(&self, replay: usize) -> (Vec<paper::Out>, broadcast::Receiver<paper::Out>) {
+        let recent = self.recent.lock().unwrap();
+        let skip = recent.len().saturating_sub(replay);
+        (
+            recent.iter().skip(skip).cloned().collect(),
+            self.tx.subscribe(),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TickerQuery {
+    /// Count of recently approved papers to replay on connect.
+    ///
+    /// Only papers approved since the backend started are replayed.
+    #[serde(default)]
+    pub replay: usize,
+}
+
+/// Streams newly approved papers over a WebSocket, as JSON
+/// texts of [`paper::Out`].
+///
+/// Clients lagging behind are closed rather than blocking approvals,
+/// and should reconnect.
+pub async fn approved<Io: IoHandle>(
+    State(Global { ticker, .. }): State<Global<Io>>,
+    Query(TickerQuery { replay }): Query<TickerQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let (recent, rx) = ticker.subscribe(replay);
+    let format = PidFormat::current();
+    ws.on_upgrade(move |socket| format.scope(serve(socket, recent, rx)))
+}
+
+async fn serve(
+    mut socket: WebSocket,
+    recent: Vec<paper::Out>,
+    mut rx: broadcast::Receiver<paper::Out>,
+) {
+    for paper in recent {
+        if send(&mut socket, &paper).await.is_err() {
+            return;
+        }
+    }
+
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    ping.reset();
+    loop {
+        tokio::select! {
+            paper = rx.recv() => match paper {
+                Ok(paper) => {
+                    if send(&mut socket, &paper).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("closing approved paper ticker lagging by {skipped} papers");
+                    let _ = socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::POLICY,
+                            reason: "lagging behind".into(),
+                        })))
+                        .await;
+                    return;
+                }
+                Err(RecvError::Closed) => return,
+            },
+            msg = socket.recv() => match msg {
+                // pings are answered automatically, and pongs need no handling
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    debug!("approved paper ticker disconnected");
+                    return;
+                }
+                Some(Ok(_)) => {}
+            },
+            _ = ping.tick() => {
+                if socket.send(Message::Ping(Default::default())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, paper: &paper::Out) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(paper).expect("papers should serialize");
+    socket.send(Message::Text(text.into())).await
+}
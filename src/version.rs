@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::json::Json;
+
+/// Build and version information of the backend.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Version {
+    pub version: String,
+    /// Git commit of the build, or `unknown` if built outside a git checkout.
+    pub git_sha: String,
+    pub built_at: Option<DateTime<Utc>>,
+}
+
+/// Gets build and version information.
+pub async fn version() -> Json<Version> {
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_sha: env!("GIT_SHA").to_owned(),
+        built_at: env!("BUILT_AT")
+            .parse()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+    })
+}
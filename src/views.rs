@@ -0,0 +1,62 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use dmds::{IoHandle, StreamExt};
+use tracing::{error, info};
+
+use crate::{pid::Pid, Global};
+
+/// Views of papers accumulated in memory, to avoid a write per fetch.
+#[derive(Debug, Default)]
+pub struct Views {
+    pending: Mutex<HashMap<Pid, u64>>,
+}
+
+impl Views {
+    /// Counts a view of the given paper.
+    pub fn hit(&self, pid: Pid) {
+        *self.pending.lock().unwrap().entry(pid).or_default() += 1;
+    }
+
+    /// Takes all accumulated views.
+    fn take(&self) -> HashMap<Pid, u64> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// Adds accumulated views to papers, returning the count of updated papers.
+///
+/// Views of papers no longer existing are dropped.
+pub async fn flush<Io: IoHandle>(state: &Global<Io>) -> usize {
+    let mut updated = 0;
+    for (pid, views) in state.views.take() {
+        let select = pid.select(&state.papers);
+        let mut iter = select.iter();
+        while let Some(Ok(mut lazy)) = iter.next().await {
+            if lazy.id() != pid {
+                continue;
+            }
+            if let Some(paper) = state.metrics.read(pid.0, lazy.get_mut().await) {
+                paper.views += views;
+                if let Err(err) = lazy.close().await {
+                    error!("failed to update views of paper {pid}: {err}");
+                } else {
+                    updated += 1;
+                }
+                break;
+            }
+        }
+    }
+    updated
+}
+
+/// Flushes accumulated views periodically.
+pub async fn daemon<Io: IoHandle>(state: Global<Io>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let updated = flush(&state).await;
+        if updated > 0 {
+            info!("flushed views of {updated} papers");
+        }
+    }
+}